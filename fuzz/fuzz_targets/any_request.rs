@@ -0,0 +1,10 @@
+#![no_main]
+
+use bytes::Bytes;
+use kafkaesque::formats::decode_request;
+use kafkaesque_fuzz::LIMITS;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = decode_request(Bytes::copy_from_slice(data), LIMITS);
+});
@@ -0,0 +1,9 @@
+#![no_main]
+
+use kafkaesque::formats::ApiKey;
+use kafkaesque_fuzz::decode_unversioned;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = decode_unversioned::<ApiKey>(data);
+});
@@ -0,0 +1,11 @@
+#![no_main]
+
+use kafkaesque::formats::messages::ApiVersionsReq;
+use kafkaesque_fuzz::decode_response;
+use libfuzzer_sys::fuzz_target;
+
+// The hand-written v0 message connections negotiate versions with, unlike the generated
+// `ApiVersionsRequest` that `api_versions_response` covers
+fuzz_target!(|data: &[u8]| {
+    let _ = decode_response::<ApiVersionsReq>(data);
+});
@@ -0,0 +1,9 @@
+#![no_main]
+
+use kafkaesque::formats::messages::CreateTopicsReqV0;
+use kafkaesque_fuzz::decode_response;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = decode_response::<CreateTopicsReqV0>(data);
+});
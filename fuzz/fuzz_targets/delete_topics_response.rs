@@ -0,0 +1,9 @@
+#![no_main]
+
+use kafkaesque::formats::messages::DeleteTopicsReqV0;
+use kafkaesque_fuzz::decode_response;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = decode_response::<DeleteTopicsReqV0>(data);
+});
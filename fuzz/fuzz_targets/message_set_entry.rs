@@ -0,0 +1,11 @@
+#![no_main]
+
+use kafkaesque::formats::records::MessageSetEntry;
+use kafkaesque_fuzz::{decode_unversioned, LIMITS};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Some(entry) = decode_unversioned::<MessageSetEntry>(data) {
+        let _ = entry.flatten(LIMITS);
+    }
+});
@@ -0,0 +1,14 @@
+#![no_main]
+
+use kafkaesque::formats::records::RecordSet;
+use kafkaesque_fuzz::decode_unversioned;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    // Headers are only parsed when read
+    if let Some(records) = decode_unversioned::<RecordSet>(data) {
+        for record in records.records() {
+            let _ = record.headers.parse();
+        }
+    }
+});
@@ -0,0 +1,49 @@
+//! Helpers shared by the fuzz targets, which check that decoders fail with a
+//! [FormatError](kafkaesque::formats::FormatError) on any input instead of panicking or hanging.
+//!
+//! Run a target with `cargo fuzz run <target>` from the repository root, and list them with
+//! `cargo fuzz list`.
+
+use bytes::Bytes;
+use kafkaesque::formats::{ApiVersion, DecodeLimits, Read, RequestMessage, Version};
+
+/// Limits keeping the allocations of a single input small, so that the fuzzer explores
+/// malformed lengths rather than spending its time filling buffers
+pub const LIMITS: DecodeLimits = DecodeLimits {
+    max_frame_size: 1024 * 1024,
+    max_array_length: 1024,
+    max_string_length: 1024,
+};
+
+/// Decodes `data` as a `T` with both [Read::read_from] and [Read::decode], which must agree
+pub fn decode<T: Read + PartialEq + std::fmt::Debug>(data: &[u8], version: Version) -> Option<T> {
+    let version = version.with_limits(LIMITS);
+    let read = futures::executor::block_on(T::read_from(&mut &data[..], version));
+    let decoded = T::decode(&mut Bytes::copy_from_slice(data), version);
+    match (read, decoded) {
+        (Ok(read), Ok(decoded)) => {
+            assert_eq!(read, decoded, "read_from and decode disagree");
+            Some(read)
+        }
+        (Err(_), Err(_)) => None,
+        (read, decoded) => panic!("read_from and decode disagree: {read:?} {decoded:?}"),
+    }
+}
+
+/// Decodes the response to `ReqM` in the version picked by the first byte of `data`
+pub fn decode_response<ReqM>(data: &[u8]) -> Option<ReqM::Response>
+where
+    ReqM: RequestMessage,
+    ReqM::Response: PartialEq + std::fmt::Debug,
+{
+    let (&selector, data) = data.split_first()?;
+    let versions = (ReqM::MAX_VERSION.0 - ReqM::MIN_VERSION.0 + 1) as u8;
+    let api_version = ApiVersion(ReqM::MIN_VERSION.0 + (selector % versions) as i16);
+    decode(data, ReqM::version(api_version))
+}
+
+/// Decodes `data` with the flexible encoding if its first byte is odd
+pub fn decode_unversioned<T: Read + PartialEq + std::fmt::Debug>(data: &[u8]) -> Option<T> {
+    let (&selector, data) = data.split_first()?;
+    decode(data, Version::new(0, selector % 2 == 1))
+}
@@ -0,0 +1,23 @@
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, CodegenError>;
+
+#[derive(Debug, Error)]
+pub enum CodegenError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid schema: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Invalid versions: {0:?}")]
+    InvalidVersions(String),
+    #[error("Unsupported type {ty} of field {field}")]
+    UnsupportedType { field: String, ty: String },
+    #[error("Struct {0} is neither defined inline nor in commonStructs")]
+    UnknownStruct(String),
+    #[error("{0} has no matching request or response schema")]
+    Unpaired(String),
+    #[error("{0} has overrides but no schemas")]
+    UnknownOverride(String),
+    #[error("rustfmt failed: {0}")]
+    Rustfmt(String),
+}
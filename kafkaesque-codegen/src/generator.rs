@@ -0,0 +1,492 @@
+use crate::errors::{CodegenError, Result};
+use crate::overrides::RequestOverrides;
+use crate::schema::{FieldSpec, MessageKind, MessageSpec, StructSpec, Versions};
+use std::collections::{BTreeSet, HashSet};
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::process::{Command, Stdio};
+
+const HEADER: &str = "// Generated by kafkaesque-codegen. Do not edit by hand.\n";
+
+/// Generates the module holding the request and response of one API
+pub fn generate_api(
+    request: &MessageSpec,
+    response: &MessageSpec,
+    overrides: RequestOverrides,
+) -> Result<String> {
+    let mut imports = BTreeSet::new();
+    let mut structs = Vec::new();
+    for spec in [request, response] {
+        let mut generator = MessageGenerator {
+            spec,
+            overrides,
+            flexible_versions: Versions::parse(&spec.flexible_versions)?,
+            imports: &mut imports,
+            structs: Vec::new(),
+            generated: HashSet::new(),
+        };
+        generator.generate()?;
+        structs.extend(generator.structs);
+    }
+
+    let mut out = String::from(HEADER);
+    out.push('\n');
+    out.push_str("use crate::formats::codec::{Arbitrary, Read, Write};\n");
+    out.push_str("use crate::formats::request::RequestMessage;\n");
+    for import in imports {
+        writeln!(out, "use {import};").unwrap();
+    }
+    for s in structs {
+        out.push('\n');
+        out.push_str(&s);
+    }
+    rustfmt(&out)
+}
+
+/// Generates the `mod.rs` declaring and re-exporting every API module, given as the module name
+/// and the name of its request, and testing that the messages of each API round trip
+pub fn generate_mod(modules: &[(String, String)]) -> Result<String> {
+    let mut out = String::from(HEADER);
+    out.push('\n');
+    for (module, _) in modules {
+        writeln!(out, "mod {module};").unwrap();
+    }
+    out.push('\n');
+    for (module, _) in modules {
+        writeln!(out, "pub use {module}::*;").unwrap();
+    }
+    out.push_str("\n#[cfg(test)]\nmod tests {\n");
+    out.push_str("use super::*;\n");
+    out.push_str("use crate::formats::arbitrary::assert_messages_round_trip;\n");
+    for (module, request) in modules {
+        writeln!(
+            out,
+            "\n#[test]\nfn test_{module}_round_trip() {{\nassert_messages_round_trip::<{request}>();\n}}"
+        )
+        .unwrap();
+    }
+    out.push_str("}\n");
+    rustfmt(&out)
+}
+
+struct MessageGenerator<'a> {
+    spec: &'a MessageSpec,
+    overrides: RequestOverrides,
+    flexible_versions: Option<Versions>,
+    imports: &'a mut BTreeSet<&'static str>,
+    /// Source of every struct, the message's own first
+    structs: Vec<String>,
+    generated: HashSet<String>,
+}
+
+impl<'a> MessageGenerator<'a> {
+    fn generate(&mut self) -> Result<()> {
+        let spec = self.spec;
+        let valid_versions = Versions::parse(&spec.valid_versions)?
+            .ok_or_else(|| CodegenError::InvalidVersions(spec.valid_versions.clone()))?;
+
+        let mut attributes = Vec::new();
+        if spec.kind == MessageKind::Request {
+            let versions = match valid_versions.max {
+                Some(max) if max == valid_versions.min => format!("version = {max}"),
+                Some(max) => format!("versions = \"{}-{max}\"", valid_versions.min),
+                None => return Err(CodegenError::InvalidVersions(spec.valid_versions.clone())),
+            };
+            let response = format!("{}Response", spec.api_name());
+            let expects_response = match self.overrides.expects_response {
+                Some(expr) => format!(", expects_response = \"{expr}\""),
+                None => String::new(),
+            };
+            attributes.push(format!(
+                "#[request_message({versions}, key = \"{}\", response = \"{response}\"{expects_response})]",
+                spec.api_name()
+            ));
+        }
+        if let Some(flexible) = self.flexible_versions {
+            attributes.push(format!("#[kafka(flexible = \"{flexible}\")]"));
+        }
+
+        let doc = format!(
+            "{} {} (API key {}), versions {valid_versions}",
+            spec.api_name(),
+            match spec.kind {
+                MessageKind::Request => "request",
+                MessageKind::Response => "response",
+                MessageKind::Header | MessageKind::Data => "data",
+            },
+            spec.api_key
+        );
+        self.generate_struct(
+            &spec.name,
+            Some(&doc),
+            &attributes,
+            &spec.fields,
+            valid_versions,
+        )
+    }
+
+    fn generate_struct(
+        &mut self,
+        name: &str,
+        doc: Option<&str>,
+        attributes: &[String],
+        fields: &[FieldSpec],
+        versions: Versions,
+    ) -> Result<()> {
+        if !self.generated.insert(name.to_string()) {
+            return Ok(());
+        }
+        // Reserve the struct's position so that it precedes the structs of its fields
+        let index = self.structs.len();
+        self.structs.push(String::new());
+
+        let mut body = String::new();
+        let mut defaults = Vec::new();
+        for field in fields {
+            let Some(declared_versions) = Versions::parse(&field.versions)? else {
+                continue;
+            };
+            let Some(field_versions) = declared_versions.intersect(&versions) else {
+                continue;
+            };
+            let ty = self.field_type(field, field_versions)?;
+            let ident = field_name(&field.name);
+
+            let mut kafka = Vec::new();
+            match field.tag {
+                Some(tag) => {
+                    kafka.push(format!("tagged = {tag}"));
+                    let declared_tagged_versions = match &field.tagged_versions {
+                        Some(tagged_versions) => Versions::parse(tagged_versions)?,
+                        None => Some(declared_versions),
+                    };
+                    let flexible_versions = self
+                        .flexible_versions
+                        .and_then(|flexible| flexible.intersect(&versions));
+                    if let (Some(declared_tagged_versions), Some(flexible_versions)) =
+                        (declared_tagged_versions, flexible_versions)
+                    {
+                        let tagged_versions = declared_tagged_versions.intersect(&field_versions);
+                        if !tagged_versions.is_some_and(|v| v.covers(&flexible_versions)) {
+                            kafka.push(format!("versions = \"{declared_tagged_versions}\""));
+                        }
+                    }
+                }
+                None if !field_versions.covers(&versions) => {
+                    kafka.push(format!("versions = \"{declared_versions}\""))
+                }
+                None => {}
+            }
+            let default = field.explicit_default();
+            if let Some(default) = &default {
+                if !is_literal(default) {
+                    return Err(CodegenError::UnsupportedType {
+                        field: field.name.clone(),
+                        ty: format!("{} with default {default}", field.ty),
+                    });
+                }
+                kafka.push(format!("default = \"{default}\""));
+            }
+            defaults.push((ident.clone(), default));
+
+            if let Some(about) = field.about.as_deref().map(str::trim) {
+                if !about.is_empty() {
+                    writeln!(body, "    /// {about}").unwrap();
+                }
+            }
+            if !kafka.is_empty() {
+                writeln!(body, "    #[kafka({})]", kafka.join(", ")).unwrap();
+            }
+            writeln!(body, "    pub {ident}: {ty},").unwrap();
+        }
+
+        let has_explicit_defaults = defaults.iter().any(|(_, default)| default.is_some());
+        let mut derives = vec!["Debug", "Clone", "PartialEq"];
+        if !has_explicit_defaults {
+            derives.push("Default");
+        }
+        derives.extend(["Read", "Write", "Arbitrary"]);
+        if index == 0 && self.spec.kind == MessageKind::Request {
+            derives.push("RequestMessage");
+        }
+
+        let mut out = String::new();
+        if let Some(doc) = doc {
+            writeln!(out, "/// {doc}").unwrap();
+        }
+        writeln!(out, "#[derive({})]", derives.join(", ")).unwrap();
+        for attribute in attributes {
+            writeln!(out, "{attribute}").unwrap();
+        }
+        writeln!(out, "pub struct {name} {{\n{body}}}").unwrap();
+
+        if has_explicit_defaults {
+            writeln!(out, "\nimpl Default for {name} {{").unwrap();
+            writeln!(out, "    fn default() -> Self {{").unwrap();
+            writeln!(out, "        {name} {{").unwrap();
+            for (ident, default) in defaults {
+                let default = default.unwrap_or_else(|| "Default::default()".to_string());
+                writeln!(out, "            {ident}: {default},").unwrap();
+            }
+            writeln!(out, "        }}\n    }}\n}}").unwrap();
+        }
+
+        self.structs[index] = out;
+        Ok(())
+    }
+
+    /// Rust type of a field, generating the structs it refers to
+    fn field_type(&mut self, field: &FieldSpec, versions: Versions) -> Result<String> {
+        let unsupported = || CodegenError::UnsupportedType {
+            field: field.name.clone(),
+            ty: field.ty.clone(),
+        };
+        let nullable = match &field.nullable_versions {
+            Some(nullable_versions) => Versions::parse(nullable_versions)?
+                .and_then(|nullable_versions| nullable_versions.intersect(&versions))
+                .is_some(),
+            None => false,
+        };
+
+        let (element, is_array) = match field.ty.strip_prefix("[]") {
+            Some(element) => (element, true),
+            None => (field.ty.as_str(), false),
+        };
+        let ty = match element {
+            "bool" => "bool".to_string(),
+            "int8" => "i8".to_string(),
+            "int16" if !is_array && field.name.ends_with("ErrorCode") => {
+                self.imports.insert("crate::formats::ErrorCode");
+                "ErrorCode".to_string()
+            }
+            "int16" => "i16".to_string(),
+            "int32" => "i32".to_string(),
+            "int64" => "i64".to_string(),
+            "float64" => "f64".to_string(),
+            "uuid" => {
+                self.imports.insert("uuid::Uuid");
+                "Uuid".to_string()
+            }
+            "string" if nullable && !is_array => {
+                self.imports.insert("crate::formats::NullableString");
+                "NullableString".to_string()
+            }
+            "string" => "String".to_string(),
+            "bytes" if !is_array => {
+                self.imports.insert("bytes::Bytes");
+                if nullable {
+                    "Option<Bytes>".to_string()
+                } else {
+                    "Bytes".to_string()
+                }
+            }
+            "records" => {
+                self.imports.insert("crate::formats::records::RecordSet");
+                "RecordSet".to_string()
+            }
+            name if name.starts_with(|c: char| c.is_ascii_uppercase()) => {
+                self.struct_type(field, name, versions)?
+            }
+            _ => return Err(unsupported()),
+        };
+        Ok(match (is_array, nullable) {
+            (true, true) => format!("Option<Vec<{ty}>>"),
+            (true, false) => format!("Vec<{ty}>"),
+            (false, _) => ty,
+        })
+    }
+
+    fn struct_type(&mut self, field: &FieldSpec, name: &str, versions: Versions) -> Result<String> {
+        let struct_name = if name.starts_with(&self.spec.name) {
+            name.to_string()
+        } else {
+            format!("{}{name}", self.spec.name)
+        };
+        let fields = if field.fields.is_empty() {
+            let common: &StructSpec = self
+                .spec
+                .common_structs
+                .iter()
+                .find(|s| s.name == name)
+                .ok_or_else(|| CodegenError::UnknownStruct(name.to_string()))?;
+            &common.fields
+        } else {
+            &field.fields
+        };
+        self.generate_struct(&struct_name, None, &[], fields, versions)?;
+        Ok(struct_name)
+    }
+}
+
+/// snake_case name of a schema field, avoiding Rust keywords
+fn field_name(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut out = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_ascii_uppercase() && i > 0 {
+            let prev = chars[i - 1];
+            let next_is_lower = chars.get(i + 1).is_some_and(|n| n.is_ascii_lowercase());
+            if prev.is_ascii_lowercase()
+                || prev.is_ascii_digit()
+                || (prev.is_ascii_uppercase() && next_is_lower)
+            {
+                out.push('_');
+            }
+        }
+        out.push(c.to_ascii_lowercase());
+    }
+    if matches!(
+        out.as_str(),
+        "as" | "async"
+            | "await"
+            | "break"
+            | "const"
+            | "crate"
+            | "enum"
+            | "fn"
+            | "impl"
+            | "in"
+            | "loop"
+            | "match"
+            | "mod"
+            | "move"
+            | "ref"
+            | "self"
+            | "static"
+            | "struct"
+            | "trait"
+            | "type"
+            | "use"
+            | "where"
+    ) {
+        out.push('_');
+    }
+    out
+}
+
+fn is_literal(default: &str) -> bool {
+    default == "true"
+        || default == "false"
+        || default
+            .strip_prefix('-')
+            .unwrap_or(default)
+            .parse::<u64>()
+            .is_ok()
+        || default
+            .strip_prefix("0x")
+            .is_some_and(|hex| u64::from_str_radix(hex, 16).is_ok())
+}
+
+fn rustfmt(source: &str) -> Result<String> {
+    let rustfmt = std::env::var("RUSTFMT").unwrap_or_else(|_| "rustfmt".to_string());
+    let mut child = Command::new(rustfmt)
+        .args(["--edition", "2021"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    child
+        .stdin
+        .take()
+        .expect("piped stdin")
+        .write_all(source.as_bytes())?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(CodegenError::Rustfmt(
+            String::from_utf8_lossy(&output.stderr).into_owned(),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_field_name() {
+        assert_eq!(field_name("ThrottleTimeMs"), "throttle_time_ms");
+        assert_eq!(field_name("ApiKey"), "api_key");
+        assert_eq!(field_name("IsrNodes"), "isr_nodes");
+        assert_eq!(
+            field_name("ClusterAuthorizedOperations"),
+            "cluster_authorized_operations"
+        );
+        assert_eq!(field_name("Type"), "type_");
+    }
+
+    #[test]
+    fn test_generated_struct() {
+        let request = MessageSpec::parse(
+            r#"{
+              "apiKey": 18, "type": "request", "name": "ApiVersionsRequest",
+              "validVersions": "0-3", "flexibleVersions": "3+",
+              "fields": [
+                { "name": "ClientSoftwareName", "type": "string", "versions": "3+",
+                  "about": "The name of the client." }
+              ]
+            }"#,
+        )
+        .unwrap();
+        let response = MessageSpec::parse(
+            r#"{
+              "apiKey": 18, "type": "response", "name": "ApiVersionsResponse",
+              "validVersions": "0-3", "flexibleVersions": "3+",
+              "fields": [
+                { "name": "ErrorCode", "type": "int16", "versions": "0+" },
+                { "name": "ApiKeys", "type": "[]ApiVersion", "versions": "0+", "fields": [
+                  { "name": "ApiKey", "type": "int16", "versions": "0+" }
+                ]},
+                { "name": "Epoch", "type": "int64", "versions": "3+", "tag": 1,
+                  "taggedVersions": "3+", "default": "-1" },
+                { "name": "TopicId", "type": "uuid", "versions": "0+" },
+                { "name": "Quota", "type": "float64", "versions": "0+" },
+                { "name": "Metadata", "type": "bytes", "versions": "0+", "nullableVersions": "0+" },
+                { "name": "Names", "type": "[]string", "versions": "0+", "nullableVersions": "0+" }
+              ]
+            }"#,
+        )
+        .unwrap();
+        let generated = generate_api(&request, &response, RequestOverrides::default()).unwrap();
+        for expected in [
+            "    versions = \"0-3\",\n    key = \"ApiVersions\",\n    response = \"ApiVersionsResponse\"\n",
+            "#[kafka(flexible = \"3+\")]",
+            "    #[kafka(versions = \"3+\")]\n    pub client_software_name: String,",
+            "    pub error_code: ErrorCode,",
+            "    pub api_keys: Vec<ApiVersionsResponseApiVersion>,",
+            "    #[kafka(tagged = 1, default = \"-1\")]\n    pub epoch: i64,",
+            "impl Default for ApiVersionsResponse {",
+            "pub struct ApiVersionsResponseApiVersion {\n    pub api_key: i16,\n}",
+            "use bytes::Bytes;",
+            "use uuid::Uuid;",
+            "    pub topic_id: Uuid,",
+            "    pub quota: f64,",
+            "    pub metadata: Option<Bytes>,",
+            "    pub names: Option<Vec<String>>,",
+            "#[derive(Debug, Clone, PartialEq, Read, Write, Arbitrary)]\n#[kafka(flexible = \"3+\")]\npub struct ApiVersionsResponse {",
+        ] {
+            assert!(generated.contains(expected), "{expected}\n\n{generated}");
+        }
+        assert!(!generated.contains("expects_response"));
+
+        let overrides = RequestOverrides {
+            expects_response: Some("self.client_software_name.is_empty()"),
+        };
+        let generated = generate_api(&request, &response, overrides).unwrap();
+        let expected = "    response = \"ApiVersionsResponse\",\n    expects_response = \"self.client_software_name.is_empty()\"\n";
+        assert!(generated.contains(expected), "{expected}\n\n{generated}");
+    }
+
+    #[test]
+    fn test_generated_mod() {
+        let modules = [("api_versions".to_string(), "ApiVersionsRequest".to_string())];
+        let generated = generate_mod(&modules).unwrap();
+        for expected in [
+            "mod api_versions;",
+            "pub use api_versions::*;",
+            "    fn test_api_versions_round_trip() {\n        assert_messages_round_trip::<ApiVersionsRequest>();\n    }",
+        ] {
+            assert!(generated.contains(expected), "{expected}\n\n{generated}");
+        }
+    }
+}
@@ -0,0 +1,146 @@
+//! Generates `kafkaesque::formats::messages::generated` from the copies of Apache Kafka's JSON
+//! message schemas (`clients/src/main/resources/common/message/*.json`) vendored in `schemas/`.
+//!
+//! Run `cargo run -p kafkaesque-codegen` after adding or updating a schema, or pass `--check` to
+//! fail if the checked-in code is out of date instead of rewriting it.
+
+mod errors;
+mod generator;
+mod overrides;
+mod schema;
+
+use errors::{CodegenError, Result};
+use schema::{MessageKind, MessageSpec};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let check = std::env::args().any(|arg| arg == "--check");
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let schemas = root.join("schemas");
+    let output = root.join("../kafkaesque/src/formats/messages/generated");
+
+    let result = generate(&schemas).and_then(|files| {
+        if check {
+            Ok(outdated(&output, &files)?)
+        } else {
+            write(&output, &files)?;
+            Ok(vec![])
+        }
+    });
+    match result {
+        Ok(outdated) if outdated.is_empty() => ExitCode::SUCCESS,
+        Ok(outdated) => {
+            for path in outdated {
+                eprintln!("{} is out of date", path.display());
+            }
+            eprintln!("Run `cargo run -p kafkaesque-codegen` to regenerate it");
+            ExitCode::FAILURE
+        }
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Generates the content of every file of the generated module, by file name
+fn generate(schemas: &Path) -> Result<BTreeMap<String, String>> {
+    let mut apis: BTreeMap<String, (Option<MessageSpec>, Option<MessageSpec>)> = BTreeMap::new();
+    let mut paths: Vec<PathBuf> = fs::read_dir(schemas)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<_>>()?;
+    paths.sort();
+    for path in paths {
+        if path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+        let spec = MessageSpec::parse(&fs::read_to_string(&path)?)?;
+        let api = apis.entry(spec.api_name().to_string()).or_default();
+        match spec.kind {
+            MessageKind::Request => api.0 = Some(spec),
+            MessageKind::Response => api.1 = Some(spec),
+            MessageKind::Header | MessageKind::Data => {}
+        }
+    }
+
+    if let Some(api) = overrides::overridden_apis().find(|api| !apis.contains_key(*api)) {
+        return Err(CodegenError::UnknownOverride(api.to_string()));
+    }
+
+    let mut files = BTreeMap::new();
+    let mut modules = Vec::new();
+    for (name, api) in apis {
+        let (Some(request), Some(response)) = api else {
+            return Err(CodegenError::Unpaired(name));
+        };
+        let module = module_name(&name);
+        files.insert(
+            format!("{module}.rs"),
+            generator::generate_api(&request, &response, overrides::request_overrides(&name))?,
+        );
+        modules.push((module, request.name.clone()));
+    }
+    files.insert("mod.rs".to_string(), generator::generate_mod(&modules)?);
+    Ok(files)
+}
+
+fn write(output: &Path, files: &BTreeMap<String, String>) -> Result<()> {
+    fs::create_dir_all(output)?;
+    for (name, content) in files {
+        fs::write(output.join(name), content)?;
+    }
+    Ok(())
+}
+
+/// Paths of the generated files whose content differs from `files`
+fn outdated(output: &Path, files: &BTreeMap<String, String>) -> Result<Vec<PathBuf>> {
+    let mut outdated = Vec::new();
+    for (name, content) in files {
+        let path = output.join(name);
+        if fs::read_to_string(&path).ok().as_ref() != Some(content) {
+            outdated.push(path);
+        }
+    }
+    Ok(outdated)
+}
+
+/// snake_case module name of an API such as "ApiVersions"
+fn module_name(api: &str) -> String {
+    let mut module = String::new();
+    for (i, c) in api.chars().enumerate() {
+        if c.is_ascii_uppercase() && i > 0 {
+            module.push('_');
+        }
+        module.push(c.to_ascii_lowercase());
+    }
+    module
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_code_is_up_to_date() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let files = generate(&root.join("schemas")).unwrap();
+        let outdated = outdated(
+            &root.join("../kafkaesque/src/formats/messages/generated"),
+            &files,
+        )
+        .unwrap();
+        assert!(
+            outdated.is_empty(),
+            "run `cargo run -p kafkaesque-codegen` to regenerate {outdated:?}"
+        );
+    }
+
+    #[test]
+    fn test_module_name() {
+        assert_eq!(module_name("ApiVersions"), "api_versions");
+        assert_eq!(module_name("Metadata"), "metadata");
+    }
+}
@@ -0,0 +1,30 @@
+//! What the JSON schemas don't say about the generated messages, by API name
+
+/// Attributes added to the `#[request_message]` of an API's request
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RequestOverrides {
+    /// Expression of the request, as `self`, telling whether brokers respond to it
+    pub expects_response: Option<&'static str>,
+}
+
+const REQUEST_OVERRIDES: &[(&str, RequestOverrides)] = &[(
+    // Brokers don't answer produce requests that ask for no acknowledgments
+    "Produce",
+    RequestOverrides {
+        expects_response: Some("self.acks != 0"),
+    },
+)];
+
+/// Overrides of the request of `api`, which are the defaults for most APIs
+pub fn request_overrides(api: &str) -> RequestOverrides {
+    REQUEST_OVERRIDES
+        .iter()
+        .find(|(name, _)| *name == api)
+        .map(|&(_, overrides)| overrides)
+        .unwrap_or_default()
+}
+
+/// Names of the APIs with overrides
+pub fn overridden_apis() -> impl Iterator<Item = &'static str> {
+    REQUEST_OVERRIDES.iter().map(|&(name, _)| name)
+}
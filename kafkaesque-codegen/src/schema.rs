@@ -0,0 +1,202 @@
+use crate::errors::{CodegenError, Result};
+use serde::Deserialize;
+use std::fmt::{self, Display};
+
+/// A message definition, as in Kafka's `clients/src/main/resources/common/message/*.json`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageSpec {
+    pub api_key: i16,
+    #[serde(rename = "type")]
+    pub kind: MessageKind,
+    pub name: String,
+    pub valid_versions: String,
+    pub flexible_versions: String,
+    #[serde(default)]
+    pub fields: Vec<FieldSpec>,
+    #[serde(default)]
+    pub common_structs: Vec<StructSpec>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MessageKind {
+    Request,
+    Response,
+    Header,
+    Data,
+}
+
+/// A struct shared by several fields of a message
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StructSpec {
+    pub name: String,
+    pub fields: Vec<FieldSpec>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldSpec {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: String,
+    pub versions: String,
+    pub nullable_versions: Option<String>,
+    pub tag: Option<u32>,
+    pub tagged_versions: Option<String>,
+    pub default: Option<serde_json::Value>,
+    pub about: Option<String>,
+    #[serde(default)]
+    pub fields: Vec<FieldSpec>,
+}
+
+impl MessageSpec {
+    /// Parses a schema file, which unlike plain JSON may contain `//` line comments
+    pub fn parse(json: &str) -> Result<Self> {
+        let json: String = json
+            .lines()
+            .filter(|line| !line.trim_start().starts_with("//"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    /// Name of the message without its "Request" or "Response" suffix, which is also the name
+    /// of its `ApiKey`
+    pub fn api_name(&self) -> &str {
+        self.name
+            .strip_suffix("Request")
+            .or_else(|| self.name.strip_suffix("Response"))
+            .unwrap_or(&self.name)
+    }
+}
+
+impl FieldSpec {
+    /// The field's default value, if it differs from the default of its type
+    pub fn explicit_default(&self) -> Option<String> {
+        let default = match self.default.as_ref()? {
+            serde_json::Value::String(s) => s.clone(),
+            value => value.to_string(),
+        };
+        match default.as_str() {
+            "" | "0" | "false" | "null" => None,
+            _ => Some(default),
+        }
+    }
+}
+
+/// An inclusive range of versions, written as "3", "2+" or "0-9"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Versions {
+    pub min: i16,
+    pub max: Option<i16>,
+}
+
+impl Versions {
+    /// Parses a version range, where "none" is [None]
+    pub fn parse(s: &str) -> Result<Option<Self>> {
+        let parse = |n: &str| {
+            n.trim()
+                .parse::<i16>()
+                .map_err(|_| CodegenError::InvalidVersions(s.to_string()))
+        };
+        let s = s.trim();
+        if s == "none" {
+            Ok(None)
+        } else if let Some(min) = s.strip_suffix('+') {
+            Ok(Some(Versions {
+                min: parse(min)?,
+                max: None,
+            }))
+        } else if let Some((min, max)) = s.split_once('-') {
+            Ok(Some(Versions {
+                min: parse(min)?,
+                max: Some(parse(max)?),
+            }))
+        } else {
+            let n = parse(s)?;
+            Ok(Some(Versions {
+                min: n,
+                max: Some(n),
+            }))
+        }
+    }
+
+    /// Whether every version in `other` is also in this range
+    pub fn covers(&self, other: &Versions) -> bool {
+        self.min <= other.min
+            && match (self.max, other.max) {
+                (None, _) => true,
+                (Some(_), None) => false,
+                (Some(max), Some(other_max)) => max >= other_max,
+            }
+    }
+
+    /// Versions in both ranges, if there are any
+    pub fn intersect(&self, other: &Versions) -> Option<Versions> {
+        let min = self.min.max(other.min);
+        let max = match (self.max, other.max) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        match max {
+            Some(max) if max < min => None,
+            _ => Some(Versions { min, max }),
+        }
+    }
+}
+
+impl Display for Versions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.max {
+            None => write!(f, "{}+", self.min),
+            Some(max) if max == self.min => write!(f, "{max}"),
+            Some(max) => write!(f, "{}-{max}", self.min),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_versions() {
+        let v = |s| Versions::parse(s).unwrap().unwrap();
+        assert_eq!(v("3+"), Versions { min: 3, max: None });
+        assert_eq!(v("0-9").to_string(), "0-9");
+        assert_eq!(v("4").to_string(), "4");
+        assert_eq!(Versions::parse("none").unwrap(), None);
+        assert!(Versions::parse("x+").is_err());
+
+        assert!(v("0+").covers(&v("3-5")));
+        assert!(!v("0-4").covers(&v("3-5")));
+        assert!(!v("1-9").covers(&v("0+")));
+        assert_eq!(v("3+").intersect(&v("0-5")), Some(v("3-5")));
+        assert_eq!(v("0-2").intersect(&v("3+")), None);
+    }
+
+    #[test]
+    fn test_parse_with_comments() {
+        let spec = MessageSpec::parse(
+            r#"// Licensed to the Apache Software Foundation (ASF)
+            {
+              "apiKey": 18,
+              "type": "request",
+              // Version 3 is the first flexible version.
+              "name": "ApiVersionsRequest",
+              "validVersions": "0-3",
+              "flexibleVersions": "3+",
+              "fields": [
+                { "name": "ClientSoftwareName", "type": "string", "versions": "3+",
+                  "ignorable": true, "about": "The name of the client." }
+              ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(spec.kind, MessageKind::Request);
+        assert_eq!(spec.api_name(), "ApiVersions");
+        assert_eq!(spec.fields[0].ty, "string");
+    }
+}
@@ -0,0 +1,157 @@
+use crate::versions::{contains, flexible_version, VersionRange};
+use darling::{
+    ast::{Data, Style},
+    FromDeriveInput, FromField,
+};
+use proc_macro::TokenStream;
+use quote::{format_ident, quote};
+use syn::{parse, parse_macro_input, DeriveInput, Ident, Type, Variant};
+
+#[derive(FromDeriveInput, Debug)]
+#[darling(attributes(kafka))]
+#[darling(supports(struct_named, struct_newtype, struct_unit))]
+struct Params {
+    ident: syn::Ident,
+    data: darling::ast::Data<Variant, StructField>,
+    generics: syn::Generics,
+    /// Versions in which the struct and everything nested in it use the flexible encoding
+    flexible: Option<VersionRange>,
+}
+
+#[derive(Debug, Clone, FromField)]
+#[darling(attributes(kafka))]
+struct StructField {
+    ident: Option<Ident>,
+    ty: Type,
+    /// Versions in which the field is present
+    versions: Option<VersionRange>,
+    /// Tag of a field only present in the tagged fields section of flexible versions
+    tagged: Option<u32>,
+    /// Value of the field in versions where it is absent, instead of [Default::default]
+    default: Option<syn::Expr>,
+}
+
+impl StructField {
+    fn default_value(&self) -> proc_macro2::TokenStream {
+        let ty = &self.ty;
+        match &self.default {
+            Some(default) => quote! { (#default) },
+            None => quote! { <#ty as Default>::default() },
+        }
+    }
+
+    /// Strategy generating the field in `version`, which only generates its default value in
+    /// versions where the field is absent, as those are the only values surviving a round trip
+    fn strategy(&self) -> proc_macro2::TokenStream {
+        let ty = &self.ty;
+        let version = quote! { version };
+        let arbitrary = quote! {
+            <#ty as crate::formats::arbitrary::Arbitrary>::arbitrary(version)
+        };
+        let present = match (self.tagged, self.versions) {
+            (Some(_), versions) => {
+                let contained = contains(versions, &version);
+                quote! { (version.flexible && #contained) }
+            }
+            (None, Some(versions)) => versions.contains(&version),
+            (None, None) => return arbitrary,
+        };
+        let default = self.default_value();
+        quote! {
+            if #present {
+                #arbitrary
+            } else {
+                proptest::strategy::Strategy::boxed(
+                    proptest::strategy::LazyJust::new(|| -> #ty { #default })
+                )
+            }
+        }
+    }
+}
+
+pub fn expand(ts: TokenStream) -> TokenStream {
+    let derive_input = parse_macro_input!(ts as DeriveInput);
+    let params = match Params::from_derive_input(&derive_input) {
+        Ok(params) => params,
+        Err(err) => return err.write_errors().into(),
+    };
+
+    let generics = params.generics;
+    let name = params.ident;
+    let fields = match params.data {
+        Data::Struct(fields) => fields,
+        Data::Enum(_) => {
+            return syn::Error::new_spanned(
+                &derive_input.ident,
+                "Arbitrary can only be derived for structs",
+            )
+            .to_compile_error()
+            .into()
+        }
+    };
+
+    let impl_generics = {
+        let mut g = generics.clone();
+        for type_param in g.type_params_mut() {
+            type_param
+                .bounds
+                .push(parse(quote! { crate::formats::arbitrary::Arbitrary }.into()).unwrap());
+        }
+        g
+    };
+
+    let flexible_version = flexible_version(params.flexible);
+
+    // The strategies of the fields are nested in pairs ending with `Just(())`, which avoids
+    // the limit on the size of the tuples proptest implements `Strategy` for
+    let locals = fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| match &field.ident {
+            Some(ident) => format_ident!("field_{}", ident),
+            None => format_ident!("field_{}", i),
+        })
+        .collect::<Vec<_>>();
+    let strategy =
+        fields
+            .iter()
+            .rev()
+            .fold(quote! { proptest::strategy::Just(()) }, |rest, field| {
+                let strategy = field.strategy();
+                quote! { (#strategy, #rest) }
+            });
+    let pattern = locals
+        .iter()
+        .rev()
+        .fold(quote! { () }, |rest, local| quote! { (#local, #rest) });
+    let construction = match fields.style {
+        Style::Tuple => quote! { #name(#(#locals),*) },
+        Style::Struct => {
+            let assignments = fields.iter().zip(&locals).map(|(field, local)| {
+                let f_name = field.ident.as_ref().unwrap();
+                quote! { #f_name: #local }
+            });
+            quote! { #name { #(#assignments),* } }
+        }
+        Style::Unit => quote! { #name },
+    };
+
+    let output = quote! {
+        #[cfg(any(test, feature = "proptest"))]
+        #[automatically_derived]
+        impl #impl_generics crate::formats::arbitrary::Arbitrary for #name #generics {
+            #[allow(unused_variables)]
+            fn arbitrary(
+                version: crate::formats::codec::Version,
+            ) -> proptest::strategy::BoxedStrategy<Self> {
+                #flexible_version
+                proptest::strategy::Strategy::boxed(proptest::strategy::Strategy::prop_map(
+                    #strategy,
+                    |#pattern| #construction,
+                ))
+            }
+        }
+    };
+
+    output.into()
+}
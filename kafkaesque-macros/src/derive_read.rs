@@ -1,20 +1,44 @@
-use darling::{ast::Data, FromDeriveInput, FromField};
+use crate::versions::{contains, flexible_version, VersionRange};
+use darling::{
+    ast::{Data, Fields, Style},
+    FromDeriveInput, FromField,
+};
 use proc_macro::TokenStream;
-use quote::quote;
+use quote::{format_ident, quote};
 use syn::{parse, parse_macro_input, DeriveInput, Ident, Type, Variant};
 
 #[derive(FromDeriveInput, Debug)]
+#[darling(attributes(kafka))]
 #[darling(supports(struct_named, struct_newtype, struct_unit))]
 struct Params {
     ident: syn::Ident,
     data: darling::ast::Data<Variant, StructField>,
     generics: syn::Generics,
+    /// Versions in which the struct and everything nested in it use the flexible encoding
+    flexible: Option<VersionRange>,
 }
 
 #[derive(Debug, Clone, FromField)]
+#[darling(attributes(kafka))]
 struct StructField {
     ident: Option<Ident>,
     ty: Type,
+    /// Versions in which the field is present
+    versions: Option<VersionRange>,
+    /// Tag of a field read from the tagged fields section of flexible versions
+    tagged: Option<u32>,
+    /// Value of the field in versions where it is absent, instead of [Default::default]
+    default: Option<syn::Expr>,
+}
+
+impl StructField {
+    fn default_value(&self) -> proc_macro2::TokenStream {
+        let ty = &self.ty;
+        match &self.default {
+            Some(default) => quote! { (#default) },
+            None => quote! { <#ty as Default>::default() },
+        }
+    }
 }
 
 pub fn expand(ts: TokenStream) -> TokenStream {
@@ -38,31 +62,28 @@ pub fn expand(ts: TokenStream) -> TokenStream {
         g
     };
 
-    let reading: Vec<proc_macro2::TokenStream> = fields
-        .into_iter()
-        .map(|field| {
-            let name = field
-                .ident
-                .clone()
-                .map(|ident| quote! {#ident})
-                .unwrap_or(quote! {0});
-            (name, field.ty)
-        })
-        .map(|(f_name, ty)| {
-            quote! { #f_name: {
-                tracing::trace!("reading {}", stringify!(#name.#f_name));
-                <#ty as Read>::read_from(reader).await? }
-            }
-        })
-        .collect();
+    let flexible_version = flexible_version(params.flexible);
+
+    let body = read_body(&name, &fields, false);
+    let decode_body = read_body(&name, &fields, true);
 
     let output = quote! {
         #[automatically_derived]
         impl #impl_generics crate::formats::codec::Read for #name #generics {
-            async fn read_from(reader: &mut (dyn tokio::io::AsyncRead + Send + Unpin)) -> crate::formats::Result<Self> {
-                let v = #name {
-                    #(#reading),*
-                };
+            async fn read_from(
+                reader: &mut (dyn tokio::io::AsyncRead + Send + Unpin),
+                version: crate::formats::codec::Version,
+            ) -> crate::formats::Result<Self> {
+                #flexible_version
+                #body
+                Ok(v)
+            }
+            fn decode<B: bytes::Buf>(
+                reader: &mut B,
+                version: crate::formats::codec::Version,
+            ) -> crate::formats::Result<Self> {
+                #flexible_version
+                #decode_body
                 Ok(v)
             }
         }
@@ -70,3 +91,108 @@ pub fn expand(ts: TokenStream) -> TokenStream {
 
     output.into()
 }
+
+/// Statements reading every field into `v`, either asynchronously from `reader` in `read_from`
+/// or synchronously from the `reader` buffer in `decode`
+fn read_body(name: &Ident, fields: &Fields<StructField>, sync: bool) -> proc_macro2::TokenStream {
+    let read_from = |ty: &Type, reader: proc_macro2::TokenStream| {
+        if sync {
+            quote! { <#ty as Read>::decode(#reader, version)? }
+        } else {
+            quote! { <#ty as Read>::read_from(#reader, version).await? }
+        }
+    };
+    let read_tagged_fields = read_from(
+        &syn::parse_quote! { crate::formats::TaggedFields },
+        quote! { reader },
+    );
+    let version = quote! { version };
+
+    if fields.style == Style::Tuple {
+        // Newtypes are encoded exactly like the type they wrap
+        let reading = fields.iter().map(|field| {
+            let read = read_from(&field.ty, quote! { reader });
+            quote! { 0: {
+                tracing::trace!("reading {}", stringify!(#name.0));
+                #read }
+            }
+        });
+        quote! {
+            let v = #name {
+                #(#reading),*
+            };
+        }
+    } else {
+        let local = |field: &StructField| format_ident!("field_{}", field.ident.as_ref().unwrap());
+
+        let reading = fields.iter().map(|field| {
+            let f_name = field.ident.as_ref().unwrap();
+            let local = local(field);
+            let ty = &field.ty;
+            let read = read_from(ty, quote! { reader });
+            match (field.tagged, field.versions) {
+                (Some(_), _) => {
+                    let default = field.default_value();
+                    quote! { let mut #local: #ty = #default; }
+                }
+                (None, Some(range)) => {
+                    let present = range.contains(&version);
+                    let default = field.default_value();
+                    quote! {
+                        let #local = if #present {
+                            tracing::trace!("reading {}", stringify!(#name.#f_name));
+                            #read
+                        } else {
+                            #default
+                        };
+                    }
+                }
+                (None, None) => quote! {
+                    tracing::trace!("reading {}", stringify!(#name.#f_name));
+                    let #local = #read;
+                },
+            }
+        });
+
+        let tagged_reading = fields
+            .iter()
+            .filter(|field| field.tagged.is_some())
+            .map(|field| {
+                let f_name = field.ident.as_ref().unwrap();
+                let local = local(field);
+                let read_tagged =
+                    read_from(&field.ty, quote! { &mut tagged_field.data.as_slice() });
+                let tag = field.tagged.expect("tagged field");
+                let present = contains(field.versions, &version);
+                quote! {
+                    #tag if #present => {
+                        tracing::trace!("reading tagged {}", stringify!(#name.#f_name));
+                        #local = #read_tagged;
+                    }
+                }
+            });
+
+        let assignments = fields.iter().map(|field| {
+            let f_name = field.ident.as_ref().unwrap();
+            let local = local(field);
+            quote! { #f_name: #local }
+        });
+
+        quote! {
+            #(#reading)*
+            if version.flexible {
+                let tagged_fields = #read_tagged_fields;
+                #[allow(clippy::never_loop)]
+                for tagged_field in tagged_fields.0 {
+                    match tagged_field.tag {
+                        #(#tagged_reading)*
+                        tag => tracing::trace!("skipping unknown tagged field {tag}"),
+                    }
+                }
+            }
+            let v = #name {
+                #(#assignments),*
+            };
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use crate::versions::VersionRange;
 use darling::FromDeriveInput;
 use proc_macro::TokenStream;
 use proc_macro2::Ident;
@@ -9,23 +10,72 @@ use syn::{parse_macro_input, DeriveInput};
 #[darling(supports(struct_any))]
 struct Params {
     ident: syn::Ident,
-    version: i16,
+    /// Single version of the message
+    version: Option<i16>,
+    /// Range of versions of the message, such as "0-9"
+    versions: Option<VersionRange>,
     key: Ident,
+    response: syn::Path,
+    /// Expression over `self` telling whether brokers respond to the message, such as
+    /// "self.acks != 0"
+    expects_response: Option<syn::Expr>,
+}
+
+/// The struct-level `#[kafka(..)]` attributes shared with the Read and Write derives
+#[derive(FromDeriveInput, Debug)]
+#[darling(attributes(kafka))]
+struct KafkaParams {
+    flexible: Option<VersionRange>,
 }
 
 pub fn expand(ts: TokenStream) -> TokenStream {
     let derive_input = parse_macro_input!(ts as DeriveInput);
     let params = Params::from_derive_input(&derive_input).expect("Failed to parse inputs");
+    let kafka_params =
+        KafkaParams::from_derive_input(&derive_input).expect("Failed to parse kafka attributes");
 
     let name = params.ident;
-    let version = params.version;
+    let (min_version, max_version) = match (params.version, params.versions) {
+        (Some(version), None) => (version, version),
+        (
+            None,
+            Some(VersionRange {
+                min,
+                max: Some(max),
+            }),
+        ) => (min, max),
+        _ => panic!("request_message needs either `version = N` or `versions = \"min-max\"`"),
+    };
     let key = params.key;
+    let response = params.response;
+    let flexible_version = match kafka_params.flexible {
+        Some(range) => {
+            let first = range.min;
+            quote! { Some(crate::formats::request::ApiVersion(#first)) }
+        }
+        None => quote! { None },
+    };
+
+    let expects_response = params.expects_response.map(|expr| {
+        quote! {
+            fn expects_response(&self) -> bool {
+                #expr
+            }
+        }
+    });
 
     let output = quote! {
         #[automatically_derived]
         impl crate::formats::request::RequestMessage for #name {
-            const API_KEY: ApiKey = crate::formats::api_keys::ApiKey::#key;
-            const API_VERSION: ApiVersion = crate::formats::request::ApiVersion(#version);
+            const API_KEY: crate::formats::api_keys::ApiKey = crate::formats::api_keys::ApiKey::#key;
+            const MIN_VERSION: crate::formats::request::ApiVersion =
+                crate::formats::request::ApiVersion(#min_version);
+            const MAX_VERSION: crate::formats::request::ApiVersion =
+                crate::formats::request::ApiVersion(#max_version);
+            const FLEXIBLE_VERSION: Option<crate::formats::request::ApiVersion> = #flexible_version;
+            type Response = #response;
+
+            #expects_response
         }
     };
 
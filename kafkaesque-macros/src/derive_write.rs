@@ -1,21 +1,44 @@
-use darling::{ast::Data, FromDeriveInput, FromField};
+use crate::versions::{contains, flexible_version, VersionRange};
+use darling::{
+    ast::{Data, Style},
+    FromDeriveInput, FromField,
+};
 use proc_macro::TokenStream;
 use quote::quote;
-use std::iter;
-use syn::{parse, parse_macro_input, DeriveInput, Ident, Variant};
+use syn::{parse, parse_macro_input, DeriveInput, Ident, Type, Variant};
 
 #[derive(FromDeriveInput, Debug)]
+#[darling(attributes(kafka))]
 #[darling(supports(struct_named, struct_newtype, struct_unit))]
 struct Params {
     ident: syn::Ident,
     data: darling::ast::Data<Variant, StructField>,
     generics: syn::Generics,
+    /// Versions in which the struct and everything nested in it use the flexible encoding
+    flexible: Option<VersionRange>,
 }
 
 #[derive(Debug, Clone, FromField)]
+#[darling(attributes(kafka))]
 struct StructField {
     ident: Option<Ident>,
-    // ty: Type,
+    ty: Type,
+    /// Versions in which the field is present
+    versions: Option<VersionRange>,
+    /// Tag of a field written in the tagged fields section of flexible versions
+    tagged: Option<u32>,
+    /// Value of the field in versions where it is absent, instead of [Default::default]
+    default: Option<syn::Expr>,
+}
+
+impl StructField {
+    fn default_value(&self) -> proc_macro2::TokenStream {
+        let ty = &self.ty;
+        match &self.default {
+            Some(default) => quote! { (#default) },
+            None => quote! { <#ty as Default>::default() },
+        }
+    }
 }
 
 pub fn expand(ts: TokenStream) -> TokenStream {
@@ -28,6 +51,8 @@ pub fn expand(ts: TokenStream) -> TokenStream {
         Data::Struct(fields) => fields,
         Data::Enum(_) => unimplemented!("Unsupported"),
     };
+    // Newtypes are encoded exactly like the type they wrap
+    let has_tagged_section = fields.style != Style::Tuple;
 
     let impl_generics = {
         let mut g = generics.clone();
@@ -39,40 +64,168 @@ pub fn expand(ts: TokenStream) -> TokenStream {
         g
     };
 
-    let field_names: Vec<proc_macro2::TokenStream> = fields
+    let version = quote! { version };
+    let flexible_version = flexible_version(params.flexible);
+
+    let (mut tagged_fields, regular_fields): (Vec<_>, Vec<_>) =
+        fields.iter().partition(|field| field.tagged.is_some());
+    tagged_fields.sort_by_key(|field| field.tagged);
+
+    let field_name = |field: &StructField| {
+        field
+            .ident
+            .clone()
+            .map(|ident| quote! {#ident})
+            .unwrap_or(quote! {0})
+    };
+
+    let size_calculation: Vec<proc_macro2::TokenStream> = regular_fields
         .iter()
         .map(|field| {
-            field
-                .ident
-                .clone()
-                .map(|ident| quote! {#ident})
-                .unwrap_or(quote! {0})
+            let name = field_name(field);
+            match field.versions {
+                Some(range) => {
+                    let present = range.contains(&version);
+                    quote! {
+                        if #present {
+                            size += Write::calculate_size(&self.#name, version);
+                        }
+                    }
+                }
+                None => quote! { size += Write::calculate_size(&self.#name, version); },
+            }
         })
         .collect();
 
-    let size_calculation: Vec<proc_macro2::TokenStream> = field_names
-        .iter()
-        .map(|name| {
-            quote! { Write::calculate_size(&self.#name) }
-        })
-        .chain(iter::once(quote! { 0 }))
-        .collect();
+    // Statements writing every field, either asynchronously to `writer` in `write_to` or
+    // synchronously to the `writer` buffer in `encode`
+    let writing = |sync: bool| {
+        let write_to = |value: proc_macro2::TokenStream, writer: proc_macro2::TokenStream| {
+            if sync {
+                quote! { Write::encode(#value, #writer, version)?; }
+            } else {
+                quote! { Write::write_to(#value, #writer, version).await?; }
+            }
+        };
+        let fields = regular_fields.iter().map(|field| {
+            let name = field_name(field);
+            let write = write_to(quote! { &self.#name }, quote! { writer });
+            match field.versions {
+                Some(range) => {
+                    let present = range.contains(&version);
+                    quote! {
+                        if #present {
+                            #write
+                        }
+                    }
+                }
+                None => write,
+            }
+        });
+        let tagged_section = if !has_tagged_section {
+            quote! {}
+        } else if tagged_fields.is_empty() {
+            let write = write_to(
+                quote! { &crate::formats::TaggedFields::default() },
+                quote! { writer },
+            );
+            quote! {
+                if version.flexible {
+                    #write
+                }
+            }
+        } else {
+            let tagged_field_writes = tagged_fields.iter().map(|field| {
+                let name = field_name(field);
+                let tag = field.tagged.expect("tagged field");
+                let present = contains(field.versions, &version);
+                let default = field.default_value();
+                quote! {
+                    if #present && self.#name != #default {
+                        let mut data = Vec::with_capacity(Write::calculate_size(&self.#name, version) as usize);
+                        Write::encode(&self.#name, &mut data, version)?;
+                        tagged_fields.0.push(crate::formats::TaggedField { tag: #tag, data });
+                    }
+                }
+            });
+            let write = write_to(quote! { &tagged_fields }, quote! { writer });
+            quote! {
+                if version.flexible {
+                    let mut tagged_fields = crate::formats::TaggedFields::default();
+                    #(#tagged_field_writes)*
+                    #write
+                }
+            }
+        };
+        quote! {
+            #(#fields)*
+            #tagged_section
+        }
+    };
+    let writing_async = writing(false);
+    let writing_sync = writing(true);
 
-    let writing: Vec<proc_macro2::TokenStream> = field_names
-        .iter()
-        .map(|name| {
-            quote! { Write::write_to(&self.#name, writer).await?;}
-        })
-        .collect();
+    let tagged_size_calculation = if !has_tagged_section {
+        quote! {}
+    } else if tagged_fields.is_empty() {
+        quote! {
+            if version.flexible {
+                size += Write::calculate_size(&crate::formats::TaggedFields::default(), version);
+            }
+        }
+    } else {
+        let tagged_field_sizes = tagged_fields.iter().map(|field| {
+            let name = field_name(field);
+            let tag = field.tagged.expect("tagged field");
+            let present = contains(field.versions, &version);
+            let default = field.default_value();
+            quote! {
+                if #present && self.#name != #default {
+                    tagged_count += 1;
+                    tagged_size += crate::formats::TaggedField::encoded_size(
+                        #tag,
+                        Write::calculate_size(&self.#name, version),
+                    );
+                }
+            }
+        });
+        quote! {
+            if version.flexible {
+                let mut tagged_count = 0u32;
+                let mut tagged_size = 0i32;
+                #(#tagged_field_sizes)*
+                size += Write::calculate_size(&crate::formats::UnsignedVarInt(tagged_count), version)
+                    + tagged_size;
+            }
+        }
+    };
 
     let output = quote! {
         #[automatically_derived]
         impl #impl_generics crate::formats::codec::Write for #name #generics {
-            fn calculate_size(&self) -> i32 {
-                #(#size_calculation)+*
+            fn calculate_size(&self, version: crate::formats::codec::Version) -> i32 {
+                #flexible_version
+                let mut size = 0i32;
+                #(#size_calculation)*
+                #tagged_size_calculation
+                size
+            }
+            async fn write_to(
+                &self,
+                writer: &mut (dyn tokio::io::AsyncWrite + Send + Unpin),
+                version: crate::formats::codec::Version,
+            ) -> crate::formats::Result<()> {
+                #flexible_version
+                #writing_async
+                Ok(())
             }
-            async fn write_to(&self, writer: &mut (dyn tokio::io::AsyncWrite + Send + Unpin)) -> crate::formats::Result<()> {
-                #(#writing) *
+            fn encode<B: bytes::BufMut>(
+                &self,
+                writer: &mut B,
+                version: crate::formats::codec::Version,
+            ) -> crate::formats::Result<()> {
+                #flexible_version
+                #writing_sync
                 Ok(())
             }
         }
@@ -1,20 +1,30 @@
+mod derive_arbitrary;
 mod derive_read;
 mod derive_request_message;
 mod derive_write;
+mod versions;
 
 use proc_macro::TokenStream;
 
-#[proc_macro_derive(Write)]
+#[proc_macro_derive(Write, attributes(kafka))]
 pub fn derive_write(input: TokenStream) -> TokenStream {
     derive_write::expand(input)
 }
 
-#[proc_macro_derive(Read)]
+#[proc_macro_derive(Read, attributes(kafka))]
 pub fn derive_read(input: TokenStream) -> TokenStream {
     derive_read::expand(input)
 }
 
-#[proc_macro_derive(RequestMessage, attributes(request_message))]
+#[proc_macro_derive(RequestMessage, attributes(request_message, kafka))]
 pub fn derive_request_message(input: TokenStream) -> TokenStream {
     derive_request_message::expand(input)
 }
+
+/// Derives `kafkaesque::formats::arbitrary::Arbitrary`, generating values that survive a round
+/// trip through `Write` and `Read` in the version they're generated for. The impl is only
+/// compiled in tests and with the `proptest` feature.
+#[proc_macro_derive(Arbitrary, attributes(kafka))]
+pub fn derive_arbitrary(input: TokenStream) -> TokenStream {
+    derive_arbitrary::expand(input)
+}
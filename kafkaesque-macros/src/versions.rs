@@ -0,0 +1,76 @@
+use darling::FromMeta;
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// An inclusive range of API versions, written as "3" (exactly 3), "2+" (2 and above) or
+/// "0-9" (0 through 9)
+#[derive(Debug, Clone, Copy)]
+pub struct VersionRange {
+    pub min: i16,
+    pub max: Option<i16>,
+}
+
+impl FromMeta for VersionRange {
+    fn from_string(value: &str) -> darling::Result<Self> {
+        let parse = |n: &str| {
+            n.trim()
+                .parse::<i16>()
+                .map_err(|_| darling::Error::unsupported_format(value))
+        };
+        if let Some(min) = value.strip_suffix('+') {
+            Ok(VersionRange {
+                min: parse(min)?,
+                max: None,
+            })
+        } else if let Some((min, max)) = value.split_once('-') {
+            Ok(VersionRange {
+                min: parse(min)?,
+                max: Some(parse(max)?),
+            })
+        } else {
+            let n = parse(value)?;
+            Ok(VersionRange {
+                min: n,
+                max: Some(n),
+            })
+        }
+    }
+}
+
+impl VersionRange {
+    /// A boolean expression checking whether `version.api_version` falls in the range
+    pub fn contains(&self, version: &TokenStream) -> TokenStream {
+        let min = self.min;
+        match self.max {
+            Some(max) if max == min => quote! { (#version.api_version == #min) },
+            Some(max) => quote! { (#version.api_version >= #min && #version.api_version <= #max) },
+            None => quote! { (#version.api_version >= #min) },
+        }
+    }
+}
+
+/// An optional [VersionRange], where a missing one contains every version
+pub fn contains(range: Option<VersionRange>, version: &TokenStream) -> TokenStream {
+    match range {
+        Some(range) => range.contains(version),
+        None => quote! { true },
+    }
+}
+
+/// Statement shadowing `version` with one whose flexibility is decided by the struct's
+/// `#[kafka(flexible = "..")]` attribute, if it has one
+pub fn flexible_version(flexible: Option<VersionRange>) -> TokenStream {
+    match flexible {
+        Some(range) => {
+            let version = quote! { version };
+            let flexible = range.contains(&version);
+            quote! {
+                let version = crate::formats::codec::Version {
+                    flexible: #flexible,
+                    ..version
+                };
+            }
+        }
+        None => quote! {},
+    }
+}
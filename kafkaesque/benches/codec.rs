@@ -0,0 +1,80 @@
+//! Compares the async [Read]/[Write] codec with the synchronous in-memory one on a large
+//! metadata response.
+//!
+//! Run with `cargo bench -p kafkaesque --bench codec`.
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use kafkaesque::formats::{
+    encode_to_vec,
+    messages::{
+        MetadataRespV0Broker, MetadataRespV0Partition, MetadataRespV0Topic, MetadataResponseV0,
+    },
+    ErrorCode, Read, Version, Write,
+};
+use tokio::runtime::Runtime;
+
+fn large_metadata_response() -> MetadataResponseV0 {
+    let brokers = (0..50)
+        .map(|node_id| MetadataRespV0Broker {
+            node_id,
+            host: format!("broker-{node_id}.kafka.internal"),
+            port: 9092,
+        })
+        .collect();
+    let topics = (0..1_000)
+        .map(|topic| MetadataRespV0Topic {
+            error_code: ErrorCode::None,
+            name: format!("topic-{topic}"),
+            partitions: (0..30)
+                .map(|partition_index| MetadataRespV0Partition {
+                    error_code: ErrorCode::None,
+                    partition_index,
+                    leader_id: partition_index % 50,
+                    replica_nodes: vec![0, 1, 2],
+                    in_sync_replica_nodes: vec![0, 1, 2],
+                })
+                .collect(),
+        })
+        .collect();
+    MetadataResponseV0 { brokers, topics }
+}
+
+fn bench_codec(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let version = Version::default();
+    let response = large_metadata_response();
+    let encoded = encode_to_vec(&response, version).unwrap();
+
+    let mut encoding = c.benchmark_group("encode MetadataResponseV0");
+    encoding.bench_function("async write_to", |b| {
+        b.to_async(&runtime).iter(|| async {
+            let mut buf = Vec::with_capacity(encoded.len());
+            response.write_to(&mut buf, version).await.unwrap();
+            buf
+        })
+    });
+    encoding.bench_function("sync encode", |b| {
+        b.iter(|| encode_to_vec(&response, version).unwrap())
+    });
+    encoding.finish();
+
+    let mut decoding = c.benchmark_group("decode MetadataResponseV0");
+    decoding.bench_function("async read_from", |b| {
+        b.to_async(&runtime).iter_batched(
+            || encoded.as_slice(),
+            |mut buf| async move {
+                MetadataResponseV0::read_from(&mut buf, version)
+                    .await
+                    .unwrap()
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    decoding.bench_function("sync decode", |b| {
+        b.iter(|| MetadataResponseV0::decode(&mut encoded.as_slice(), version).unwrap())
+    });
+    decoding.finish();
+}
+
+criterion_group!(benches, bench_codec);
+criterion_main!(benches);
@@ -1,4 +1,4 @@
-use super::{ClientConfigBuilderError, TopicName};
+use super::{ClientConfigBuilderError, PartitionId, TopicName};
 use crate::formats::{ErrorCode, FormatError};
 use thiserror::Error;
 
@@ -16,4 +16,16 @@ pub enum ClientError {
     TopicCreation { errors: Vec<(TopicName, ErrorCode)> },
     #[error("TopicDeletion error: {errors:?}")]
     TopicDeletion { errors: Vec<(TopicName, ErrorCode)> },
+    #[error("TopicMetadata error for {topic}: {error:?}")]
+    TopicMetadata { topic: TopicName, error: ErrorCode },
+    #[error("ListOffsets error for {topic}: {errors:?}")]
+    ListOffsets {
+        topic: TopicName,
+        errors: Vec<(PartitionId, ErrorCode)>,
+    },
+    #[error("ListOffsets response for {topic} lacks partitions {partitions:?}")]
+    MissingPartitions {
+        topic: TopicName,
+        partitions: Vec<PartitionId>,
+    },
 }
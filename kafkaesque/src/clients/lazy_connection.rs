@@ -2,14 +2,14 @@ use super::{ClientConfig, Result};
 use crate::formats::BrokerConnection;
 use futures::future::select_ok;
 use std::sync::Arc;
-use tokio::sync::{MappedMutexGuard, Mutex, MutexGuard};
+use tokio::sync::Mutex;
 use tracing::trace;
 
 /// A lazily-initialized connection to one of several available brokers.
 #[derive(Debug, Clone)]
 pub struct LazyBrokerConnection {
     config: ClientConfig,
-    conn: Arc<Mutex<Option<BrokerConnection>>>,
+    conn: Arc<Mutex<Option<Arc<BrokerConnection>>>>,
 }
 
 impl LazyBrokerConnection {
@@ -21,25 +21,24 @@ impl LazyBrokerConnection {
         }
     }
 
-    /// Get a working mutable reference to a broker connection, initializing one beforehand if
-    /// necessary.
+    /// Get a working broker connection, initializing one beforehand if necessary. The connection
+    /// can be shared by concurrent requests.
     #[allow(unused)]
-    pub async fn get_connection(&self) -> Result<MappedMutexGuard<BrokerConnection>> {
+    pub async fn get_connection(&self) -> Result<Arc<BrokerConnection>> {
         let mut lock = self.conn.lock().await;
-        if lock.is_none() {
-            trace!("creating connection to");
-            lock.insert(self.connect_to_single_broker().await?);
+        if let Some(conn) = lock.as_ref() {
+            return Ok(conn.clone());
         }
-        Ok(MutexGuard::map(lock, |l| {
-            l.as_mut().expect("BrokerConnection is missing")
-        }))
+        trace!("creating connection to");
+        let conn = Arc::new(self.connect_to_single_broker().await?);
+        Ok(lock.insert(conn).clone())
     }
 
     #[allow(unused)]
     pub async fn reset(&self) -> Result<()> {
         trace!("resetting connection");
         let mut lock = self.conn.lock().await;
-        let new_connection = self.connect_to_single_broker().await?;
+        let new_connection = Arc::new(self.connect_to_single_broker().await?);
         if let Some(old_conn) = lock.replace(new_connection) {
             old_conn.shutdown().await?;
         }
@@ -69,11 +68,13 @@ mod tests {
             messages::{ApiVersionsReq, ApiVersionsResp},
             ErrorCode,
         },
+        testing::MockBroker,
     };
 
     #[tokio::test]
     async fn test_lazy_connection() {
-        let bootstrap_broker_list = BrokerList(vec!["localhost:9092".into()]);
+        let broker = MockBroker::start().await.unwrap();
+        let bootstrap_broker_list = BrokerList(vec![broker.address().into()]);
         let client_id = "test-client".into();
         let client_config = ClientConfig {
             bootstrap_broker_list,
@@ -1,47 +1,90 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
 use std::time::Duration;
 
+use futures::future::try_join_all;
 use itertools::Itertools;
+use tokio::sync::Mutex;
 
-use super::{Metadata, TopicName, TopicSpec};
+use super::{
+    Broker, Metadata, MetadataOptions, NodeId, Offset, OffsetAndTimestamp, PartitionId, TopicName,
+    TopicSpec,
+};
 use crate::{
     clients::{lazy_connection::LazyBrokerConnection, ClientConfig, ClientError, Result},
     formats::{
         messages::{
-            CreateTopicsReqV0, CreateTopicsReqV0CreateTopic, CreateTopicsRespV0, DeleteTopicsReqV0,
-            DeleteTopicsRespV0, MetadataReqV0Topic, MetadataRequestV0, MetadataResponseV0,
+            CreateTopicsReqV0, CreateTopicsReqV0CreateTopic, DeleteTopicsReqV0, ListOffsetsRequest,
+            ListOffsetsRequestListOffsetsPartition, ListOffsetsRequestListOffsetsTopic,
+            MetadataRequest, MetadataRequestTopic,
         },
-        ErrorCode,
+        ApiKey, ApiVersion, BrokerConnection, ErrorCode, FormatError, RequestMessage, VersionRange,
     },
 };
 
+/// ListOffsets timestamps looking up the log start offset, the high watermark and the offset of
+/// the record with the highest timestamp
+const EARLIEST_TIMESTAMP: i64 = -2;
+const LATEST_TIMESTAMP: i64 = -1;
+const MAX_TIMESTAMP: i64 = -3;
+
+/// First ListOffsets version brokers look up [MAX_TIMESTAMP] in
+const MAX_TIMESTAMP_VERSION: ApiVersion = ApiVersion(7);
+
 /// Metadata client
 #[derive(Debug, Clone)]
 pub struct MetadataClient {
+    config: ClientConfig,
     conn: LazyBrokerConnection,
+    /// Connections to the partition leaders that offsets were looked up from
+    brokers: Arc<Mutex<HashMap<NodeId, Arc<BrokerConnection>>>>,
 }
 
 impl MetadataClient {
     pub fn new(config: ClientConfig) -> Self {
         MetadataClient {
-            conn: LazyBrokerConnection::new(config),
+            conn: LazyBrokerConnection::new(config.clone()),
+            config,
+            brokers: Default::default(),
         }
     }
 
-    /// Get topic and broker metadata for topic names
+    /// Get topic and broker metadata for topic names, or for every topic if there are none, with
+    /// the default [MetadataOptions]
     pub async fn get_metadata(
         &self,
         topic_names: impl IntoIterator<Item = impl Into<TopicName>>,
     ) -> Result<Metadata> {
-        let req = MetadataRequestV0 {
-            topics: topic_names
-                .into_iter()
-                .map(|name| MetadataReqV0Topic {
-                    name: name.into().into(),
-                })
-                .collect(),
+        self.get_metadata_with_options(topic_names, MetadataOptions::default())
+            .await
+    }
+
+    /// Get topic and broker metadata for topic names, or for every topic if there are none.
+    /// Missing topics that aren't created are listed with an
+    /// [UnknownTopicOrPartition](ErrorCode::UnknownTopicOrPartition) error.
+    pub async fn get_metadata_with_options(
+        &self,
+        topic_names: impl IntoIterator<Item = impl Into<TopicName>>,
+        options: MetadataOptions,
+    ) -> Result<Metadata> {
+        let topics: Vec<_> = topic_names
+            .into_iter()
+            .map(|name| MetadataRequestTopic {
+                name: name.into().0.into(),
+                ..Default::default()
+            })
+            .collect();
+        let conn = self.conn.get_connection().await?;
+        // Version 0 has no null array, and lists every topic for an empty one instead
+        let all_topics = topics.is_empty()
+            && conn.supported_versions().negotiate::<MetadataRequest>()? > ApiVersion(0);
+        let req = MetadataRequest {
+            topics: (!all_topics).then_some(topics),
+            allow_auto_topic_creation: options.allow_auto_topic_creation,
+            include_topic_authorized_operations: options.include_topic_authorized_operations,
+            ..Default::default()
         };
-        let resp: MetadataResponseV0 = self.conn.get_connection().await?.send(req).await?;
-        Ok(resp.into())
+        Ok(conn.send(req).await?.into())
     }
 
     /// Create topics
@@ -64,7 +107,7 @@ impl MetadataClient {
             timeout_ms: timeout.as_millis() as i32,
         };
 
-        let resp: CreateTopicsRespV0 = self.conn.get_connection().await?.send(req).await?;
+        let resp = self.conn.get_connection().await?.send(req).await?;
 
         let errors = resp
             .topics
@@ -91,7 +134,7 @@ impl MetadataClient {
             timeout_ms: timeout.as_millis() as i32,
         };
 
-        let resp: DeleteTopicsRespV0 = self.conn.get_connection().await?.send(req).await?;
+        let resp = self.conn.get_connection().await?.send(req).await?;
 
         let errors = resp
             .topics
@@ -106,6 +149,201 @@ impl MetadataClient {
             Ok(())
         }
     }
+
+    /// Offset of the first record still available in each partition of a topic
+    pub async fn earliest_offsets(
+        &self,
+        topic: impl Into<TopicName>,
+    ) -> Result<BTreeMap<PartitionId, Offset>> {
+        let offsets = self.list_offsets(topic.into(), EARLIEST_TIMESTAMP).await?;
+        Ok(only_offsets(offsets))
+    }
+
+    /// Offset the next record will be appended at in each partition of a topic, i.e. its high
+    /// watermark
+    pub async fn latest_offsets(
+        &self,
+        topic: impl Into<TopicName>,
+    ) -> Result<BTreeMap<PartitionId, Offset>> {
+        let offsets = self.list_offsets(topic.into(), LATEST_TIMESTAMP).await?;
+        Ok(only_offsets(offsets))
+    }
+
+    /// Offset of the first record with a timestamp at or after `timestamp`, in milliseconds
+    /// since the Unix epoch, in each partition of a topic. Partitions without such a record map
+    /// to [None]. Timestamps before the epoch find the first record.
+    pub async fn offsets_for_times(
+        &self,
+        topic: impl Into<TopicName>,
+        timestamp: i64,
+    ) -> Result<BTreeMap<PartitionId, Option<OffsetAndTimestamp>>> {
+        self.list_offsets(topic.into(), timestamp.max(0)).await
+    }
+
+    /// Offset of the record with the highest timestamp in each partition of a topic, or [None]
+    /// for empty partitions. Requires brokers supporting ListOffsets v7 (Kafka 3.0).
+    pub async fn max_timestamp_offsets(
+        &self,
+        topic: impl Into<TopicName>,
+    ) -> Result<BTreeMap<PartitionId, Option<OffsetAndTimestamp>>> {
+        self.list_offsets(topic.into(), MAX_TIMESTAMP).await
+    }
+
+    /// Looks up `timestamp` in every partition of `topic`, asking the leader of each partition
+    async fn list_offsets(
+        &self,
+        topic: TopicName,
+        timestamp: i64,
+    ) -> Result<BTreeMap<PartitionId, Option<OffsetAndTimestamp>>> {
+        // Looking up offsets must not create the topic
+        let options = MetadataOptions {
+            allow_auto_topic_creation: false,
+            ..MetadataOptions::default()
+        };
+        let metadata = self
+            .get_metadata_with_options([topic.clone()], options)
+            .await?;
+        let topic_metadata = metadata.topics.iter().find(|t| t.name.0 == topic.0).ok_or(
+            ClientError::TopicMetadata {
+                topic: topic.clone(),
+                error: ErrorCode::UnknownTopicOrPartition,
+            },
+        )?;
+        if let Some(error) = topic_metadata.error {
+            return Err(ClientError::TopicMetadata { topic, error });
+        }
+
+        let mut errors = Vec::new();
+        let mut partitions_by_leader: BTreeMap<NodeId, (&Broker, Vec<i32>)> = BTreeMap::new();
+        for partition in &topic_metadata.partitions {
+            let leader = metadata
+                .brokers
+                .iter()
+                .find(|broker| Some(broker.id) == partition.leader);
+            match (partition.error, leader) {
+                (None, Some(leader)) => partitions_by_leader
+                    .entry(leader.id)
+                    .or_insert_with(|| (leader, vec![]))
+                    .1
+                    .push(partition.id.0),
+                (None, None) => errors.push((partition.id, ErrorCode::LeaderNotAvailable)),
+                (Some(error), _) => errors.push((partition.id, error)),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(ClientError::ListOffsets { topic, errors });
+        }
+
+        let min_version = match timestamp {
+            MAX_TIMESTAMP => MAX_TIMESTAMP_VERSION,
+            _ => ListOffsetsRequest::MIN_VERSION,
+        };
+        let requests = partitions_by_leader
+            .into_values()
+            .map(|(broker, partitions)| {
+                let req = ListOffsetsRequest {
+                    replica_id: -1,
+                    isolation_level: 0,
+                    topics: vec![ListOffsetsRequestListOffsetsTopic {
+                        name: topic.0.clone(),
+                        partitions: partitions
+                            .into_iter()
+                            .map(|partition_index| ListOffsetsRequestListOffsetsPartition {
+                                partition_index,
+                                timestamp,
+                                ..Default::default()
+                            })
+                            .collect(),
+                    }],
+                };
+                async move {
+                    let conn = self.broker_connection(broker).await?;
+                    // Older brokers would take the timestamp for a real one and find no record
+                    let versions = conn.supported_versions();
+                    if versions.negotiate::<ListOffsetsRequest>()? < min_version {
+                        return Err(FormatError::UnsupportedVersion {
+                            api_key: ApiKey::ListOffsets,
+                            client: VersionRange::new(min_version, ListOffsetsRequest::MAX_VERSION),
+                            broker: versions.get(ApiKey::ListOffsets),
+                        }
+                        .into());
+                    }
+                    let resp = conn.send(req).await;
+                    if resp.is_err() {
+                        self.brokers.lock().await.remove(&broker.id);
+                    }
+                    Ok::<_, ClientError>(resp?)
+                }
+            });
+        let responses = try_join_all(requests).await?;
+
+        let mut offsets = BTreeMap::new();
+        for partition in responses
+            .into_iter()
+            .flat_map(|resp| resp.topics)
+            .flat_map(|topic| topic.partitions)
+        {
+            let partition_id = PartitionId(partition.partition_index);
+            if partition.error_code != ErrorCode::None {
+                errors.push((partition_id, partition.error_code));
+                continue;
+            }
+            // Version 0 responds with a list of offsets instead
+            let offset = match partition.old_style_offsets.first() {
+                Some(&offset) => offset,
+                None => partition.offset,
+            };
+            let found = (offset >= 0).then_some(OffsetAndTimestamp {
+                offset: Offset(offset),
+                timestamp: partition.timestamp,
+            });
+            offsets.insert(partition_id, found);
+        }
+        if !errors.is_empty() {
+            return Err(ClientError::ListOffsets { topic, errors });
+        }
+        let missing = topic_metadata
+            .partitions
+            .iter()
+            .map(|partition| partition.id)
+            .filter(|id| !offsets.contains_key(id))
+            .collect_vec();
+        if !missing.is_empty() {
+            return Err(ClientError::MissingPartitions {
+                topic,
+                partitions: missing,
+            });
+        }
+        Ok(offsets)
+    }
+
+    /// Connection to a broker, opening one unless an earlier lookup left one open
+    async fn broker_connection(&self, broker: &Broker) -> Result<Arc<BrokerConnection>> {
+        if let Some(conn) = self.brokers.lock().await.get(&broker.id) {
+            return Ok(conn.clone());
+        }
+        // Connecting without the lock, so that lookups from other leaders don't wait on this
+        // one. Concurrent lookups from the same leader keep whichever connection opened first.
+        let addr = format!("{}:{}", broker.host, broker.port);
+        let conn = Arc::new(BrokerConnection::connect(self.config.client_id.clone(), addr).await?);
+        Ok(self
+            .brokers
+            .lock()
+            .await
+            .entry(broker.id)
+            .or_insert(conn)
+            .clone())
+    }
+}
+
+/// Offsets of lookups which always find one, such as the earliest and latest offsets
+fn only_offsets(
+    offsets: BTreeMap<PartitionId, Option<OffsetAndTimestamp>>,
+) -> BTreeMap<PartitionId, Offset> {
+    offsets
+        .into_iter()
+        .filter_map(|(partition, found)| Some((partition, found?.offset)))
+        .collect()
 }
 
 #[cfg(test)]
@@ -113,13 +351,24 @@ mod tests {
     use std::time::Duration;
 
     use crate::clients::*;
+    use crate::formats::{
+        messages::{
+            ProduceRequest, ProduceRequestPartitionProduceData, ProduceRequestTopicProduceData,
+        },
+        records::{Record, RecordBatch, RecordBatchAttributes, RecordSet, RecordSetEntry},
+        AnyRequest, ApiKey, ApiVersion, BrokerConnection, ErrorCode, FormatError,
+    };
+    use crate::testing::{MockBroker, Script};
+    use bytes::Bytes;
+    use itertools::Itertools;
     use tracing::info;
     use tracing_subscriber::{fmt, prelude::*, EnvFilter};
 
     #[tokio::test]
     async fn test_topic_creation_and_reading_topic_metadata() {
         setup_tracing();
-        let bootstrap_broker_list = BrokerList(vec!["localhost:9092".into()]);
+        let broker = MockBroker::start().await.unwrap();
+        let bootstrap_broker_list = BrokerList(vec![broker.address().into()]);
         let client_id = "test-client".into();
         let client_config = ClientConfig {
             bootstrap_broker_list,
@@ -141,13 +390,219 @@ mod tests {
             .await
             .unwrap();
 
-        let metadata = client.get_metadata([topic_name.clone()]).await.unwrap();
+        assert_eq!(broker.topics(), [topic_name.to_string()]);
+
+        let options = MetadataOptions {
+            include_topic_authorized_operations: true,
+            ..MetadataOptions::default()
+        };
+        let metadata = client
+            .get_metadata_with_options([topic_name.clone()], options)
+            .await
+            .unwrap();
         println!("{metadata:#?}");
+        assert_eq!(metadata.topics.len(), 1);
+        assert_eq!(metadata.topics[0].name.0, topic_name.0);
+        assert!(metadata.topics[0].id.is_some());
+        assert!(!metadata.topics[0].is_internal);
+        assert!(metadata.topics[0].authorized_operations.is_some());
+        assert_eq!(metadata.topics[0].error, None);
+        let partition = &metadata.topics[0].partitions[0];
+        assert_eq!(partition.id, PartitionId(0));
+        assert_eq!(partition.leader, Some(metadata.brokers[0].id));
+        assert_eq!(partition.isr, [metadata.brokers[0].id]);
+        assert_eq!(partition.error, None);
+        assert_eq!(metadata.brokers[0].port, i32::from(broker.addr().port()));
+        assert_eq!(metadata.brokers[0].rack, None);
+        assert_eq!(metadata.controller_id, Some(metadata.brokers[0].id));
+        assert!(metadata.cluster_id.is_some());
+
+        let metadata = client.get_metadata(["missing"]).await.unwrap();
+        assert_eq!(
+            metadata.topics[0].error,
+            Some(ErrorCode::UnknownTopicOrPartition)
+        );
+        assert!(metadata.topics[0].partitions.is_empty());
+        assert_eq!(broker.topics(), [topic_name.to_string()]);
+
+        broker.create_topic("other", 1);
+        let metadata = client.get_metadata(Vec::<TopicName>::new()).await.unwrap();
+        assert_eq!(metadata.topics.len(), 2);
 
         client
             .delete_topics([topic_name], Duration::from_secs(5))
             .await
             .unwrap();
+        assert_eq!(broker.topics(), ["other"]);
+    }
+
+    /// Flags of the metadata requests the broker received
+    fn metadata_flags(broker: &MockBroker) -> Vec<(bool, bool)> {
+        broker
+            .requests()
+            .into_iter()
+            .filter_map(|(_, request)| match request {
+                AnyRequest::Metadata(req) => Some((
+                    req.allow_auto_topic_creation,
+                    req.include_topic_authorized_operations,
+                )),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_metadata_options() {
+        let broker = MockBroker::start().await.unwrap();
+        broker.create_topic("topic", 1);
+        let client = MetadataClient::new(ClientConfig {
+            bootstrap_broker_list: BrokerList(vec![broker.address().into()]),
+            client_id: "test-client".into(),
+        });
+
+        let metadata = client.get_metadata(["topic"]).await.unwrap();
+        assert_eq!(metadata.topics[0].authorized_operations, None);
+        assert_eq!(metadata_flags(&broker), [(true, false)]);
+
+        let options = MetadataOptions {
+            allow_auto_topic_creation: false,
+            include_topic_authorized_operations: true,
+        };
+        let metadata = client
+            .get_metadata_with_options(["topic"], options)
+            .await
+            .unwrap();
+        assert!(metadata.topics[0].authorized_operations.is_some());
+        assert_eq!(metadata_flags(&broker)[1], (false, true));
+
+        // Offset lookups never create topics
+        client.latest_offsets("topic").await.unwrap();
+        assert_eq!(metadata_flags(&broker)[2], (false, false));
+    }
+
+    /// Produces a batch of records with the given timestamps to a partition
+    async fn produce(broker: &MockBroker, partition: i32, timestamps: &[i64]) {
+        let batch = RecordBatch {
+            base_offset: 0,
+            partition_leader_epoch: -1,
+            attributes: RecordBatchAttributes::default(),
+            last_offset_delta: timestamps.len() as i32 - 1,
+            base_timestamp: timestamps[0],
+            max_timestamp: *timestamps.iter().max().unwrap(),
+            producer_id: -1,
+            producer_epoch: -1,
+            base_sequence: -1,
+            records: timestamps
+                .iter()
+                .enumerate()
+                .map(|(i, timestamp)| Record {
+                    offset_delta: i as i32,
+                    timestamp_delta: timestamp - timestamps[0],
+                    value: Some(Bytes::from_static(b"value")),
+                    ..Default::default()
+                })
+                .collect(),
+        };
+        let req = ProduceRequest {
+            acks: -1,
+            timeout_ms: 1000,
+            topic_data: vec![ProduceRequestTopicProduceData {
+                name: "topic".into(),
+                partition_data: vec![ProduceRequestPartitionProduceData {
+                    index: partition,
+                    records: RecordSet(vec![RecordSetEntry::Batch(batch)]),
+                }],
+            }],
+            ..Default::default()
+        };
+        let conn = BrokerConnection::connect("test-client", broker.addr())
+            .await
+            .unwrap();
+        conn.send(req).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_list_offsets() {
+        let broker = MockBroker::start().await.unwrap();
+        broker.create_topic("topic", 2);
+        produce(&broker, 0, &[1_000, 3_000]).await;
+        produce(&broker, 0, &[2_000]).await;
+        let client = MetadataClient::new(ClientConfig {
+            bootstrap_broker_list: BrokerList(vec![broker.address().into()]),
+            client_id: "test-client".into(),
+        });
+
+        let earliest = client.earliest_offsets("topic").await.unwrap();
+        assert_eq!(
+            earliest,
+            [(0.into(), 0.into()), (1.into(), 0.into())].into()
+        );
+        let latest = client.latest_offsets("topic").await.unwrap();
+        assert_eq!(latest, [(0.into(), 3.into()), (1.into(), 0.into())].into());
+
+        let found = client.offsets_for_times("topic", 1_500).await.unwrap();
+        assert_eq!(
+            found[&PartitionId(0)],
+            Some(OffsetAndTimestamp {
+                offset: Offset(1),
+                timestamp: 3_000
+            })
+        );
+        assert_eq!(found[&PartitionId(1)], None);
+        let found = client.max_timestamp_offsets("topic").await.unwrap();
+        assert_eq!(found[&PartitionId(0)].unwrap().offset, Offset(1));
+
+        let err = client.latest_offsets("unknown").await.unwrap_err();
+        assert!(
+            matches!(
+                err,
+                ClientError::TopicMetadata {
+                    error: ErrorCode::UnknownTopicOrPartition,
+                    ..
+                }
+            ),
+            "{err}"
+        );
+        broker.script(
+            ApiKey::ListOffsets,
+            Script::Error(ErrorCode::NotLeaderOrFollower),
+        );
+        let err = client.earliest_offsets("topic").await.unwrap_err();
+        assert!(matches!(err, ClientError::ListOffsets { .. }), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_max_timestamp_offsets_need_list_offsets_v7() {
+        let broker = MockBroker::start().await.unwrap();
+        broker.create_topic("topic", 1);
+        broker.cap_version(ApiKey::ListOffsets, 6);
+        let client = MetadataClient::new(ClientConfig {
+            bootstrap_broker_list: BrokerList(vec![broker.address().into()]),
+            client_id: "test-client".into(),
+        });
+
+        let err = client.max_timestamp_offsets("topic").await.unwrap_err();
+        assert!(
+            matches!(
+                err,
+                ClientError::Format(FormatError::UnsupportedVersion {
+                    api_key: ApiKey::ListOffsets,
+                    ..
+                })
+            ),
+            "{err}"
+        );
+
+        // Other lookups work with older versions
+        let latest = client.latest_offsets("topic").await.unwrap();
+        assert_eq!(latest, [(0.into(), 0.into())].into());
+        let list_offsets = broker
+            .requests()
+            .into_iter()
+            .filter(|(_, request)| matches!(request, AnyRequest::ListOffsets(_)))
+            .map(|(header, _)| header.api_version)
+            .collect_vec();
+        assert_eq!(list_offsets, [ApiVersion(6)]);
     }
 
     pub fn setup_tracing() {
@@ -1,13 +1,31 @@
-use crate::formats::messages::{MetadataRespV0Broker, MetadataRespV0Topic, MetadataResponseV0};
+use crate::formats::{
+    messages::{
+        MetadataResponse, MetadataResponseBroker, MetadataResponsePartition, MetadataResponseTopic,
+    },
+    ErrorCode, NullableString,
+};
 use derive_more::{Display, From, Into};
 use std::collections::HashMap;
+use uuid::Uuid;
 
-#[derive(Debug, Clone, Copy, From, Into, Display)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, From, Into, Display)]
 pub struct NodeId(pub i32);
 
-#[derive(Debug, Clone, Copy, From, Into, Display)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, From, Into, Display)]
 pub struct PartitionId(pub i32);
 
+/// Position of a record in a partition
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, From, Into, Display)]
+pub struct Offset(pub i64);
+
+/// Offset of the record found by a timestamp lookup, and that record's timestamp
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OffsetAndTimestamp {
+    pub offset: Offset,
+    /// Milliseconds since the Unix epoch
+    pub timestamp: i64,
+}
+
 #[derive(Debug, Clone, Copy, From, Into, Display)]
 pub struct PartitionCount(pub i32);
 
@@ -17,19 +35,72 @@ pub struct ReplicationFactor(pub i16);
 #[derive(Debug, Clone, From, Into, Display)]
 pub struct TopicName(pub String);
 
+impl From<&str> for TopicName {
+    fn from(value: &str) -> Self {
+        TopicName(value.to_string())
+    }
+}
+
 #[derive(Debug, Clone, namewise::From)]
-#[namewise_from(from_type = "MetadataRespV0Broker")]
+#[namewise_from(from_type = "MetadataResponseBroker")]
 pub struct Broker {
     #[namewise_from(from_name = "node_id")]
     pub id: NodeId,
     pub host: String,
     pub port: i32,
+    /// Rack of the broker, if it was assigned one
+    pub rack: Option<String>,
 }
 
 #[derive(Debug, Clone, namewise::From)]
-#[namewise_from(from_type = "MetadataRespV0Topic")]
+#[namewise_from(from_type = "MetadataResponseTopic")]
 pub struct Topic {
+    #[namewise_from(mapper = "topic_name")]
     pub name: TopicName,
+    /// Id of the topic, if the broker is recent enough to report it
+    #[namewise_from(from_name = "topic_id", mapper = "topic_id")]
+    pub id: Option<Uuid>,
+    /// Why the topic couldn't be described, such as
+    /// [UnknownTopicOrPartition](ErrorCode::UnknownTopicOrPartition) for missing topics
+    #[namewise_from(from_name = "error_code", mapper = "error")]
+    pub error: Option<ErrorCode>,
+    /// Whether the topic is internal to Kafka, such as `__consumer_offsets`
+    pub is_internal: bool,
+    #[namewise_from(collect)]
+    pub partitions: Vec<Partition>,
+    /// Bitfield of the ACL operations the client may perform on the topic, by operation code,
+    /// if requested with [MetadataOptions::include_topic_authorized_operations] and the broker
+    /// is recent enough to report it
+    #[namewise_from(
+        from_name = "topic_authorized_operations",
+        mapper = "authorized_operations"
+    )]
+    pub authorized_operations: Option<i32>,
+}
+
+#[derive(Debug, Clone, namewise::From)]
+#[namewise_from(from_type = "MetadataResponsePartition")]
+pub struct Partition {
+    #[namewise_from(from_name = "partition_index")]
+    pub id: PartitionId,
+    /// Broker the partition is read from and written to, unless it has no leader at the moment
+    #[namewise_from(from_name = "leader_id", mapper = "node_id")]
+    pub leader: Option<NodeId>,
+    /// Epoch of the leader, if the broker is recent enough to report it
+    #[namewise_from(mapper = "leader_epoch")]
+    pub leader_epoch: Option<i32>,
+    /// Brokers hosting the partition
+    #[namewise_from(from_name = "replica_nodes", collect)]
+    pub replicas: Vec<NodeId>,
+    /// Replicas in sync with the leader
+    #[namewise_from(from_name = "isr_nodes", collect)]
+    pub isr: Vec<NodeId>,
+    #[namewise_from(collect)]
+    pub offline_replicas: Vec<NodeId>,
+    /// Why the partition couldn't be described, such as
+    /// [LeaderNotAvailable](ErrorCode::LeaderNotAvailable) during leader elections
+    #[namewise_from(from_name = "error_code", mapper = "error")]
+    pub error: Option<ErrorCode>,
 }
 
 #[derive(Debug, Clone)]
@@ -40,12 +111,38 @@ pub struct TopicSpec {
 }
 
 #[derive(Debug, Clone, namewise::From)]
-#[namewise_from(from_type = "MetadataResponseV0")]
+#[namewise_from(from_type = "MetadataResponse")]
 pub struct Metadata {
     #[namewise_from(collect)]
     pub topics: Vec<Topic>,
     #[namewise_from(collect)]
     pub brokers: Vec<Broker>,
+    /// Id of the cluster, if the broker is recent enough to report it
+    pub cluster_id: Option<String>,
+    /// Broker acting as the controller of the cluster, which admin requests go to
+    #[namewise_from(mapper = "node_id")]
+    pub controller_id: Option<NodeId>,
+}
+
+/// Settings of a metadata lookup. The defaults keep the behavior of MetadataRequest v0: brokers
+/// allowing it create missing topics, and don't compute authorized operations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MetadataOptions {
+    /// Whether brokers with `auto.create.topics.enable` set create requested topics that don't
+    /// exist yet
+    pub allow_auto_topic_creation: bool,
+    /// Whether to fill [Topic::authorized_operations], which costs brokers an authorizer lookup
+    /// per topic
+    pub include_topic_authorized_operations: bool,
+}
+
+impl Default for MetadataOptions {
+    fn default() -> Self {
+        MetadataOptions {
+            allow_auto_topic_creation: true,
+            include_topic_authorized_operations: false,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -55,3 +152,32 @@ pub struct TopicAssignments {
     /// Mapping from PartitionIDs to Broker IDs
     pub partition_to_broker_ids: HashMap<PartitionId, Vec<NodeId>>,
 }
+
+/// Names are only ever null in responses to requests for unknown topic ids
+fn topic_name(name: NullableString) -> TopicName {
+    TopicName(name.0.unwrap_or_default())
+}
+
+/// Brokers before version 10 leave topic ids nil
+fn topic_id(id: Uuid) -> Option<Uuid> {
+    Some(id).filter(|id| !id.is_nil())
+}
+
+/// Brokers report -1 when they don't know a broker, such as the controller
+fn node_id(id: i32) -> Option<NodeId> {
+    (id >= 0).then_some(NodeId(id))
+}
+
+/// Brokers report -1 when they don't know a leader epoch, or before version 7
+fn leader_epoch(epoch: i32) -> Option<i32> {
+    (epoch >= 0).then_some(epoch)
+}
+
+fn error(error_code: ErrorCode) -> Option<ErrorCode> {
+    (error_code != ErrorCode::None).then_some(error_code)
+}
+
+/// Brokers report `i32::MIN` unless authorized operations were requested, since version 8
+fn authorized_operations(operations: i32) -> Option<i32> {
+    (operations != i32::MIN).then_some(operations)
+}
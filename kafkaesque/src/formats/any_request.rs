@@ -0,0 +1,311 @@
+use super::{
+    codec::{Read, Version, Write},
+    messages::{
+        ApiVersionsRequest, CreateTopicsReqV0, DeleteTopicsReqV0, FetchRequest, ListOffsetsRequest,
+        MetadataRequest, ProduceRequest,
+    },
+    request::{
+        has_flexible_response_header, ApiVersion, CorrelationId, FlexibleResponseHeader,
+        RequestHeader, RequestMessage,
+    },
+    supported_versions::VersionRange,
+    variable_lengths::TaggedFields,
+    ApiKey, DecodeLimits, FormatError, Result,
+};
+use bytes::{Buf, Bytes};
+use tracing::{debug, trace};
+
+/// Declares [AnyRequest] with a variant for each request message a broker can decode
+macro_rules! any_request {
+    ($($variant:ident($ReqM:ty)),* $(,)?) => {
+        /// A request of any API the crate can decode, as received by a broker
+        #[derive(Debug, Clone, PartialEq)]
+        pub enum AnyRequest {
+            $($variant($ReqM),)*
+        }
+
+        impl AnyRequest {
+            /// API keys and versions of the requests [decode_request] can decode
+            pub const VERSIONS: &'static [(ApiKey, VersionRange)] = &[$((
+                <$ReqM as RequestMessage>::API_KEY,
+                VersionRange {
+                    min: <$ReqM as RequestMessage>::MIN_VERSION,
+                    max: <$ReqM as RequestMessage>::MAX_VERSION,
+                },
+            ),)*];
+
+            pub fn api_key(&self) -> ApiKey {
+                match self {
+                    $(AnyRequest::$variant(_) => <$ReqM as RequestMessage>::API_KEY,)*
+                }
+            }
+        }
+
+        $(impl From<$ReqM> for AnyRequest {
+            fn from(request: $ReqM) -> Self {
+                AnyRequest::$variant(request)
+            }
+        })*
+
+        /// Decodes the body of a request following `header`, dispatching on its API key
+        fn decode_message(
+            header: &RequestHeader,
+            reader: &mut Bytes,
+            limits: DecodeLimits,
+        ) -> Result<AnyRequest> {
+            match header.api_key {
+                $(api_key if api_key == <$ReqM as RequestMessage>::API_KEY => {
+                    decode_versioned::<$ReqM>(header.api_version, reader, limits)
+                        .map(AnyRequest::$variant)
+                })*
+                api_key => Err(FormatError::UnsupportedRequest {
+                    api_key,
+                    api_version: header.api_version.0,
+                }),
+            }
+        }
+    };
+}
+
+any_request! {
+    ApiVersions(ApiVersionsRequest),
+    CreateTopics(CreateTopicsReqV0),
+    DeleteTopics(DeleteTopicsReqV0),
+    Fetch(FetchRequest),
+    ListOffsets(ListOffsetsRequest),
+    Metadata(MetadataRequest),
+    Produce(ProduceRequest),
+}
+
+/// Decodes a request frame, without its length prefix, into its header and message. Records in
+/// the request share the frame rather than copying out of it.
+pub fn decode_request(frame: Bytes, limits: DecodeLimits) -> Result<(RequestHeader, AnyRequest)> {
+    let len = frame.len();
+    let reader = &mut frame.clone();
+    // Request headers v1 and v2 only differ by the tagged fields following them, which depend on
+    // the version of the message
+    let header = RequestHeader::decode(reader, Version::default().with_limits(limits))?;
+    debug!("Decoding request [len={len},header={header:?}]");
+    let request = decode_message(&header, reader, limits)?;
+    if reader.has_remaining() {
+        return Err(FormatError::TrailingBytes {
+            api_key: header.api_key,
+            remaining: reader.len(),
+        });
+    }
+    Ok((header, request))
+}
+
+/// Decodes the tagged fields of request header v2 if `api_version` of [ReqM] is flexible, then
+/// the message itself
+fn decode_versioned<ReqM: RequestMessage + Read>(
+    api_version: ApiVersion,
+    reader: &mut Bytes,
+    limits: DecodeLimits,
+) -> Result<ReqM> {
+    if api_version < ReqM::MIN_VERSION || api_version > ReqM::MAX_VERSION {
+        return Err(FormatError::UnsupportedRequest {
+            api_key: ReqM::API_KEY,
+            api_version: api_version.0,
+        });
+    }
+    let version = ReqM::version(api_version).with_limits(limits);
+    if version.flexible {
+        let tagged_fields = TaggedFields::decode(reader, Version::default().with_limits(limits))?;
+        trace!("Received request tagged fields {tagged_fields:?}");
+    }
+    ReqM::decode(reader, version)
+}
+
+/// Encodes the response to the [ReqM] request with correlation id `cid` in `api_version`,
+/// prefixed by its length
+pub fn encode_response<ReqM: RequestMessage>(
+    cid: CorrelationId,
+    api_version: ApiVersion,
+    response: &ReqM::Response,
+) -> Result<Vec<u8>>
+where
+    ReqM::Response: Write,
+{
+    let version = ReqM::version(api_version);
+    let header = FlexibleResponseHeader {
+        cid,
+        tagged_fields: Default::default(),
+    };
+    // Headers spell out their tagged fields, so they're always encoded as non-flexible
+    let flexible_header = has_flexible_response_header::<ReqM>(version);
+    let header_len = if flexible_header {
+        header.calculate_size(Version::default())
+    } else {
+        cid.calculate_size(Version::default())
+    };
+    let resp_len = header_len + response.calculate_size(version);
+    let mut frame = Vec::with_capacity(resp_len as usize + 4);
+    resp_len.encode(&mut frame, version)?;
+    if flexible_header {
+        header.encode(&mut frame, Version::default())?;
+    } else {
+        cid.encode(&mut frame, Version::default())?;
+    }
+    response.encode(&mut frame, version)?;
+    Ok(frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::{
+        encode_to_vec,
+        messages::{
+            ApiVersionsResponse, MetadataRequestTopic, MetadataRequestV0, MetadataResponseV0,
+        },
+        request::FlexibleRequestHeader,
+        ErrorCode,
+    };
+
+    fn request_header<ReqM: RequestMessage>(api_version: i16) -> RequestHeader {
+        RequestHeader {
+            api_key: ReqM::API_KEY,
+            api_version: ApiVersion(api_version),
+            cid: CorrelationId::from(7),
+            client_id: "test-client".into(),
+        }
+    }
+
+    /// Encodes a request frame the way [BrokerConnection](super::super::BrokerConnection) does,
+    /// without the length prefix
+    fn request_frame<ReqM: RequestMessage + Write>(api_version: i16, message: &ReqM) -> Bytes {
+        let version = ReqM::version(ApiVersion(api_version));
+        let mut frame = if version.flexible {
+            let header = FlexibleRequestHeader {
+                header: request_header::<ReqM>(api_version),
+                tagged_fields: Default::default(),
+            };
+            encode_to_vec(&header, Version::default()).unwrap()
+        } else {
+            encode_to_vec(&request_header::<ReqM>(api_version), Version::default()).unwrap()
+        };
+        frame.extend(encode_to_vec(message, version).unwrap());
+        frame.into()
+    }
+
+    #[test]
+    fn test_decode_request() {
+        let metadata = MetadataRequest {
+            topics: Some(vec![MetadataRequestTopic {
+                name: "topic".into(),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        };
+        let (header, request) =
+            decode_request(request_frame(0, &metadata), DecodeLimits::DEFAULT).unwrap();
+        assert_eq!(header, request_header::<MetadataRequest>(0));
+        assert_eq!(request, AnyRequest::Metadata(metadata));
+        assert_eq!(request.api_key(), ApiKey::Metadata);
+
+        // Flexible versions are preceded by the tagged fields of request header v2
+        let api_versions = ApiVersionsRequest {
+            client_software_name: "kafkaesque".into(),
+            client_software_version: "1.0".into(),
+        };
+        for api_version in 0..=3 {
+            let expected = if api_version < 3 {
+                ApiVersionsRequest::default()
+            } else {
+                api_versions.clone()
+            };
+            let frame = request_frame(api_version, &api_versions);
+            let (header, request) = decode_request(frame, DecodeLimits::DEFAULT).unwrap();
+            assert_eq!(header, request_header::<ApiVersionsRequest>(api_version));
+            assert_eq!(request, expected.into());
+        }
+    }
+
+    #[test]
+    fn test_decode_unsupported_request() {
+        let mut frame =
+            encode_to_vec(&request_header::<MetadataRequest>(13), Version::default()).unwrap();
+        frame.extend([0, 0, 0, 0]);
+        let err = decode_request(frame.into(), DecodeLimits::DEFAULT).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                FormatError::UnsupportedRequest {
+                    api_key: ApiKey::Metadata,
+                    api_version: 13
+                }
+            ),
+            "{err}"
+        );
+
+        let mut header = request_header::<MetadataRequest>(0);
+        header.api_key = ApiKey::Unsupported(1000);
+        let frame = encode_to_vec(&header, Version::default()).unwrap();
+        let err = decode_request(frame.into(), DecodeLimits::DEFAULT).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                FormatError::UnsupportedRequest {
+                    api_key: ApiKey::Unsupported(1000),
+                    api_version: 0
+                }
+            ),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_decode_request_with_trailing_bytes() {
+        let mut frame = request_frame(0, &MetadataRequestV0 { topics: vec![] }).to_vec();
+        frame.extend([1, 2]);
+        let err = decode_request(frame.into(), DecodeLimits::DEFAULT).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                FormatError::TrailingBytes {
+                    api_key: ApiKey::Metadata,
+                    remaining: 2
+                }
+            ),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_encode_response() {
+        let response = ApiVersionsResponse {
+            error_code: ErrorCode::None,
+            throttle_time_ms: 10,
+            ..Default::default()
+        };
+        let cid = CorrelationId::from(7);
+        for api_version in 0..=3 {
+            let version = ApiVersionsRequest::version(ApiVersion(api_version));
+            let frame =
+                encode_response::<ApiVersionsRequest>(cid, ApiVersion(api_version), &response)
+                    .unwrap();
+            let mut reader = Bytes::from(frame);
+            assert_eq!(reader.get_i32() as usize, reader.len());
+            // ApiVersions responses always carry response header v0
+            assert_eq!(CorrelationId::decode(&mut reader, version).unwrap(), cid);
+            let decoded = ApiVersionsResponse::decode(&mut reader, version).unwrap();
+            assert_eq!(
+                decoded.throttle_time_ms,
+                if api_version > 0 { 10 } else { 0 }
+            );
+            assert!(!reader.has_remaining());
+        }
+
+        let frame = encode_response::<MetadataRequestV0>(
+            cid,
+            ApiVersion(0),
+            &MetadataResponseV0 {
+                brokers: vec![],
+                topics: vec![],
+            },
+        )
+        .unwrap();
+        assert_eq!(frame, [0, 0, 0, 12, 0, 0, 0, 7, 0, 0, 0, 0, 0, 0, 0, 0]);
+    }
+}
@@ -1,10 +1,11 @@
 use super::{
-    codec::{FixedLength, Read, Write},
+    codec::{FixedLength, Read, Version, Write},
     errors::Result,
 };
-use tokio::io::AsyncWrite;
+use bytes::{Buf, BufMut};
+use tokio::io::{AsyncRead, AsyncWrite};
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum ApiKey {
     Produce,
     Fetch,
@@ -70,13 +71,10 @@ pub enum ApiKey {
     Unsupported(i16),
 }
 
-impl Write for ApiKey {
-    fn calculate_size(&self) -> i32 {
-        i16::SIZE
-    }
-    async fn write_to(&self, writer: &mut (dyn AsyncWrite + Send + Unpin)) -> Result<()> {
+impl From<ApiKey> for i16 {
+    fn from(api_key: ApiKey) -> Self {
         use ApiKey::*;
-        let n: i16 = match self {
+        match api_key {
             Produce => 0,
             Fetch => 1,
             ListOffsets => 2,
@@ -138,17 +136,15 @@ impl Write for ApiKey {
             DescribeTransactions => 65,
             ListTransactions => 66,
             AllocateProducerIds => 67,
-            Unsupported(n) => *n,
-        };
-        n.write_to(writer).await
+            Unsupported(n) => n,
+        }
     }
 }
 
-impl Read for ApiKey {
-    async fn read_from(reader: &mut (dyn tokio::io::AsyncRead + Send + Unpin)) -> Result<Self> {
-        let n = i16::read_from(reader).await?;
+impl From<i16> for ApiKey {
+    fn from(n: i16) -> Self {
         use ApiKey::*;
-        let v = match n {
+        match n {
             0 => Produce,
             1 => Fetch,
             2 => ListOffsets,
@@ -211,7 +207,34 @@ impl Read for ApiKey {
             66 => ListTransactions,
             67 => AllocateProducerIds,
             n => Unsupported(n),
-        };
-        Ok(v)
+        }
+    }
+}
+
+impl Write for ApiKey {
+    fn calculate_size(&self, _version: Version) -> i32 {
+        i16::SIZE
+    }
+    async fn write_to(
+        &self,
+        writer: &mut (dyn AsyncWrite + Send + Unpin),
+        version: Version,
+    ) -> Result<()> {
+        i16::from(*self).write_to(writer, version).await
+    }
+    fn encode<B: BufMut>(&self, buf: &mut B, version: Version) -> Result<()> {
+        i16::from(*self).encode(buf, version)
+    }
+}
+
+impl Read for ApiKey {
+    async fn read_from(
+        reader: &mut (dyn AsyncRead + Send + Unpin),
+        version: Version,
+    ) -> Result<Self> {
+        Ok(i16::read_from(reader, version).await?.into())
+    }
+    fn decode<B: Buf>(buf: &mut B, version: Version) -> Result<Self> {
+        Ok(i16::decode(buf, version)?.into())
     }
 }
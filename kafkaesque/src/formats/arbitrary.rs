@@ -0,0 +1,306 @@
+//! Values of the wire types for property tests, generated per [Version] so that they survive a
+//! round trip through [Write](super::Write) and [Read](super::Read). Derive [Arbitrary] with
+//! `#[derive(Arbitrary)]` alongside `Read` and `Write`.
+//!
+//! Only compiled in tests and with the `proptest` feature.
+
+use super::{
+    codec::Version,
+    records::{Header, Record, RecordBatch, RecordBatchAttributes, RecordSet, RecordSetEntry},
+    ApiKey, CompactArray, CompactBytes, CompactNullableString, CompactString, ErrorCode,
+    NullableString, TaggedField, TaggedFields, UnsignedVarInt, VarInt, VarLong,
+};
+use bytes::Bytes;
+use proptest::{collection::vec, option, prelude::*};
+use std::fmt::Debug;
+use uuid::Uuid;
+
+/// Largest number of elements generated for arrays, which nest in most messages
+const MAX_ELEMENTS: usize = 3;
+
+pub trait Arbitrary: Sized + Debug + 'static {
+    /// Strategy generating values encodable in `version`, where fields absent from `version`
+    /// hold the value they're decoded as
+    fn arbitrary(version: Version) -> BoxedStrategy<Self>;
+}
+
+macro_rules! arbitrary_any_impl {
+    ($($SelfT:ty),*) => {
+        $(impl Arbitrary for $SelfT {
+            fn arbitrary(_version: Version) -> BoxedStrategy<Self> {
+                any::<$SelfT>().boxed()
+            }
+        })*
+    };
+}
+
+arbitrary_any_impl!(bool, i8, i16, i32, i64, u32);
+
+impl Arbitrary for f64 {
+    fn arbitrary(_version: Version) -> BoxedStrategy<Self> {
+        // NaN isn't equal to itself
+        any::<f64>().prop_filter("NaN", |f| !f.is_nan()).boxed()
+    }
+}
+
+impl Arbitrary for Uuid {
+    fn arbitrary(_version: Version) -> BoxedStrategy<Self> {
+        any::<u128>().prop_map(Uuid::from_u128).boxed()
+    }
+}
+
+impl Arbitrary for VarInt {
+    fn arbitrary(_version: Version) -> BoxedStrategy<Self> {
+        any::<i32>().prop_map(VarInt).boxed()
+    }
+}
+
+impl Arbitrary for VarLong {
+    fn arbitrary(_version: Version) -> BoxedStrategy<Self> {
+        any::<i64>().prop_map(VarLong).boxed()
+    }
+}
+
+impl Arbitrary for UnsignedVarInt {
+    fn arbitrary(_version: Version) -> BoxedStrategy<Self> {
+        any::<u32>().prop_map(UnsignedVarInt).boxed()
+    }
+}
+
+impl Arbitrary for ErrorCode {
+    fn arbitrary(_version: Version) -> BoxedStrategy<Self> {
+        any::<i16>().prop_map(ErrorCode::from).boxed()
+    }
+}
+
+impl Arbitrary for ApiKey {
+    fn arbitrary(_version: Version) -> BoxedStrategy<Self> {
+        any::<i16>().prop_map(ApiKey::from).boxed()
+    }
+}
+
+impl Arbitrary for String {
+    fn arbitrary(_version: Version) -> BoxedStrategy<Self> {
+        "\\PC{0,12}".boxed()
+    }
+}
+
+impl Arbitrary for NullableString {
+    fn arbitrary(version: Version) -> BoxedStrategy<Self> {
+        option::of(String::arbitrary(version))
+            .prop_map(NullableString)
+            .boxed()
+    }
+}
+
+impl Arbitrary for CompactString {
+    fn arbitrary(version: Version) -> BoxedStrategy<Self> {
+        String::arbitrary(version).prop_map(CompactString).boxed()
+    }
+}
+
+impl Arbitrary for CompactNullableString {
+    fn arbitrary(version: Version) -> BoxedStrategy<Self> {
+        option::of(String::arbitrary(version))
+            .prop_map(CompactNullableString)
+            .boxed()
+    }
+}
+
+impl Arbitrary for Bytes {
+    fn arbitrary(_version: Version) -> BoxedStrategy<Self> {
+        vec(any::<u8>(), 0..16).prop_map(Bytes::from).boxed()
+    }
+}
+
+impl Arbitrary for Option<Bytes> {
+    fn arbitrary(version: Version) -> BoxedStrategy<Self> {
+        option::of(Bytes::arbitrary(version)).boxed()
+    }
+}
+
+impl Arbitrary for CompactBytes {
+    fn arbitrary(_version: Version) -> BoxedStrategy<Self> {
+        vec(any::<u8>(), 0..16).prop_map(CompactBytes).boxed()
+    }
+}
+
+impl<A: Arbitrary> Arbitrary for Vec<A> {
+    fn arbitrary(version: Version) -> BoxedStrategy<Self> {
+        vec(A::arbitrary(version), 0..=MAX_ELEMENTS).boxed()
+    }
+}
+
+impl<A: Arbitrary> Arbitrary for Option<Vec<A>> {
+    fn arbitrary(version: Version) -> BoxedStrategy<Self> {
+        option::of(Vec::<A>::arbitrary(version)).boxed()
+    }
+}
+
+impl<A: Arbitrary> Arbitrary for CompactArray<A> {
+    fn arbitrary(version: Version) -> BoxedStrategy<Self> {
+        Vec::<A>::arbitrary(version).prop_map(CompactArray).boxed()
+    }
+}
+
+impl Arbitrary for TaggedFields {
+    fn arbitrary(_version: Version) -> BoxedStrategy<Self> {
+        let field = (any::<u32>(), vec(any::<u8>(), 0..8))
+            .prop_map(|(tag, data)| TaggedField { tag, data });
+        vec(field, 0..=MAX_ELEMENTS).prop_map(TaggedFields).boxed()
+    }
+}
+
+impl Arbitrary for RecordSet {
+    /// Record sets of uncompressed record batches. Legacy message sets are converted to record
+    /// batches by brokers handling recent versions, and compression doesn't round trip
+    /// byte for byte across codec implementations.
+    fn arbitrary(version: Version) -> BoxedStrategy<Self> {
+        vec(RecordBatch::arbitrary(version), 0..=MAX_ELEMENTS)
+            .prop_map(|batches| RecordSet(batches.into_iter().map(RecordSetEntry::Batch).collect()))
+            .boxed()
+    }
+}
+
+impl Arbitrary for RecordBatch {
+    fn arbitrary(version: Version) -> BoxedStrategy<Self> {
+        (
+            (any::<i64>(), any::<i32>(), any::<i16>(), any::<i32>()),
+            (any::<i64>(), any::<i64>(), any::<i64>(), any::<i16>()),
+            (
+                any::<i32>(),
+                vec(Record::arbitrary(version), 0..=MAX_ELEMENTS),
+            ),
+        )
+            .prop_map(
+                |(
+                    (base_offset, partition_leader_epoch, attributes, last_offset_delta),
+                    (base_timestamp, max_timestamp, producer_id, producer_epoch),
+                    (base_sequence, records),
+                )| RecordBatch {
+                    base_offset,
+                    partition_leader_epoch,
+                    // The lowest 3 bits are the compression codec
+                    attributes: RecordBatchAttributes(attributes & !0x07),
+                    last_offset_delta,
+                    base_timestamp,
+                    max_timestamp,
+                    producer_id,
+                    producer_epoch,
+                    base_sequence,
+                    records: records.into(),
+                },
+            )
+            .boxed()
+    }
+}
+
+impl Arbitrary for Record {
+    fn arbitrary(version: Version) -> BoxedStrategy<Self> {
+        let header = (
+            String::arbitrary(version),
+            Option::<Bytes>::arbitrary(version),
+        )
+            .prop_map(|(key, value)| Header { key, value });
+        (
+            any::<i8>(),
+            any::<i64>(),
+            any::<i32>(),
+            Option::<Bytes>::arbitrary(version),
+            Option::<Bytes>::arbitrary(version),
+            vec(header, 0..=MAX_ELEMENTS),
+        )
+            .prop_map(
+                |(attributes, timestamp_delta, offset_delta, key, value, headers)| Record {
+                    attributes,
+                    timestamp_delta,
+                    offset_delta,
+                    key,
+                    value,
+                    headers: headers.into(),
+                },
+            )
+            .boxed()
+    }
+}
+
+#[cfg(test)]
+use super::{Read, Write};
+
+/// Checks that values of `T` generated for `version` encode to as many bytes as
+/// [Write::calculate_size] announces, with both codecs, and decode back to themselves
+#[cfg(test)]
+pub(crate) fn assert_round_trips<T: Arbitrary + Read + Write + PartialEq>(version: Version) {
+    use proptest::test_runner::TestRunner;
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    TestRunner::default()
+        .run(&T::arbitrary(version), |value| {
+            let mut buf = Vec::new();
+            runtime.block_on(value.write_to(&mut buf, version)).unwrap();
+            prop_assert_eq!(value.calculate_size(version), buf.len() as i32);
+            prop_assert_eq!(&super::encode_to_vec(&value, version).unwrap(), &buf);
+
+            let read = runtime
+                .block_on(T::read_from(&mut buf.as_slice(), version))
+                .unwrap();
+            prop_assert_eq!(&read, &value);
+            let mut rest = Bytes::from(buf);
+            let decoded = T::decode(&mut rest, version).unwrap();
+            prop_assert_eq!(&decoded, &value);
+            prop_assert!(rest.is_empty(), "{} bytes left undecoded", rest.len());
+            Ok(())
+        })
+        .unwrap_or_else(|err| panic!("{} in {version:?}: {err}", std::any::type_name::<T>()));
+}
+
+/// Checks [assert_round_trips] for a request and its response, in every version of the request
+#[cfg(test)]
+pub(crate) fn assert_messages_round_trip<ReqM>()
+where
+    ReqM: super::RequestMessage + Arbitrary + Read + Write + PartialEq,
+    ReqM::Response: Arbitrary + Write + PartialEq,
+{
+    for api_version in ReqM::MIN_VERSION.0..=ReqM::MAX_VERSION.0 {
+        let version = ReqM::version(super::ApiVersion(api_version));
+        assert_round_trips::<ReqM>(version);
+        assert_round_trips::<ReqM::Response>(version);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wire_types_round_trip() {
+        for version in [Version::new(0, false), Version::new(0, true)] {
+            assert_round_trips::<bool>(version);
+            assert_round_trips::<i8>(version);
+            assert_round_trips::<i16>(version);
+            assert_round_trips::<i32>(version);
+            assert_round_trips::<i64>(version);
+            assert_round_trips::<u32>(version);
+            assert_round_trips::<f64>(version);
+            assert_round_trips::<Uuid>(version);
+            assert_round_trips::<VarInt>(version);
+            assert_round_trips::<VarLong>(version);
+            assert_round_trips::<UnsignedVarInt>(version);
+            assert_round_trips::<ErrorCode>(version);
+            assert_round_trips::<String>(version);
+            assert_round_trips::<NullableString>(version);
+            assert_round_trips::<CompactString>(version);
+            assert_round_trips::<CompactNullableString>(version);
+            assert_round_trips::<Bytes>(version);
+            assert_round_trips::<Option<Bytes>>(version);
+            assert_round_trips::<CompactBytes>(version);
+            assert_round_trips::<Vec<i32>>(version);
+            assert_round_trips::<Option<Vec<String>>>(version);
+            assert_round_trips::<CompactArray<i32>>(version);
+            assert_round_trips::<TaggedFields>(version);
+            assert_round_trips::<RecordSet>(version);
+        }
+    }
+}
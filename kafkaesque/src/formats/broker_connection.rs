@@ -1,28 +1,92 @@
 use super::{
-    codec::{Read, Write},
-    request::{CorrelationId, RequestHeader, RequestMessage},
-    Result, DEFAULT_BUF_SIZE,
+    codec::{read_bytes, Read, Version, Write},
+    messages::ApiVersionsReq,
+    request::{
+        has_flexible_response_header, ApiVersion, CorrelationId, FlexibleRequestHeader,
+        FlexibleResponseHeader, RequestHeader, RequestMessage,
+    },
+    supported_versions::{SupportedVersions, VersionRange},
+    ApiKey, DecodeLimits, ErrorCode, FormatError, LengthKind, Result, DEFAULT_BUF_SIZE,
+    DEFAULT_MAX_IN_FLIGHT,
 };
+use bytes::{Buf, Bytes};
+use futures::future::try_join_all;
+use std::collections::HashMap;
 use std::fmt::Debug;
-use tokio::io::AsyncWriteExt;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::{Arc, Mutex};
 use tokio::{
-    io::BufStream,
-    net::{TcpStream, ToSocketAddrs},
+    io::{AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader, BufWriter},
+    net::{
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+        TcpStream, ToSocketAddrs,
+    },
+    sync::{mpsc, oneshot, OwnedSemaphorePermit, Semaphore},
+    task::JoinHandle,
 };
-use tracing::debug;
+use tracing::{debug, trace};
 
-/// A connection to a single broker
+/// Options of a [BrokerConnection]
+#[derive(Debug, Clone, Copy)]
+pub struct ConnectionConfig {
+    /// Size of the TCP read buffer in bytes
+    pub read_buf_size: usize,
+    /// Size of the TCP write buffer in bytes
+    pub write_buf_size: usize,
+    /// Maximum number of requests awaiting a response at any time. Further requests wait for
+    /// earlier ones to complete.
+    pub max_in_flight: usize,
+    /// Bounds on the size of response frames and of what they hold, against misbehaving brokers
+    pub limits: DecodeLimits,
+}
+
+impl Default for ConnectionConfig {
+    fn default() -> Self {
+        ConnectionConfig {
+            read_buf_size: DEFAULT_BUF_SIZE,
+            write_buf_size: DEFAULT_BUF_SIZE,
+            max_in_flight: DEFAULT_MAX_IN_FLIGHT,
+            limits: DecodeLimits::default(),
+        }
+    }
+}
+
+/// A connection to a single broker.
+///
+/// Requests can be sent concurrently from `&self`: they're pipelined over the socket by a
+/// background writer task, and a background reader task hands every response to the request
+/// with its correlation id.
 #[derive(Debug)]
 pub struct BrokerConnection {
-    next_cid: i32,
-    stream: BufStream<TcpStream>,
+    next_cid: AtomicI32,
+    commands: mpsc::UnboundedSender<WriterCommand>,
+    pending: Arc<Mutex<Pending>>,
+    in_flight: Arc<Semaphore>,
     client_id: String,
+    supported_versions: SupportedVersions,
+    limits: DecodeLimits,
+    reader: JoinHandle<()>,
+}
+
+/// Senders of the requests awaiting a response, by correlation id
+#[derive(Debug, Default)]
+struct Pending {
+    responses: HashMap<i32, oneshot::Sender<Result<Bytes>>>,
+    /// Why the connection stopped working, once it has
+    closed: Option<String>,
+}
+
+#[derive(Debug)]
+enum WriterCommand {
+    /// A frame to write, with the in-flight permit of a request without response, which is
+    /// released once the frame is written
+    Frame(Vec<u8>, Option<OwnedSemaphorePermit>),
+    Shutdown(oneshot::Sender<std::io::Result<()>>),
 }
 
 impl BrokerConnection {
     pub async fn connect(client_id: impl Into<String>, addr: impl ToSocketAddrs) -> Result<Self> {
-        Self::connect_with_buffer_size(client_id.into(), addr, DEFAULT_BUF_SIZE, DEFAULT_BUF_SIZE)
-            .await
+        Self::connect_with_config(client_id, addr, ConnectionConfig::default()).await
     }
 
     pub async fn connect_with_buffer_size(
@@ -31,85 +95,608 @@ impl BrokerConnection {
         read_buf_size: usize,
         write_buf_size: usize,
     ) -> Result<Self> {
-        let c = BrokerConnection {
-            next_cid: 0,
-            stream: BufStream::with_capacity(
-                read_buf_size,
-                write_buf_size,
-                TcpStream::connect(addr).await?,
-            ),
+        let config = ConnectionConfig {
+            read_buf_size,
+            write_buf_size,
+            ..ConnectionConfig::default()
+        };
+        Self::connect_with_config(client_id, addr, config).await
+    }
+
+    pub async fn connect_with_config(
+        client_id: impl Into<String>,
+        addr: impl ToSocketAddrs,
+        config: ConnectionConfig,
+    ) -> Result<Self> {
+        let (read_half, write_half) = TcpStream::connect(addr).await?.into_split();
+        let pending = Arc::new(Mutex::new(Pending::default()));
+        let (commands, command_receiver) = mpsc::unbounded_channel();
+        tokio::spawn(write_requests(
+            BufWriter::with_capacity(config.write_buf_size, write_half),
+            command_receiver,
+            pending.clone(),
+        ));
+        let reader = tokio::spawn(read_responses(
+            BufReader::with_capacity(config.read_buf_size, read_half),
+            pending.clone(),
+            config.limits,
+        ));
+
+        let mut c = BrokerConnection {
+            next_cid: AtomicI32::new(0),
+            commands,
+            pending,
+            in_flight: Arc::new(Semaphore::new(config.max_in_flight.max(1))),
             client_id: client_id.into(),
+            supported_versions: SupportedVersions::default(),
+            limits: config.limits,
+            reader,
         };
+        c.supported_versions = c.fetch_supported_versions().await?;
         Ok(c)
     }
 
-    pub async fn send<Req: RequestMessage + Write + Debug, Resp: Read + Debug>(
-        &mut self,
+    /// Versions of every API the broker supports
+    pub fn supported_versions(&self) -> &SupportedVersions {
+        &self.supported_versions
+    }
+
+    /// Sends a request in the highest version supported by both sides, and waits for its response
+    pub async fn send<Req: RequestMessage + Write + Debug>(
+        &self,
         message: Req,
-    ) -> Result<Resp> {
-        self.write_request(message).await?;
-        self.stream.flush().await?;
-        self.read_response().await
+    ) -> Result<Req::Response> {
+        let api_version = self.supported_versions.negotiate::<Req>()?;
+        self.send_version(message, api_version).await
     }
 
-    pub async fn send_many<ReqM: RequestMessage + Write + Debug, Resp: Read + Debug>(
-        &mut self,
+    /// Sends requests concurrently, returning their responses in the same order
+    pub async fn send_many<ReqM: RequestMessage + Write + Debug>(
+        &self,
         messages: impl IntoIterator<Item = ReqM>,
-    ) -> Result<Vec<Resp>> {
-        let mut len = 0;
-        for message in messages {
-            self.write_request(message).await?;
-            len += 1;
-        }
-        self.stream.flush().await?;
-        let mut responses = Vec::with_capacity(len);
-        for _ in 0..len {
-            responses.push(self.read_response().await?);
+    ) -> Result<Vec<ReqM::Response>> {
+        let api_version = self.supported_versions.negotiate::<ReqM>()?;
+        try_join_all(
+            messages
+                .into_iter()
+                .map(|message| self.send_version(message, api_version)),
+        )
+        .await
+    }
+
+    /// Sends a request the broker doesn't respond to, such as a produce request with
+    /// `acks = 0`, returning as soon as it's queued for writing. It counts towards
+    /// [ConnectionConfig::max_in_flight] until it's written.
+    pub async fn send_without_response<ReqM: RequestMessage + Write + Debug>(
+        &self,
+        message: ReqM,
+    ) -> Result<()> {
+        if message.expects_response() {
+            return Err(FormatError::ResponseExpected(ReqM::API_KEY));
         }
-        Ok(responses)
+        let api_version = self.supported_versions.negotiate::<ReqM>()?;
+        let (_, frame) = self.encode_request(message, api_version)?;
+        let permit = self
+            .in_flight
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("in-flight semaphore is never closed");
+        self.check_open()?;
+        self.queue_frame(frame, Some(permit))
     }
 
-    pub async fn shutdown(mut self) -> Result<()> {
-        self.stream.shutdown().await?;
+    /// Flushes the requests written so far and closes the connection
+    pub async fn shutdown(&self) -> Result<()> {
+        let (ack, done) = oneshot::channel();
+        if self.commands.send(WriterCommand::Shutdown(ack)).is_ok() {
+            if let Ok(result) = done.await {
+                result?;
+            }
+        }
         Ok(())
     }
 
-    async fn write_request<ReqM: RequestMessage + Write + Debug>(
-        &mut self,
+    /// Sends a request in `api_version`, which the broker is assumed to support
+    pub(crate) async fn send_version<ReqM: RequestMessage + Write + Debug>(
+        &self,
         message: ReqM,
-    ) -> Result<()> {
-        let header = self.generate_header::<ReqM>();
-        let req_len = header.calculate_size() + message.calculate_size();
-        debug!("Sending request [len={req_len},header={header:?},message={message:?}]");
-        req_len.write_to(&mut self.stream).await?;
-        header.write_to(&mut self.stream).await?;
-        message.write_to(&mut self.stream).await?;
-        Ok(())
+        api_version: ApiVersion,
+    ) -> Result<ReqM::Response> {
+        if !message.expects_response() {
+            return Err(FormatError::NoResponseExpected(ReqM::API_KEY));
+        }
+        let _permit = self
+            .in_flight
+            .acquire()
+            .await
+            .expect("in-flight semaphore is never closed");
+        let (cid, response) = self.write_request(message, api_version).await?;
+        let frame = response
+            .await
+            .map_err(|_| FormatError::ConnectionClosed("response sender dropped".into()))??;
+        decode_response::<ReqM>(
+            frame,
+            cid,
+            ReqM::version(api_version).with_limits(self.limits),
+        )
+    }
+
+    /// Asks the broker which versions of each API it supports, using ApiVersions v0 which every
+    /// broker understands
+    async fn fetch_supported_versions(&self) -> Result<SupportedVersions> {
+        let resp = self
+            .send_version(ApiVersionsReq, ApiVersionsReq::MAX_VERSION)
+            .await?;
+        if resp.error_code != ErrorCode::None {
+            return Err(FormatError::ErrorResponse {
+                api_key: ApiKey::ApiVersions,
+                error_code: resp.error_code,
+            });
+        }
+        let supported_versions = resp
+            .api_keys
+            .into_iter()
+            .map(|key| {
+                let range = VersionRange::new(key.min_version, key.max_version);
+                (key.api_key, range)
+            })
+            .collect();
+        debug!("Broker supports {supported_versions:?}");
+        Ok(supported_versions)
     }
 
-    async fn read_response<Resp: Read + Debug>(&mut self) -> Result<Resp> {
-        let resp_len = i32::read_from(&mut self.stream).await?;
-        let resp_cid = CorrelationId::read_from(&mut self.stream).await?;
+    /// Registers a request as pending and queues it for writing, returning its correlation id
+    /// and the receiver of the response's frame
+    async fn write_request<ReqM: RequestMessage + Write + Debug>(
+        &self,
+        message: ReqM,
+        api_version: ApiVersion,
+    ) -> Result<(i32, oneshot::Receiver<Result<Bytes>>)> {
+        let (cid, frame) = self.encode_request(message, api_version)?;
+        let (sender, receiver) = oneshot::channel();
+        {
+            let mut pending = self.pending.lock().expect("pending lock poisoned");
+            if let Some(reason) = &pending.closed {
+                return Err(FormatError::ConnectionClosed(reason.clone()));
+            }
+            pending.responses.insert(cid, sender);
+        }
+        if let Err(e) = self.queue_frame(frame, None) {
+            self.pending
+                .lock()
+                .expect("pending lock poisoned")
+                .responses
+                .remove(&cid);
+            return Err(e);
+        }
+        Ok((cid, receiver))
+    }
 
-        debug!("Received response [len={resp_len},cid={resp_cid:?}]",);
+    /// Encodes a request with its header, returning its correlation id and frame
+    fn encode_request<ReqM: RequestMessage + Write + Debug>(
+        &self,
+        message: ReqM,
+        api_version: ApiVersion,
+    ) -> Result<(i32, Vec<u8>)> {
+        let header = self.generate_header::<ReqM>(api_version);
+        let cid = header.cid.into();
+        let version = ReqM::version(api_version);
+        let frame = if version.flexible {
+            let header = FlexibleRequestHeader {
+                header,
+                tagged_fields: Default::default(),
+            };
+            encode_frame(header, message, version)?
+        } else {
+            encode_frame(header, message, version)?
+        };
+        Ok((cid, frame))
+    }
 
-        let resp = Resp::read_from(&mut self.stream).await?;
+    fn check_open(&self) -> Result<()> {
+        match &self.pending.lock().expect("pending lock poisoned").closed {
+            Some(reason) => Err(FormatError::ConnectionClosed(reason.clone())),
+            None => Ok(()),
+        }
+    }
 
-        Ok(resp)
+    fn queue_frame(&self, frame: Vec<u8>, permit: Option<OwnedSemaphorePermit>) -> Result<()> {
+        self.commands
+            .send(WriterCommand::Frame(frame, permit))
+            .map_err(|_| FormatError::ConnectionClosed("writer stopped".into()))
     }
 
-    fn generate_header<M: RequestMessage>(&mut self) -> RequestHeader {
+    fn generate_header<M: RequestMessage>(&self, api_version: ApiVersion) -> RequestHeader {
         RequestHeader {
             api_key: M::API_KEY,
-            api_version: M::API_VERSION,
+            api_version,
             cid: self.get_next_cid(),
             client_id: self.client_id.clone(),
         }
     }
 
-    fn get_next_cid(&mut self) -> CorrelationId {
-        let cid = CorrelationId::from(self.next_cid);
-        self.next_cid += 1;
-        cid
+    fn get_next_cid(&self) -> CorrelationId {
+        CorrelationId::from(self.next_cid.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+impl Drop for BrokerConnection {
+    fn drop(&mut self) {
+        // The writer stops by itself once `commands` is dropped
+        self.reader.abort();
+    }
+}
+
+/// Encodes a request, prefixed by its length
+fn encode_frame<H: Write + Debug, ReqM: Write + Debug>(
+    header: H,
+    message: ReqM,
+    version: Version,
+) -> Result<Vec<u8>> {
+    // Headers spell out their tagged fields, so they're always encoded as non-flexible
+    let req_len = header.calculate_size(Version::default()) + message.calculate_size(version);
+    debug!("Sending request [len={req_len},header={header:?},message={message:?}]");
+    let mut frame = Vec::with_capacity(req_len as usize + 4);
+    req_len.encode(&mut frame, version)?;
+    header.encode(&mut frame, Version::default())?;
+    message.encode(&mut frame, version)?;
+    Ok(frame)
+}
+
+/// Decodes the response to the request with correlation id `cid`, which must span the whole
+/// frame. Records in the response share the frame rather than copying out of it.
+fn decode_response<ReqM: RequestMessage>(
+    frame: Bytes,
+    cid: i32,
+    version: Version,
+) -> Result<ReqM::Response> {
+    let len = frame.len();
+    let reader = &mut frame.clone();
+    let resp_cid: i32 = if has_flexible_response_header::<ReqM>(version) {
+        let header = FlexibleResponseHeader::decode(reader, Version::default())?;
+        trace!("Received response tagged fields {:?}", header.tagged_fields);
+        header.cid.into()
+    } else {
+        CorrelationId::decode(reader, Version::default())?.into()
+    };
+    if resp_cid != cid {
+        return Err(FormatError::CorrelationIdMismatch {
+            expected: cid,
+            actual: resp_cid,
+        });
+    }
+    debug!("Decoding response [len={len},cid={resp_cid}]");
+    let response = ReqM::Response::decode(reader, version)?;
+    if reader.has_remaining() {
+        return Err(FormatError::TrailingBytes {
+            api_key: ReqM::API_KEY,
+            remaining: reader.len(),
+        });
+    }
+    Ok(response)
+}
+
+/// Writes queued frames, flushing whenever the queue runs empty
+async fn write_requests(
+    mut writer: BufWriter<OwnedWriteHalf>,
+    mut commands: mpsc::UnboundedReceiver<WriterCommand>,
+    pending: Arc<Mutex<Pending>>,
+) {
+    let mut next = commands.recv().await;
+    while let Some(command) = next {
+        match command {
+            WriterCommand::Frame(frame, permit) => {
+                if let Err(err) = writer.write_all(&frame).await {
+                    close_with_reason(&pending, format!("failed writing request: {err}"));
+                    return;
+                }
+                drop(permit);
+                next = match commands.try_recv() {
+                    Ok(command) => Some(command),
+                    Err(_) => {
+                        if let Err(err) = writer.flush().await {
+                            close_with_reason(&pending, format!("failed writing request: {err}"));
+                            return;
+                        }
+                        commands.recv().await
+                    }
+                }
+            }
+            WriterCommand::Shutdown(ack) => {
+                close_with_reason(&pending, "connection shut down".into());
+                let result = async {
+                    writer.flush().await?;
+                    writer.shutdown().await
+                }
+                .await;
+                let _ = ack.send(result);
+                return;
+            }
+        }
+    }
+}
+
+/// Reads response frames, handing each to the request with its correlation id
+async fn read_responses(
+    mut reader: BufReader<OwnedReadHalf>,
+    pending: Arc<Mutex<Pending>>,
+    limits: DecodeLimits,
+) {
+    loop {
+        let frame = match read_frame(&mut reader, limits).await {
+            Ok(frame) => frame,
+            Err(FormatError::InvalidFrameLength(len)) => {
+                close(&pending, || FormatError::InvalidFrameLength(len));
+                return;
+            }
+            Err(FormatError::LengthOutOfBounds {
+                kind,
+                length,
+                limit,
+            }) => {
+                close(&pending, || FormatError::LengthOutOfBounds {
+                    kind,
+                    length,
+                    limit,
+                });
+                return;
+            }
+            Err(err) => {
+                close_with_reason(&pending, format!("failed reading response: {err}"));
+                return;
+            }
+        };
+        let cid = i32::from_be_bytes(frame[..4].try_into().expect("4 bytes"));
+        trace!("Received response [len={},cid={cid}]", frame.len());
+        let sender = pending
+            .lock()
+            .expect("pending lock poisoned")
+            .responses
+            .remove(&cid);
+        match sender {
+            // The receiver is gone if the request was cancelled
+            Some(sender) => drop(sender.send(Ok(frame))),
+            // The stream can't be trusted anymore, so everything still pending fails
+            None => {
+                close(&pending, || FormatError::UnexpectedCorrelationId(cid));
+                return;
+            }
+        }
+    }
+}
+
+/// Reads a length-prefixed frame, which must at least hold a correlation id, into a buffer of
+/// its own that the decoded message can share
+pub(crate) async fn read_frame(
+    reader: &mut (impl AsyncRead + Unpin),
+    limits: DecodeLimits,
+) -> Result<Bytes> {
+    let len = reader.read_i32().await?;
+    if len < 4 {
+        return Err(FormatError::InvalidFrameLength(len));
+    }
+    let len = limits.check(LengthKind::Frame, len.into())?;
+    Ok(read_bytes(reader, len).await?.into())
+}
+
+/// Marks the connection as closed, failing every pending request with `error`
+fn close(pending: &Mutex<Pending>, error: impl Fn() -> FormatError) {
+    let reason = error().to_string();
+    debug!("Closing connection: {reason}");
+    let mut pending = pending.lock().expect("pending lock poisoned");
+    for (_, sender) in pending.responses.drain() {
+        let _ = sender.send(Err(error()));
+    }
+    pending.closed.get_or_insert(reason);
+}
+
+fn close_with_reason(pending: &Mutex<Pending>, reason: String) {
+    close(pending, || FormatError::ConnectionClosed(reason.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::codec::{tracking_alloc::largest_allocation, HUGE_LENGTH};
+    use tokio::net::TcpListener;
+
+    /// Reads a request frame, returning its api key and correlation id
+    async fn read_request(stream: &mut TcpStream) -> (i16, i32) {
+        let len = stream.read_i32().await.unwrap();
+        let mut frame = vec![0u8; len as usize];
+        stream.read_exact(&mut frame).await.unwrap();
+        let api_key = i16::from_be_bytes(frame[0..2].try_into().unwrap());
+        let cid = i32::from_be_bytes(frame[4..8].try_into().unwrap());
+        (api_key, cid)
+    }
+
+    /// ApiVersions v0 response supporting ApiVersions up to `max_version`
+    fn api_versions_response(cid: i32, max_version: i16) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend(cid.to_be_bytes());
+        body.extend(0i16.to_be_bytes());
+        body.extend(1i32.to_be_bytes());
+        body.extend(18i16.to_be_bytes());
+        body.extend(0i16.to_be_bytes());
+        body.extend(max_version.to_be_bytes());
+        body
+    }
+
+    async fn write_frame(stream: &mut TcpStream, body: &[u8]) {
+        stream
+            .write_all(&(body.len() as i32).to_be_bytes())
+            .await
+            .unwrap();
+        stream.write_all(body).await.unwrap();
+    }
+
+    async fn write_api_versions_response(stream: &mut TcpStream, cid: i32, max_version: i16) {
+        write_frame(stream, &api_versions_response(cid, max_version)).await;
+    }
+
+    /// Connects to a fake broker that completes the handshake, then answers the next request
+    /// with `respond(cid)`
+    async fn connect_to_fake_broker(respond: fn(i32) -> Vec<u8>) -> BrokerConnection {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let (_, cid) = read_request(&mut stream).await;
+            write_api_versions_response(&mut stream, cid, 3).await;
+            let (_, cid) = read_request(&mut stream).await;
+            stream.write_all(&respond(cid)).await.unwrap();
+            // Keep the connection open until the client is done
+            let _ = stream.read_i32().await;
+        });
+        BrokerConnection::connect("test-client", addr)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_requests_with_out_of_order_responses() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let broker = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let (_, cid) = read_request(&mut stream).await;
+            write_api_versions_response(&mut stream, cid, 3).await;
+
+            let mut requests = Vec::new();
+            for _ in 0..3 {
+                requests.push(read_request(&mut stream).await);
+            }
+            // Answer the last request first, telling requests apart by their correlation id
+            for (api_key, cid) in requests.into_iter().rev() {
+                assert_eq!(api_key, 18);
+                write_api_versions_response(&mut stream, cid, cid as i16).await;
+            }
+        });
+
+        let conn = BrokerConnection::connect("test-client", addr)
+            .await
+            .unwrap();
+        assert_eq!(
+            conn.supported_versions().get(ApiKey::ApiVersions),
+            Some(VersionRange::new(0, 3))
+        );
+        let responses = conn
+            .send_many([ApiVersionsReq, ApiVersionsReq, ApiVersionsReq])
+            .await
+            .unwrap();
+        let max_versions: Vec<i16> = responses
+            .iter()
+            .map(|resp| resp.api_keys[0].max_version)
+            .collect();
+        assert_eq!(max_versions, [1, 2, 3]);
+        broker.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_pending_requests_fail_when_the_broker_disconnects() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let (_, cid) = read_request(&mut stream).await;
+            write_api_versions_response(&mut stream, cid, 3).await;
+            read_request(&mut stream).await;
+        });
+
+        let conn = BrokerConnection::connect("test-client", addr)
+            .await
+            .unwrap();
+        let err = conn.send(ApiVersionsReq).await.unwrap_err();
+        assert!(matches!(err, FormatError::ConnectionClosed(_)), "{err}");
+        let err = conn.send(ApiVersionsReq).await.unwrap_err();
+        assert!(matches!(err, FormatError::ConnectionClosed(_)), "{err}");
+    }
+
+    fn framed(body: Vec<u8>) -> Vec<u8> {
+        let mut frame = (body.len() as i32).to_be_bytes().to_vec();
+        frame.extend(body);
+        frame
+    }
+
+    #[tokio::test]
+    async fn test_unexpected_correlation_id() {
+        let conn = connect_to_fake_broker(|cid| framed(api_versions_response(cid + 10, 3))).await;
+        let err = conn.send(ApiVersionsReq).await.unwrap_err();
+        assert!(
+            matches!(err, FormatError::UnexpectedCorrelationId(11)),
+            "{err}"
+        );
+        let err = conn.send(ApiVersionsReq).await.unwrap_err();
+        assert!(matches!(err, FormatError::ConnectionClosed(_)), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_trailing_bytes() {
+        let conn = connect_to_fake_broker(|cid| {
+            let mut body = api_versions_response(cid, 3);
+            body.extend([0, 0, 0]);
+            framed(body)
+        })
+        .await;
+        let err = conn.send(ApiVersionsReq).await.unwrap_err();
+        assert!(
+            matches!(
+                err,
+                FormatError::TrailingBytes {
+                    api_key: ApiKey::ApiVersions,
+                    remaining: 3
+                }
+            ),
+            "{err}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_truncated_response() {
+        let conn = connect_to_fake_broker(|cid| {
+            let mut body = api_versions_response(cid, 3);
+            body.truncate(body.len() - 2);
+            framed(body)
+        })
+        .await;
+        let err = conn.send(ApiVersionsReq).await.unwrap_err();
+        assert!(matches!(err, FormatError::Io(_)), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_invalid_frame_length() {
+        let conn = connect_to_fake_broker(|_| (-1i32).to_be_bytes().to_vec()).await;
+        let err = conn.send(ApiVersionsReq).await.unwrap_err();
+        assert!(matches!(err, FormatError::InvalidFrameLength(-1)), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_oversized_frame() {
+        let conn = connect_to_fake_broker(|_| i32::MAX.to_be_bytes().to_vec()).await;
+        let err = conn.send(ApiVersionsReq).await.unwrap_err();
+        assert!(
+            matches!(
+                err,
+                FormatError::LengthOutOfBounds {
+                    kind: LengthKind::Frame,
+                    length: 0x7fff_ffff,
+                    ..
+                }
+            ),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_huge_frame_length_with_short_body() {
+        let mut buf = (HUGE_LENGTH as i32).to_be_bytes().to_vec();
+        buf.extend([0; 64]);
+        let (read, largest) = largest_allocation(|| {
+            futures::executor::block_on(read_frame(&mut buf.as_slice(), DecodeLimits::DEFAULT))
+        });
+        let err = read.unwrap_err();
+        assert!(matches!(err, FormatError::Io(_)), "{err}");
+        assert!(largest < 1024 * 1024, "allocated {largest} bytes");
     }
 }
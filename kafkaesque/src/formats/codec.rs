@@ -1,17 +1,353 @@
-use super::Result;
-use tokio::io::{AsyncRead, AsyncWrite};
+use super::{DecodeLimits, FormatError, Result};
+use bytes::{Buf, BufMut, Bytes};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite};
 
-pub use kafkaesque_macros::{Read, Write};
+pub use kafkaesque_macros::{Arbitrary, Read, Write};
+
+/// The API version a message is encoded in, and whether that version uses the flexible
+/// encoding of KIP-482 (compact strings and arrays, tagged fields)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Version {
+    pub api_version: i16,
+    pub flexible: bool,
+    /// Bounds on the lengths decoders accept, ignored when encoding
+    pub limits: DecodeLimits,
+}
+
+impl Version {
+    pub const fn new(api_version: i16, flexible: bool) -> Self {
+        Version {
+            api_version,
+            flexible,
+            limits: DecodeLimits::DEFAULT,
+        }
+    }
+
+    pub fn with_limits(self, limits: DecodeLimits) -> Self {
+        Version { limits, ..self }
+    }
+}
 
 pub trait Write {
-    fn calculate_size(&self) -> i32;
-    async fn write_to(&self, writer: &mut (dyn AsyncWrite + Send + Unpin)) -> Result<()>;
+    fn calculate_size(&self, version: Version) -> i32;
+    async fn write_to(
+        &self,
+        writer: &mut (dyn AsyncWrite + Send + Unpin),
+        version: Version,
+    ) -> Result<()>;
+    /// Synchronous counterpart of [Write::write_to], encoding into an in-memory buffer
+    fn encode<B: BufMut>(&self, buf: &mut B, version: Version) -> Result<()>;
 }
 
 pub trait Read: Sized {
-    async fn read_from(reader: &mut (dyn AsyncRead + Send + Unpin)) -> Result<Self>;
+    async fn read_from(
+        reader: &mut (dyn AsyncRead + Send + Unpin),
+        version: Version,
+    ) -> Result<Self>;
+    /// Synchronous counterpart of [Read::read_from], decoding from an in-memory buffer. Fails
+    /// with an [std::io::ErrorKind::UnexpectedEof] error if the buffer runs out.
+    fn decode<B: Buf>(buf: &mut B, version: Version) -> Result<Self>;
+}
+
+/// Encodes a value into a new buffer
+pub fn encode_to_vec<W: Write>(value: &W, version: Version) -> Result<Vec<u8>> {
+    let mut buf = Vec::with_capacity(value.calculate_size(version).max(0) as usize);
+    value.encode(&mut buf, version)?;
+    Ok(buf)
+}
+
+/// Fails unless `buf` holds at least `len` more bytes
+pub(crate) fn ensure_remaining(buf: &impl Buf, len: usize) -> Result<()> {
+    if buf.remaining() < len {
+        Err(FormatError::Io(std::io::ErrorKind::UnexpectedEof.into()))
+    } else {
+        Ok(())
+    }
+}
+
+/// Takes the next `len` bytes out of `buf`
+pub(crate) fn take_bytes(buf: &mut impl Buf, len: usize) -> Result<Vec<u8>> {
+    ensure_remaining(buf, len)?;
+    let mut bytes = vec![0u8; len];
+    buf.copy_to_slice(&mut bytes);
+    Ok(bytes)
+}
+
+/// Number of bytes allocated upfront when reading a length-prefixed value, as a length within
+/// the limits may still be far more than the reader holds
+const MAX_PREALLOCATED_BYTES: usize = 64 * 1024;
+
+/// Reads the next `len` bytes, growing the buffer as they arrive rather than allocating all of
+/// them upfront. Fails with an [std::io::ErrorKind::UnexpectedEof] error if the reader runs out.
+pub(crate) async fn read_bytes(
+    reader: &mut (impl AsyncRead + Unpin + ?Sized),
+    len: usize,
+) -> Result<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(len.min(MAX_PREALLOCATED_BYTES));
+    (&mut *reader)
+        .take(len as u64)
+        .read_to_end(&mut bytes)
+        .await?;
+    if bytes.len() < len {
+        return Err(FormatError::Io(std::io::ErrorKind::UnexpectedEof.into()));
+    }
+    Ok(bytes)
+}
+
+/// Takes the next `len` bytes out of `buf`, without copying them if `buf` is [Bytes]
+pub(crate) fn take_shared(buf: &mut impl Buf, len: usize) -> Result<Bytes> {
+    ensure_remaining(buf, len)?;
+    Ok(buf.copy_to_bytes(len))
 }
 
 pub trait FixedLength {
     const SIZE: i32;
 }
+
+/// Checks that `value` encodes to exactly `expected`, with both the async and the sync codecs,
+/// and that `expected` decodes back to `value`
+#[cfg(test)]
+pub(crate) async fn assert_golden<T: Read + Write + PartialEq + std::fmt::Debug>(
+    value: T,
+    version: Version,
+    expected: &[u8],
+) {
+    let mut buf = Vec::new();
+    value.write_to(&mut buf, version).await.unwrap();
+    assert_eq!(buf, expected, "{value:?}");
+    assert_eq!(
+        encode_to_vec(&value, version).unwrap(),
+        expected,
+        "{value:?}"
+    );
+    assert_eq!(
+        value.calculate_size(version),
+        expected.len() as i32,
+        "{value:?}"
+    );
+    let read = T::read_from(&mut &expected[..], version).await.unwrap();
+    assert_eq!(read, value);
+    let mut buf = expected;
+    let decoded = T::decode(&mut buf, version).unwrap();
+    assert_eq!(decoded, value);
+    assert!(
+        buf.is_empty(),
+        "{value:?} left {} bytes undecoded",
+        buf.len()
+    );
+}
+
+/// Global allocator of the tests, recording the largest allocation of each thread so that tests
+/// can check decoders don't allocate whatever a corrupt length announces
+#[cfg(test)]
+pub(crate) mod tracking_alloc {
+    use std::{
+        alloc::{GlobalAlloc, Layout, System},
+        cell::Cell,
+    };
+
+    struct TrackingAllocator;
+
+    #[global_allocator]
+    static ALLOCATOR: TrackingAllocator = TrackingAllocator;
+
+    thread_local! {
+        static LARGEST: Cell<usize> = const { Cell::new(0) };
+    }
+
+    fn record(size: usize) {
+        let _ = LARGEST.try_with(|largest| largest.set(largest.get().max(size)));
+    }
+
+    unsafe impl GlobalAlloc for TrackingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            record(layout.size());
+            System.alloc(layout)
+        }
+        unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+            record(layout.size());
+            System.alloc_zeroed(layout)
+        }
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            record(new_size);
+            System.realloc(ptr, layout, new_size)
+        }
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    /// Runs `f`, returning its result and the largest allocation it made on this thread
+    pub(crate) fn largest_allocation<T>(f: impl FnOnce() -> T) -> (T, usize) {
+        LARGEST.with(|largest| largest.set(0));
+        let result = f();
+        (result, LARGEST.with(Cell::get))
+    }
+}
+
+/// Length announced by [assert_huge_length_rejected] inputs, within the default limits
+#[cfg(test)]
+pub(crate) const HUGE_LENGTH: usize = 64 * 1024 * 1024;
+
+/// Checks that reading `data`, which announces [HUGE_LENGTH] bytes but holds far fewer, fails
+/// without allocating anywhere near that much
+#[cfg(test)]
+pub(crate) fn assert_huge_length_rejected<T: Read + std::fmt::Debug>(
+    data: &[u8],
+    version: Version,
+) {
+    let (read, largest) = tracking_alloc::largest_allocation(|| {
+        futures::executor::block_on(T::read_from(&mut &data[..], version))
+    });
+    let err = read.unwrap_err();
+    assert!(
+        largest < 1024 * 1024,
+        "allocated {largest} bytes before failing with {err}"
+    );
+    let (decoded, largest) =
+        tracking_alloc::largest_allocation(|| T::decode(&mut &data[..], version));
+    let err = decoded.unwrap_err();
+    assert!(
+        largest < 1024 * 1024,
+        "allocated {largest} bytes before failing with {err}"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Default, PartialEq, Read, Write)]
+    #[kafka(flexible = "2+")]
+    struct Sample {
+        id: i32,
+        #[kafka(versions = "1+")]
+        name: String,
+        #[kafka(versions = "0-1")]
+        legacy: i16,
+        items: Vec<SampleItem>,
+        #[kafka(tagged = 0, versions = "2+")]
+        note: String,
+    }
+
+    #[derive(Debug, Default, PartialEq, Read, Write)]
+    struct SampleItem {
+        value: i8,
+    }
+
+    fn sample() -> Sample {
+        Sample {
+            id: 1,
+            name: "x".into(),
+            legacy: 7,
+            items: vec![SampleItem { value: 3 }],
+            note: "n".into(),
+        }
+    }
+
+    async fn assert_encoding(version: Version, expected_bytes: &[u8], expected: Sample) {
+        let mut buf = Vec::new();
+        sample().write_to(&mut buf, version).await.unwrap();
+        assert_eq!(buf, expected_bytes);
+        assert_eq!(sample().calculate_size(version), buf.len() as i32);
+        let read = Sample::read_from(&mut buf.as_slice(), version)
+            .await
+            .unwrap();
+        assert_eq!(read, expected);
+
+        assert_eq!(encode_to_vec(&sample(), version).unwrap(), expected_bytes);
+        let decoded = Sample::decode(&mut buf.as_slice(), version).unwrap();
+        assert_eq!(decoded, expected);
+    }
+
+    #[tokio::test]
+    async fn test_versioned_fields() {
+        assert_encoding(
+            Version::new(0, false),
+            &[0, 0, 0, 1, 0, 7, 0, 0, 0, 1, 3],
+            Sample {
+                name: String::new(),
+                note: String::new(),
+                ..sample()
+            },
+        )
+        .await;
+        assert_encoding(
+            Version::new(1, false),
+            &[0, 0, 0, 1, 0, 1, b'x', 0, 7, 0, 0, 0, 1, 3],
+            Sample {
+                note: String::new(),
+                ..sample()
+            },
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_flexible_version_with_tagged_fields() {
+        assert_encoding(
+            Version::new(2, false),
+            &[0, 0, 0, 1, 2, b'x', 2, 3, 0, 1, 0, 2, 2, b'n'],
+            Sample {
+                legacy: 0,
+                ..sample()
+            },
+        )
+        .await;
+
+        // Tagged fields holding their default value are omitted
+        let empty_note = Sample {
+            note: String::new(),
+            ..sample()
+        };
+        let mut buf = Vec::new();
+        empty_note
+            .write_to(&mut buf, Version::new(2, false))
+            .await
+            .unwrap();
+        assert_eq!(buf, [0, 0, 0, 1, 2, b'x', 2, 3, 0, 0]);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_tagged_fields_are_skipped() {
+        let buf = [0, 0, 0, 1, 2, b'x', 1, 2, 5, 1, 0xff, 0, 2, 2, b'n'];
+        let read = Sample::read_from(&mut buf.as_slice(), Version::new(2, false))
+            .await
+            .unwrap();
+        let decoded = Sample::decode(&mut buf.as_slice(), Version::new(2, false)).unwrap();
+        assert_eq!(read, decoded);
+        assert_eq!(
+            read,
+            Sample {
+                id: 1,
+                name: "x".into(),
+                note: "n".into(),
+                ..Sample::default()
+            }
+        );
+    }
+
+    #[derive(Debug, PartialEq, Read, Write)]
+    struct WithDefault {
+        #[kafka(versions = "1+", default = "-1")]
+        epoch: i64,
+    }
+
+    #[test]
+    fn test_decoding_past_the_end_fails() {
+        let buf = encode_to_vec(&sample(), Version::new(1, false)).unwrap();
+        let err = Sample::decode(&mut &buf[..buf.len() - 1], Version::new(1, false)).unwrap_err();
+        assert!(
+            matches!(&err, FormatError::Io(e) if e.kind() == std::io::ErrorKind::UnexpectedEof),
+            "{err}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_default_of_absent_field() {
+        let read = WithDefault::read_from(&mut [].as_slice(), Version::new(0, false))
+            .await
+            .unwrap();
+        assert_eq!(read, WithDefault { epoch: -1 });
+    }
+}
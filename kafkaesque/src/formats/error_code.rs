@@ -1,12 +1,14 @@
 use super::{
-    codec::{FixedLength, Read, Write},
+    codec::{FixedLength, Read, Version, Write},
     Result,
 };
+use bytes::{Buf, BufMut};
 use tokio::io::{AsyncRead, AsyncWrite};
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
 pub enum ErrorCode {
     UnknownServerError,
+    #[default]
     None,
     OffsetOutOfRange,
     CorruptMessage,
@@ -119,13 +121,10 @@ pub enum ErrorCode {
     Unsupported(i16),
 }
 
-impl Write for ErrorCode {
-    fn calculate_size(&self) -> i32 {
-        i16::SIZE
-    }
-    async fn write_to(&self, writer: &mut (dyn AsyncWrite + Send + Unpin)) -> Result<()> {
+impl From<ErrorCode> for i16 {
+    fn from(error_code: ErrorCode) -> Self {
         use ErrorCode::*;
-        let n = match self {
+        match error_code {
             UnknownServerError => -1,
             None => 0,
             OffsetOutOfRange => 1,
@@ -236,17 +235,15 @@ impl Write for ErrorCode {
             FetchSessionTopicIdError => 106,
             IneligibleReplica => 107,
             NewLeaderElected => 108,
-            Unsupported(n) => *n,
-        };
-        n.write_to(writer).await
+            Unsupported(n) => n,
+        }
     }
 }
 
-impl Read for ErrorCode {
-    async fn read_from(reader: &mut (dyn AsyncRead + Send + Unpin)) -> Result<Self> {
-        let n = i16::read_from(reader).await?;
+impl From<i16> for ErrorCode {
+    fn from(n: i16) -> Self {
         use ErrorCode::*;
-        let err_code = match n {
+        match n {
             -1 => UnknownServerError,
             0 => None,
             1 => OffsetOutOfRange,
@@ -358,8 +355,35 @@ impl Read for ErrorCode {
             107 => IneligibleReplica,
             108 => NewLeaderElected,
             n => Unsupported(n),
-        };
-        Ok(err_code)
+        }
+    }
+}
+
+impl Write for ErrorCode {
+    fn calculate_size(&self, _version: Version) -> i32 {
+        i16::SIZE
+    }
+    async fn write_to(
+        &self,
+        writer: &mut (dyn AsyncWrite + Send + Unpin),
+        version: Version,
+    ) -> Result<()> {
+        i16::from(*self).write_to(writer, version).await
+    }
+    fn encode<B: BufMut>(&self, buf: &mut B, version: Version) -> Result<()> {
+        i16::from(*self).encode(buf, version)
+    }
+}
+
+impl Read for ErrorCode {
+    async fn read_from(
+        reader: &mut (dyn AsyncRead + Send + Unpin),
+        version: Version,
+    ) -> Result<Self> {
+        Ok(i16::read_from(reader, version).await?.into())
+    }
+    fn decode<B: Buf>(buf: &mut B, version: Version) -> Result<Self> {
+        Ok(i16::decode(buf, version)?.into())
     }
 }
 
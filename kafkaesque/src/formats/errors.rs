@@ -1,3 +1,7 @@
+use super::{
+    api_keys::ApiKey, error_code::ErrorCode, limits::LengthKind, records::Compression,
+    supported_versions::VersionRange,
+};
 use std::string::FromUtf8Error;
 use thiserror::Error;
 
@@ -9,4 +13,52 @@ pub enum FormatError {
     Utf8Parsing(#[from] FromUtf8Error),
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
+    #[error("Unsupported record magic: {0}")]
+    UnsupportedMagic(i8),
+    #[error("Unsupported compression codec: {0}")]
+    UnsupportedCompression(i16),
+    #[error("Compression codec {0:?} requires enabling its cargo feature")]
+    DisabledCompression(Compression),
+    #[error("CRC mismatch: expected {expected:#010x}, computed {computed:#010x}")]
+    CrcMismatch { expected: u32, computed: u32 },
+    #[error("Corrupt record: {0}")]
+    CorruptRecord(String),
+    #[error(
+        "Unsupported version of {api_key:?}: client supports {client}, broker supports {}",
+        .broker.map_or("none".to_string(), |broker| broker.to_string())
+    )]
+    UnsupportedVersion {
+        api_key: ApiKey,
+        client: VersionRange,
+        broker: Option<VersionRange>,
+    },
+    #[error("Connection closed: {0}")]
+    ConnectionClosed(String),
+    #[error("Brokers don't respond to this {0:?} request, send it without waiting for a response")]
+    NoResponseExpected(ApiKey),
+    #[error("Brokers respond to {0:?} requests, send them waiting for a response")]
+    ResponseExpected(ApiKey),
+    #[error("Invalid response frame length: {0}")]
+    InvalidFrameLength(i32),
+    #[error("Received a response with unknown correlation id {0}")]
+    UnexpectedCorrelationId(i32),
+    #[error("Correlation id mismatch: expected {expected}, received {actual}")]
+    CorrelationIdMismatch { expected: i32, actual: i32 },
+    #[error("Null {0} where the protocol doesn't allow one")]
+    UnexpectedNull(LengthKind),
+    #[error("Invalid {kind} length {length}, the limit is {limit}")]
+    LengthOutOfBounds {
+        kind: LengthKind,
+        length: i64,
+        limit: usize,
+    },
+    #[error("{remaining} bytes left over after decoding a {api_key:?} message")]
+    TrailingBytes { api_key: ApiKey, remaining: usize },
+    #[error("Unsupported request: {api_key:?} v{api_version}")]
+    UnsupportedRequest { api_key: ApiKey, api_version: i16 },
+    #[error("Broker responded to {api_key:?} with error {error_code:?}")]
+    ErrorResponse {
+        api_key: ApiKey,
+        error_code: ErrorCode,
+    },
 }
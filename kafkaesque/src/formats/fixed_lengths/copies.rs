@@ -1,35 +1,49 @@
-use crate::formats::codec::{FixedLength, Read, Write};
+use crate::formats::codec::{ensure_remaining, FixedLength, Read, Version, Write};
 use crate::formats::Result;
+use bytes::{Buf, BufMut};
 use std::convert::identity;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
 macro_rules! fixed_length_copy_io_impl {
-    ($SelfT:ty, $size:expr, $write_map:expr, $write:expr, $read:expr, $read_map:expr,) => {
+    ($SelfT:ty, $size:expr, $write_map:expr, $write:expr, $put:expr, $read:expr, $get:expr, $read_map:expr,) => {
         impl FixedLength for $SelfT {
             const SIZE: i32 = $size;
         }
         impl Write for $SelfT {
-            fn calculate_size(&self) -> i32 {
+            fn calculate_size(&self, _version: Version) -> i32 {
                 $size
             }
             async fn write_to(
                 &self,
                 sink: &mut (dyn tokio::io::AsyncWrite + Send + Unpin),
+                _version: Version,
             ) -> Result<()> {
                 let to_be_written = ($write_map)(self);
                 $write(sink, *to_be_written).await?;
                 Ok(())
             }
+            fn encode<B: BufMut>(&self, buf: &mut B, _version: Version) -> Result<()> {
+                let to_be_written = ($write_map)(self);
+                $put(buf, *to_be_written);
+                Ok(())
+            }
         }
         #[allow(clippy::redundant_closure_call)]
         impl Read for $SelfT {
             async fn read_from(
                 source: &mut (dyn tokio::io::AsyncRead + Send + Unpin),
+                _version: Version,
             ) -> Result<Self> {
                 let read = ($read)(source).await?;
                 let s = $read_map(read);
                 Ok(s.into())
             }
+            fn decode<B: Buf>(buf: &mut B, _version: Version) -> Result<Self> {
+                ensure_remaining(buf, $size)?;
+                let read = $get(buf);
+                let s = $read_map(read);
+                Ok(s.into())
+            }
         }
     };
 }
@@ -39,7 +53,9 @@ fixed_length_copy_io_impl!(
     1,
     identity,
     AsyncWriteExt::write_i8,
+    BufMut::put_i8,
     AsyncReadExt::read_i8,
+    Buf::get_i8,
     identity,
 );
 
@@ -48,7 +64,9 @@ fixed_length_copy_io_impl!(
     2,
     identity,
     AsyncWriteExt::write_i16,
+    BufMut::put_i16,
     AsyncReadExt::read_i16,
+    Buf::get_i16,
     identity,
 );
 
@@ -57,7 +75,9 @@ fixed_length_copy_io_impl!(
     4,
     identity,
     AsyncWriteExt::write_i32,
+    BufMut::put_i32,
     AsyncReadExt::read_i32,
+    Buf::get_i32,
     identity,
 );
 
@@ -66,7 +86,9 @@ fixed_length_copy_io_impl!(
     8,
     identity,
     AsyncWriteExt::write_i64,
+    BufMut::put_i64,
     AsyncReadExt::read_i64,
+    Buf::get_i64,
     identity,
 );
 
@@ -75,6 +97,19 @@ fixed_length_copy_io_impl!(
     4,
     identity,
     AsyncWriteExt::write_u32,
+    BufMut::put_u32,
     AsyncReadExt::read_u32,
+    Buf::get_u32,
+    identity,
+);
+
+fixed_length_copy_io_impl!(
+    f64,
+    8,
+    identity,
+    AsyncWriteExt::write_f64,
+    BufMut::put_f64,
+    AsyncReadExt::read_f64,
+    Buf::get_f64,
     identity,
 );
@@ -1,2 +1,89 @@
 mod copies;
 mod noncopies;
+
+/// Encodings of every fixed-length primitive, written out by hand from the protocol guide
+/// (<https://kafka.apache.org/protocol#protocol_types>)
+#[cfg(test)]
+mod tests {
+    use crate::formats::codec::{assert_golden, Read, Version};
+    use uuid::Uuid;
+
+    const V: Version = Version::new(0, false);
+
+    #[tokio::test]
+    async fn test_boolean() {
+        assert_golden(false, V, &[0x00]).await;
+        assert_golden(true, V, &[0x01]).await;
+        // Any non-zero byte is true
+        assert!(bool::decode(&mut [0x02].as_slice(), V).unwrap());
+        assert!(bool::read_from(&mut [0xff].as_slice(), V).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_int8() {
+        assert_golden(0i8, V, &[0x00]).await;
+        assert_golden(-1i8, V, &[0xff]).await;
+        assert_golden(i8::MAX, V, &[0x7f]).await;
+        assert_golden(i8::MIN, V, &[0x80]).await;
+    }
+
+    #[tokio::test]
+    async fn test_int16() {
+        assert_golden(0i16, V, &[0x00, 0x00]).await;
+        assert_golden(-1i16, V, &[0xff, 0xff]).await;
+        assert_golden(0x0102i16, V, &[0x01, 0x02]).await;
+        assert_golden(i16::MIN, V, &[0x80, 0x00]).await;
+    }
+
+    #[tokio::test]
+    async fn test_int32() {
+        assert_golden(0i32, V, &[0x00, 0x00, 0x00, 0x00]).await;
+        assert_golden(-1i32, V, &[0xff, 0xff, 0xff, 0xff]).await;
+        assert_golden(0x01020304i32, V, &[0x01, 0x02, 0x03, 0x04]).await;
+        assert_golden(i32::MIN, V, &[0x80, 0x00, 0x00, 0x00]).await;
+    }
+
+    #[tokio::test]
+    async fn test_int64() {
+        assert_golden(0i64, V, &[0x00; 8]).await;
+        assert_golden(-1i64, V, &[0xff; 8]).await;
+        assert_golden(
+            0x0102030405060708i64,
+            V,
+            &[0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08],
+        )
+        .await;
+        assert_golden(i64::MIN, V, &[0x80, 0, 0, 0, 0, 0, 0, 0]).await;
+    }
+
+    #[tokio::test]
+    async fn test_uint32() {
+        assert_golden(0u32, V, &[0x00, 0x00, 0x00, 0x00]).await;
+        assert_golden(0xdeadbeefu32, V, &[0xde, 0xad, 0xbe, 0xef]).await;
+        assert_golden(u32::MAX, V, &[0xff, 0xff, 0xff, 0xff]).await;
+    }
+
+    #[tokio::test]
+    async fn test_float64() {
+        assert_golden(0.0f64, V, &[0x00; 8]).await;
+        assert_golden(1.0f64, V, &[0x3f, 0xf0, 0, 0, 0, 0, 0, 0]).await;
+        assert_golden(-2.5f64, V, &[0xc0, 0x04, 0, 0, 0, 0, 0, 0]).await;
+        assert_golden(f64::INFINITY, V, &[0x7f, 0xf0, 0, 0, 0, 0, 0, 0]).await;
+    }
+
+    #[tokio::test]
+    async fn test_uuid() {
+        assert_golden(Uuid::nil(), V, &[0x00; 16]).await;
+        // Most significant bits first, as `UUID.getMostSignificantBits()` then
+        // `getLeastSignificantBits()`
+        assert_golden(
+            Uuid::from_u128(0x00112233_4455_6677_8899_aabbccddeeff),
+            V,
+            &[
+                0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0xcc, 0xdd,
+                0xee, 0xff,
+            ],
+        )
+        .await;
+    }
+}
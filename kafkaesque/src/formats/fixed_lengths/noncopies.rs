@@ -1,103 +1,110 @@
 use crate::formats::{
-    codec::{FixedLength, Read, Write},
+    codec::{ensure_remaining, FixedLength, Read, Version, Write},
     Result,
 };
-use tokio::io::{AsyncRead, AsyncWrite};
+use bytes::{Buf, BufMut};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use uuid::Uuid;
 
 impl FixedLength for bool {
     const SIZE: i32 = 1;
 }
 
 impl Read for bool {
-    async fn read_from(reader: &mut (dyn AsyncRead + Send + Unpin)) -> Result<Self> {
-        Ok(i8::read_from(reader).await? == 0)
+    async fn read_from(
+        reader: &mut (dyn AsyncRead + Send + Unpin),
+        version: Version,
+    ) -> Result<Self> {
+        Ok(i8::read_from(reader, version).await? != 0)
+    }
+    fn decode<B: Buf>(buf: &mut B, version: Version) -> Result<Self> {
+        Ok(i8::decode(buf, version)? != 0)
     }
 }
 
 impl Write for bool {
-    fn calculate_size(&self) -> i32 {
+    fn calculate_size(&self, _version: Version) -> i32 {
         bool::SIZE
     }
-    async fn write_to(&self, writer: &mut (dyn AsyncWrite + Send + Unpin)) -> Result<()> {
+    async fn write_to(
+        &self,
+        writer: &mut (dyn AsyncWrite + Send + Unpin),
+        version: Version,
+    ) -> Result<()> {
+        let n: i8 = if *self { 1 } else { 0 };
+        n.write_to(writer, version).await
+    }
+    fn encode<B: BufMut>(&self, buf: &mut B, version: Version) -> Result<()> {
         let n: i8 = if *self { 1 } else { 0 };
-        n.write_to(writer).await
+        n.encode(buf, version)
     }
 }
 
-// use crate::Result;
-// use derive_more::{Display, From, Into};
-// use integer_encoding::VarIntAsyncReader;
-// use std::convert::identity;
-// use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
-
-// #[derive(Debug, Copy, Clone, Display, From, Into)]
-// pub struct VarInt(i32);
-
-// #[derive(Debug, Copy, Clone, Display, From, Into)]
-// pub struct VarLong(i64);
-
-// // #[derive(Debug, Clone, Display, From, Into)]
-// // pub struct UnsignedVarInt(pub u64);
+impl FixedLength for Uuid {
+    const SIZE: i32 = 16;
+}
 
-// macro_rules! var_number_io_impl {
-//     ($SelfT:ty, $size:expr, $write_map:expr, $write:expr, $read:expr, $read_map:expr,) => {
-//         impl $crate::io::Write for $SelfT {
-//             fn calculate_size(&self) -> i32 {
-//                 $size(self)
-//             }
-//             async fn write_to(
-//                 &self,
-//                 sink: &mut (dyn tokio::io::AsyncWrite + Send + Unpin),
-//             ) -> Result<()> {
-//                 let to_be_written = ($write_map)(self.0);
-//                 $write(sink, to_be_written).await?;
-//                 Ok(())
-//             }
-//         }
-//         #[allow(clippy::redundant_closure_call)]
-//         impl $crate::io::Read for $SelfT {
-//             async fn read_from(
-//                 source: &mut (dyn tokio::io::AsyncRead + Send + Unpin),
-//             ) -> Result<Self> {
-//                 let read = ($read)(source).await?;
-//                 let s = $read_map(read);
-//                 Ok(s.into())
-//             }
-//         }
-//     };
-// }
+impl Read for Uuid {
+    async fn read_from(
+        reader: &mut (dyn AsyncRead + Send + Unpin),
+        _version: Version,
+    ) -> Result<Self> {
+        let mut bytes = [0u8; 16];
+        reader.read_exact(&mut bytes).await?;
+        Ok(Uuid::from_bytes(bytes))
+    }
+    fn decode<B: Buf>(buf: &mut B, _version: Version) -> Result<Self> {
+        ensure_remaining(buf, Uuid::SIZE as usize)?;
+        let mut bytes = [0u8; 16];
+        buf.copy_to_slice(&mut bytes);
+        Ok(Uuid::from_bytes(bytes))
+    }
+}
 
-// var_number_io_impl!(
-//     VarInt,
-//     |n: &VarInt| integer_encoding::VarInt::required_space(i32::from(*n)) as i32,
-//     identity,
-//     write_varint_bytes,
-//     read_varint_bytes::<i32>,
-//     identity,
-// );
+impl Write for Uuid {
+    fn calculate_size(&self, _version: Version) -> i32 {
+        Uuid::SIZE
+    }
+    async fn write_to(
+        &self,
+        writer: &mut (dyn AsyncWrite + Send + Unpin),
+        _version: Version,
+    ) -> Result<()> {
+        writer.write_all(self.as_bytes()).await?;
+        Ok(())
+    }
+    fn encode<B: BufMut>(&self, buf: &mut B, _version: Version) -> Result<()> {
+        buf.put_slice(self.as_bytes());
+        Ok(())
+    }
+}
 
-// var_number_io_impl!(
-//     VarLong,
-//     |n: &VarLong| integer_encoding::VarInt::required_space(i64::from(*n)) as i32,
-//     identity,
-//     write_varint_bytes,
-//     read_varint_bytes::<i64>,
-//     identity,
-// );
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::encode_to_vec;
 
-// async fn write_varint_bytes(
-//     mut writer: impl AsyncWrite + Send + Unpin,
-//     n: impl integer_encoding::VarInt,
-// ) -> Result<usize> {
-//     let mut buf = [0_u8; 10];
-//     let b = n.encode_var(&mut buf);
-//     writer.write_all(&buf[0..b]).await?;
-//     Ok(b)
-// }
+    #[tokio::test]
+    async fn test_uuid_and_float64() {
+        let version = Version::default();
+        let id = Uuid::from_u128(0x0102_0304_0506_0708_090a_0b0c_0d0e_0f10);
+        let mut buf = Vec::new();
+        id.write_to(&mut buf, version).await.unwrap();
+        assert_eq!(buf, (1..=16).collect::<Vec<u8>>());
+        assert_eq!(encode_to_vec(&id, version).unwrap(), buf);
+        assert_eq!(
+            Uuid::read_from(&mut buf.as_slice(), version).await.unwrap(),
+            id
+        );
+        assert_eq!(Uuid::decode(&mut buf.as_slice(), version).unwrap(), id);
+        assert!(Uuid::decode(&mut &buf[..15], version).is_err());
 
-// async fn read_varint_bytes<VI: integer_encoding::VarInt>(
-//     mut reader: impl AsyncRead + Send + Unpin,
-// ) -> Result<VI> {
-//     let n = reader.read_varint_async().await?;
-//     Ok(n)
-// }
+        let buf = encode_to_vec(&-1.5f64, version).unwrap();
+        assert_eq!(buf, (-1.5f64).to_be_bytes());
+        assert_eq!(
+            f64::read_from(&mut buf.as_slice(), version).await.unwrap(),
+            -1.5
+        );
+        assert_eq!(f64::decode(&mut buf.as_slice(), version).unwrap(), -1.5);
+    }
+}
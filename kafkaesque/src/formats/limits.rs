@@ -0,0 +1,75 @@
+use super::{FormatError, Result};
+use std::fmt;
+
+/// Bounds on the lengths read from the wire, so that corrupt or hostile input fails with
+/// [FormatError::LengthOutOfBounds] instead of exhausting memory.
+///
+/// Decoders find them in the [Version](super::Version) they're given, and [BrokerConnection]
+/// sets them from its [ConnectionConfig].
+///
+/// [BrokerConnection]: super::BrokerConnection
+/// [ConnectionConfig]: super::ConnectionConfig
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeLimits {
+    /// Maximum size of a response frame in bytes, which also bounds every byte sequence and
+    /// record set within it
+    pub max_frame_size: usize,
+    /// Maximum number of elements of an array
+    pub max_array_length: usize,
+    /// Maximum length of a string in bytes
+    pub max_string_length: usize,
+}
+
+impl DecodeLimits {
+    /// Limits matching what brokers accept by default: 100 MiB requests (`socket.request.max.bytes`)
+    /// and strings of at most `i16::MAX` bytes
+    pub const DEFAULT: DecodeLimits = DecodeLimits {
+        max_frame_size: 100 * 1024 * 1024,
+        max_array_length: 1024 * 1024,
+        max_string_length: i16::MAX as usize,
+    };
+
+    /// Checks a length read from the wire against the limit of its kind, failing if it's
+    /// negative or too large
+    pub(crate) fn check(&self, kind: LengthKind, length: i64) -> Result<usize> {
+        let limit = match kind {
+            LengthKind::Frame | LengthKind::Bytes => self.max_frame_size,
+            LengthKind::Array => self.max_array_length,
+            LengthKind::String => self.max_string_length,
+        };
+        usize::try_from(length)
+            .ok()
+            .filter(|&length| length <= limit)
+            .ok_or(FormatError::LengthOutOfBounds {
+                kind,
+                length,
+                limit,
+            })
+    }
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        DecodeLimits::DEFAULT
+    }
+}
+
+/// What a length bounded by [DecodeLimits] measures
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LengthKind {
+    Frame,
+    Array,
+    String,
+    Bytes,
+}
+
+impl fmt::Display for LengthKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LengthKind::Frame => "frame",
+            LengthKind::Array => "array",
+            LengthKind::String => "string",
+            LengthKind::Bytes => "bytes",
+        })
+    }
+}
@@ -1,19 +1,19 @@
 use crate::formats::api_keys::ApiKey;
-use crate::formats::codec::{Read, Write};
-use crate::formats::request::{ApiVersion, RequestMessage};
+use crate::formats::codec::{Arbitrary, Read, Write};
+use crate::formats::request::RequestMessage;
 use crate::formats::ErrorCode;
 
-#[derive(Debug, Write, RequestMessage)]
-#[request_message(version = 0, key = "ApiVersions")]
+#[derive(Debug, Clone, PartialEq, Read, Write, Arbitrary, RequestMessage)]
+#[request_message(version = 0, key = "ApiVersions", response = "ApiVersionsResp")]
 pub struct ApiVersionsReq;
 
-#[derive(Debug, Read)]
+#[derive(Debug, Clone, PartialEq, Read, Write, Arbitrary)]
 pub struct ApiVersionsResp {
     pub error_code: ErrorCode,
     pub api_keys: Vec<ApiKeyVersionsReqV0Version>,
 }
 
-#[derive(Debug, Read)]
+#[derive(Debug, Clone, PartialEq, Read, Write, Arbitrary)]
 pub struct ApiKeyVersionsReqV0Version {
     pub api_key: ApiKey,
     pub min_version: i16,
@@ -23,15 +23,48 @@ pub struct ApiKeyVersionsReqV0Version {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::formats::{error_code::ErrorCode, BrokerConnection};
+    use crate::formats::messages::{ApiVersionsRequest, ApiVersionsResponse};
+    use crate::formats::{error_code::ErrorCode, ApiVersion, BrokerConnection};
+    use crate::testing::MockBroker;
+
+    #[tokio::test]
+    async fn test_api_versions_request_encoding() {
+        let req = ApiVersionsRequest {
+            client_software_name: "a".into(),
+            client_software_version: "b".into(),
+        };
+        for (api_version, expected) in [(0, vec![]), (3, vec![2, b'a', 2, b'b', 0])] {
+            let version = ApiVersionsRequest::version(ApiVersion(api_version));
+            let mut buf = Vec::new();
+            req.write_to(&mut buf, version).await.unwrap();
+            assert_eq!(buf, expected);
+            assert_eq!(req.calculate_size(version), buf.len() as i32);
+        }
+    }
 
     #[tokio::test]
     async fn test_api_versions() {
-        let mut conn = BrokerConnection::connect("test-client", "localhost:9092")
+        let broker = MockBroker::start().await.unwrap();
+        let conn = BrokerConnection::connect("test-client", broker.addr())
             .await
             .unwrap();
         let resp: ApiVersionsResp = conn.send(ApiVersionsReq).await.unwrap();
         assert_eq!(resp.error_code, ErrorCode::None);
         assert!(!resp.api_keys.is_empty())
     }
+
+    #[tokio::test]
+    async fn test_api_versions_v3() {
+        let broker = MockBroker::start().await.unwrap();
+        let conn = BrokerConnection::connect("test-client", broker.addr())
+            .await
+            .unwrap();
+        let req = ApiVersionsRequest {
+            client_software_name: "kafkaesque".into(),
+            client_software_version: env!("CARGO_PKG_VERSION").into(),
+        };
+        let resp: ApiVersionsResponse = conn.send(req).await.unwrap();
+        assert_eq!(resp.error_code, ErrorCode::None);
+        assert!(!resp.api_keys.is_empty())
+    }
 }
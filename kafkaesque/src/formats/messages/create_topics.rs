@@ -1,16 +1,15 @@
-use crate::formats::api_keys::ApiKey;
-use crate::formats::codec::{Read, Write};
-use crate::formats::request::{ApiVersion, RequestMessage};
+use crate::formats::codec::{Arbitrary, Read, Write};
+use crate::formats::request::RequestMessage;
 use crate::formats::{ErrorCode, NullableString};
 
-#[derive(Debug, Write, RequestMessage)]
-#[request_message(version = 0, key = "CreateTopics")]
+#[derive(Debug, Clone, PartialEq, Read, Write, Arbitrary, RequestMessage)]
+#[request_message(version = 0, key = "CreateTopics", response = "CreateTopicsRespV0")]
 pub struct CreateTopicsReqV0 {
     pub topics: Vec<CreateTopicsReqV0CreateTopic>,
     pub timeout_ms: i32,
 }
 
-#[derive(Debug, Write, Read)]
+#[derive(Debug, Clone, PartialEq, Read, Write, Arbitrary)]
 pub struct CreateTopicsReqV0CreateTopic {
     pub name: String,
     pub num_partitions: i32,
@@ -19,24 +18,24 @@ pub struct CreateTopicsReqV0CreateTopic {
     pub configs: Vec<CreateTopicsV0Config>,
 }
 
-#[derive(Debug, Write, Read)]
+#[derive(Debug, Clone, PartialEq, Read, Write, Arbitrary)]
 pub struct CreateTopicV0Assignments {
     pub partition_index: i32,
     pub broker_ids: Vec<i32>,
 }
 
-#[derive(Debug, Write, Read)]
+#[derive(Debug, Clone, PartialEq, Read, Write, Arbitrary)]
 pub struct CreateTopicsV0Config {
     pub name: String,
     pub value: NullableString,
 }
 
-#[derive(Debug, Write, Read)]
+#[derive(Debug, Clone, PartialEq, Read, Write, Arbitrary)]
 pub struct CreateTopicsRespV0 {
     pub topics: Vec<CreateTopicsRespV0Topic>,
 }
 
-#[derive(Debug, Write, Read)]
+#[derive(Debug, Clone, PartialEq, Read, Write, Arbitrary)]
 pub struct CreateTopicsRespV0Topic {
     pub name: String,
     pub err_code: ErrorCode,
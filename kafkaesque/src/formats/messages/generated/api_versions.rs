@@ -0,0 +1,91 @@
+// Generated by kafkaesque-codegen. Do not edit by hand.
+
+use crate::formats::codec::{Arbitrary, Read, Write};
+use crate::formats::request::RequestMessage;
+use crate::formats::ErrorCode;
+
+/// ApiVersions request (API key 18), versions 0-3
+#[derive(Debug, Clone, PartialEq, Default, Read, Write, Arbitrary, RequestMessage)]
+#[request_message(
+    versions = "0-3",
+    key = "ApiVersions",
+    response = "ApiVersionsResponse"
+)]
+#[kafka(flexible = "3+")]
+pub struct ApiVersionsRequest {
+    /// The name of the client.
+    #[kafka(versions = "3+")]
+    pub client_software_name: String,
+    /// The version of the client.
+    #[kafka(versions = "3+")]
+    pub client_software_version: String,
+}
+
+/// ApiVersions response (API key 18), versions 0-3
+#[derive(Debug, Clone, PartialEq, Read, Write, Arbitrary)]
+#[kafka(flexible = "3+")]
+pub struct ApiVersionsResponse {
+    /// The top-level error code.
+    pub error_code: ErrorCode,
+    /// The APIs supported by the broker.
+    pub api_keys: Vec<ApiVersionsResponseApiVersion>,
+    /// The duration in milliseconds for which the request was throttled due to a quota violation, or zero if the request did not violate any quota.
+    #[kafka(versions = "1+")]
+    pub throttle_time_ms: i32,
+    /// Features supported by the broker.
+    #[kafka(tagged = 0)]
+    pub supported_features: Vec<ApiVersionsResponseSupportedFeatureKey>,
+    /// The monotonically increasing epoch for the finalized features information. Valid values are >= 0. A value of -1 is special and represents unknown epoch.
+    #[kafka(tagged = 1, default = "-1")]
+    pub finalized_features_epoch: i64,
+    /// List of cluster-wide finalized features. The information is valid only if FinalizedFeaturesEpoch >= 0.
+    #[kafka(tagged = 2)]
+    pub finalized_features: Vec<ApiVersionsResponseFinalizedFeatureKey>,
+    /// Set by a KRaft controller if the required configurations for ZK migration are present
+    #[kafka(tagged = 3)]
+    pub zk_migration_ready: bool,
+}
+
+impl Default for ApiVersionsResponse {
+    fn default() -> Self {
+        ApiVersionsResponse {
+            error_code: Default::default(),
+            api_keys: Default::default(),
+            throttle_time_ms: Default::default(),
+            supported_features: Default::default(),
+            finalized_features_epoch: -1,
+            finalized_features: Default::default(),
+            zk_migration_ready: Default::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Read, Write, Arbitrary)]
+pub struct ApiVersionsResponseApiVersion {
+    /// The API index.
+    pub api_key: i16,
+    /// The minimum supported version, inclusive.
+    pub min_version: i16,
+    /// The maximum supported version, inclusive.
+    pub max_version: i16,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Read, Write, Arbitrary)]
+pub struct ApiVersionsResponseSupportedFeatureKey {
+    /// The name of the feature.
+    pub name: String,
+    /// The minimum supported version for the feature.
+    pub min_version: i16,
+    /// The maximum supported version for the feature.
+    pub max_version: i16,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Read, Write, Arbitrary)]
+pub struct ApiVersionsResponseFinalizedFeatureKey {
+    /// The name of the feature.
+    pub name: String,
+    /// The cluster-wide finalized max version level for the feature.
+    pub max_version_level: i16,
+    /// The cluster-wide finalized min version level for the feature.
+    pub min_version_level: i16,
+}
@@ -0,0 +1,258 @@
+// Generated by kafkaesque-codegen. Do not edit by hand.
+
+use crate::formats::codec::{Arbitrary, Read, Write};
+use crate::formats::records::RecordSet;
+use crate::formats::request::RequestMessage;
+use crate::formats::ErrorCode;
+use crate::formats::NullableString;
+use uuid::Uuid;
+
+/// Fetch request (API key 1), versions 0-13
+#[derive(Debug, Clone, PartialEq, Read, Write, Arbitrary, RequestMessage)]
+#[request_message(versions = "0-13", key = "Fetch", response = "FetchResponse")]
+#[kafka(flexible = "12+")]
+pub struct FetchRequest {
+    /// The clusterId if known. This is used to validate metadata fetches prior to broker registration.
+    #[kafka(tagged = 0)]
+    pub cluster_id: NullableString,
+    /// The broker ID of the follower, of -1 if this request is from a consumer.
+    pub replica_id: i32,
+    /// The maximum time in milliseconds to wait for the response.
+    pub max_wait_ms: i32,
+    /// The minimum bytes to accumulate in the response.
+    pub min_bytes: i32,
+    /// The maximum bytes to fetch.  See KIP-74 for cases where this limit may not be honored.
+    #[kafka(versions = "3+", default = "0x7fffffff")]
+    pub max_bytes: i32,
+    /// This setting controls the visibility of transactional records. Using READ_UNCOMMITTED (isolation_level = 0) makes all records visible. With READ_COMMITTED (isolation_level = 1), non-transactional and COMMITTED transactional records are visible. To be more concrete, READ_COMMITTED returns all data from offsets smaller than the current LSO (last stable offset), and enables the inclusion of the list of aborted transactions in the result, which allows consumers to discard ABORTED transactional records
+    #[kafka(versions = "4+")]
+    pub isolation_level: i8,
+    /// The fetch session ID.
+    #[kafka(versions = "7+")]
+    pub session_id: i32,
+    /// The fetch session epoch, which is used for ordering requests in a session.
+    #[kafka(versions = "7+", default = "-1")]
+    pub session_epoch: i32,
+    /// The topics to fetch.
+    pub topics: Vec<FetchRequestFetchTopic>,
+    /// In an incremental fetch request, the partitions to remove.
+    #[kafka(versions = "7+")]
+    pub forgotten_topics_data: Vec<FetchRequestForgottenTopic>,
+    /// Rack ID of the consumer making this request
+    #[kafka(versions = "11+")]
+    pub rack_id: String,
+}
+
+impl Default for FetchRequest {
+    fn default() -> Self {
+        FetchRequest {
+            cluster_id: Default::default(),
+            replica_id: Default::default(),
+            max_wait_ms: Default::default(),
+            min_bytes: Default::default(),
+            max_bytes: 0x7fffffff,
+            isolation_level: Default::default(),
+            session_id: Default::default(),
+            session_epoch: -1,
+            topics: Default::default(),
+            forgotten_topics_data: Default::default(),
+            rack_id: Default::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Read, Write, Arbitrary)]
+pub struct FetchRequestFetchTopic {
+    /// The name of the topic to fetch.
+    #[kafka(versions = "0-12")]
+    pub topic: String,
+    /// The unique topic ID
+    #[kafka(versions = "13+")]
+    pub topic_id: Uuid,
+    /// The partitions to fetch.
+    pub partitions: Vec<FetchRequestFetchPartition>,
+}
+
+#[derive(Debug, Clone, PartialEq, Read, Write, Arbitrary)]
+pub struct FetchRequestFetchPartition {
+    /// The partition index.
+    pub partition: i32,
+    /// The current leader epoch of the partition.
+    #[kafka(versions = "9+", default = "-1")]
+    pub current_leader_epoch: i32,
+    /// The message offset.
+    pub fetch_offset: i64,
+    /// The epoch of the last fetched record or -1 if there is none
+    #[kafka(versions = "12+", default = "-1")]
+    pub last_fetched_epoch: i32,
+    /// The earliest available offset of the follower replica.  The field is only used when the request is sent by the follower.
+    #[kafka(versions = "5+", default = "-1")]
+    pub log_start_offset: i64,
+    /// The maximum bytes to fetch from this partition.  See KIP-74 for cases where this limit may not be honored.
+    pub partition_max_bytes: i32,
+}
+
+impl Default for FetchRequestFetchPartition {
+    fn default() -> Self {
+        FetchRequestFetchPartition {
+            partition: Default::default(),
+            current_leader_epoch: -1,
+            fetch_offset: Default::default(),
+            last_fetched_epoch: -1,
+            log_start_offset: -1,
+            partition_max_bytes: Default::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Read, Write, Arbitrary)]
+pub struct FetchRequestForgottenTopic {
+    /// The topic name.
+    #[kafka(versions = "7-12")]
+    pub topic: String,
+    /// The unique topic ID
+    #[kafka(versions = "13+")]
+    pub topic_id: Uuid,
+    /// The partitions indexes to forget.
+    pub partitions: Vec<i32>,
+}
+
+/// Fetch response (API key 1), versions 0-13
+#[derive(Debug, Clone, PartialEq, Default, Read, Write, Arbitrary)]
+#[kafka(flexible = "12+")]
+pub struct FetchResponse {
+    /// The duration in milliseconds for which the request was throttled due to a quota violation, or zero if the request did not violate any quota.
+    #[kafka(versions = "1+")]
+    pub throttle_time_ms: i32,
+    /// The top level response error code.
+    #[kafka(versions = "7+")]
+    pub error_code: ErrorCode,
+    /// The fetch session ID, or 0 if this is not part of a fetch session.
+    #[kafka(versions = "7+")]
+    pub session_id: i32,
+    /// The response topics.
+    pub responses: Vec<FetchResponseFetchableTopicResponse>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Read, Write, Arbitrary)]
+pub struct FetchResponseFetchableTopicResponse {
+    /// The topic name.
+    #[kafka(versions = "0-12")]
+    pub topic: String,
+    /// The unique topic ID
+    #[kafka(versions = "13+")]
+    pub topic_id: Uuid,
+    /// The topic partitions.
+    pub partitions: Vec<FetchResponsePartitionData>,
+}
+
+#[derive(Debug, Clone, PartialEq, Read, Write, Arbitrary)]
+pub struct FetchResponsePartitionData {
+    /// The partition index.
+    pub partition_index: i32,
+    /// The error code, or 0 if there was no fetch error.
+    pub error_code: ErrorCode,
+    /// The current high water mark.
+    pub high_watermark: i64,
+    /// The last stable offset (or LSO) of the partition. This is the last offset such that the state of all transactional records prior to this offset have been decided (ABORTED or COMMITTED)
+    #[kafka(versions = "4+", default = "-1")]
+    pub last_stable_offset: i64,
+    /// The current log start offset.
+    #[kafka(versions = "5+", default = "-1")]
+    pub log_start_offset: i64,
+    /// In case divergence is detected based on the `LastFetchedEpoch` and `FetchOffset` in the request, this field indicates the largest epoch and its end offset such that subsequent records are known to diverge
+    #[kafka(tagged = 0)]
+    pub diverging_epoch: FetchResponseEpochEndOffset,
+    #[kafka(tagged = 1)]
+    pub current_leader: FetchResponseLeaderIdAndEpoch,
+    /// In the case of fetching an offset less than the LogStartOffset, this is the end offset and epoch that should be used in the FetchSnapshot request.
+    #[kafka(tagged = 2)]
+    pub snapshot_id: FetchResponseSnapshotId,
+    /// The aborted transactions.
+    #[kafka(versions = "4+")]
+    pub aborted_transactions: Option<Vec<FetchResponseAbortedTransaction>>,
+    /// The preferred read replica for the consumer to use on its next fetch request
+    #[kafka(versions = "11+", default = "-1")]
+    pub preferred_read_replica: i32,
+    /// The record data.
+    pub records: RecordSet,
+}
+
+impl Default for FetchResponsePartitionData {
+    fn default() -> Self {
+        FetchResponsePartitionData {
+            partition_index: Default::default(),
+            error_code: Default::default(),
+            high_watermark: Default::default(),
+            last_stable_offset: -1,
+            log_start_offset: -1,
+            diverging_epoch: Default::default(),
+            current_leader: Default::default(),
+            snapshot_id: Default::default(),
+            aborted_transactions: Default::default(),
+            preferred_read_replica: -1,
+            records: Default::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Read, Write, Arbitrary)]
+pub struct FetchResponseEpochEndOffset {
+    #[kafka(default = "-1")]
+    pub epoch: i32,
+    #[kafka(default = "-1")]
+    pub end_offset: i64,
+}
+
+impl Default for FetchResponseEpochEndOffset {
+    fn default() -> Self {
+        FetchResponseEpochEndOffset {
+            epoch: -1,
+            end_offset: -1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Read, Write, Arbitrary)]
+pub struct FetchResponseLeaderIdAndEpoch {
+    /// The ID of the current leader or -1 if the leader is unknown.
+    #[kafka(default = "-1")]
+    pub leader_id: i32,
+    /// The latest known leader epoch
+    #[kafka(default = "-1")]
+    pub leader_epoch: i32,
+}
+
+impl Default for FetchResponseLeaderIdAndEpoch {
+    fn default() -> Self {
+        FetchResponseLeaderIdAndEpoch {
+            leader_id: -1,
+            leader_epoch: -1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Read, Write, Arbitrary)]
+pub struct FetchResponseSnapshotId {
+    #[kafka(default = "-1")]
+    pub end_offset: i64,
+    #[kafka(default = "-1")]
+    pub epoch: i32,
+}
+
+impl Default for FetchResponseSnapshotId {
+    fn default() -> Self {
+        FetchResponseSnapshotId {
+            end_offset: -1,
+            epoch: -1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Read, Write, Arbitrary)]
+pub struct FetchResponseAbortedTransaction {
+    /// The producer id associated with the aborted transaction.
+    pub producer_id: i64,
+    /// The first offset in the aborted transaction.
+    pub first_offset: i64,
+}
@@ -0,0 +1,107 @@
+// Generated by kafkaesque-codegen. Do not edit by hand.
+
+use crate::formats::codec::{Arbitrary, Read, Write};
+use crate::formats::request::RequestMessage;
+use crate::formats::ErrorCode;
+
+/// ListOffsets request (API key 2), versions 0-7
+#[derive(Debug, Clone, PartialEq, Default, Read, Write, Arbitrary, RequestMessage)]
+#[request_message(
+    versions = "0-7",
+    key = "ListOffsets",
+    response = "ListOffsetsResponse"
+)]
+#[kafka(flexible = "6+")]
+pub struct ListOffsetsRequest {
+    /// The broker ID of the requestor, or -1 if this request is being made by a normal consumer.
+    pub replica_id: i32,
+    /// This setting controls the visibility of transactional records. Using READ_UNCOMMITTED (isolation_level = 0) makes all records visible. With READ_COMMITTED (isolation_level = 1), non-transactional and COMMITTED transactional records are visible. To be more concrete, READ_COMMITTED returns all data from offsets smaller than the current LSO (last stable offset), and enables the inclusion of the list of aborted transactions in the result, which allows consumers to discard ABORTED transactional records
+    #[kafka(versions = "2+")]
+    pub isolation_level: i8,
+    /// Each topic in the request.
+    pub topics: Vec<ListOffsetsRequestListOffsetsTopic>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Read, Write, Arbitrary)]
+pub struct ListOffsetsRequestListOffsetsTopic {
+    /// The topic name.
+    pub name: String,
+    /// Each partition in the request.
+    pub partitions: Vec<ListOffsetsRequestListOffsetsPartition>,
+}
+
+#[derive(Debug, Clone, PartialEq, Read, Write, Arbitrary)]
+pub struct ListOffsetsRequestListOffsetsPartition {
+    /// The partition index.
+    pub partition_index: i32,
+    /// The current leader epoch.
+    #[kafka(versions = "4+", default = "-1")]
+    pub current_leader_epoch: i32,
+    /// The current timestamp.
+    pub timestamp: i64,
+    /// The maximum number of offsets to report.
+    #[kafka(versions = "0", default = "1")]
+    pub max_num_offsets: i32,
+}
+
+impl Default for ListOffsetsRequestListOffsetsPartition {
+    fn default() -> Self {
+        ListOffsetsRequestListOffsetsPartition {
+            partition_index: Default::default(),
+            current_leader_epoch: -1,
+            timestamp: Default::default(),
+            max_num_offsets: 1,
+        }
+    }
+}
+
+/// ListOffsets response (API key 2), versions 0-7
+#[derive(Debug, Clone, PartialEq, Default, Read, Write, Arbitrary)]
+#[kafka(flexible = "6+")]
+pub struct ListOffsetsResponse {
+    /// The duration in milliseconds for which the request was throttled due to a quota violation, or zero if the request did not violate any quota.
+    #[kafka(versions = "2+")]
+    pub throttle_time_ms: i32,
+    /// Each topic in the response.
+    pub topics: Vec<ListOffsetsResponseListOffsetsTopicResponse>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Read, Write, Arbitrary)]
+pub struct ListOffsetsResponseListOffsetsTopicResponse {
+    /// The topic name
+    pub name: String,
+    /// Each partition in the response.
+    pub partitions: Vec<ListOffsetsResponseListOffsetsPartitionResponse>,
+}
+
+#[derive(Debug, Clone, PartialEq, Read, Write, Arbitrary)]
+pub struct ListOffsetsResponseListOffsetsPartitionResponse {
+    /// The partition index.
+    pub partition_index: i32,
+    /// The partition error code, or 0 if there was no error.
+    pub error_code: ErrorCode,
+    /// The result offsets.
+    #[kafka(versions = "0")]
+    pub old_style_offsets: Vec<i64>,
+    /// The timestamp associated with the returned offset.
+    #[kafka(versions = "1+", default = "-1")]
+    pub timestamp: i64,
+    /// The returned offset.
+    #[kafka(versions = "1+", default = "-1")]
+    pub offset: i64,
+    #[kafka(versions = "4+", default = "-1")]
+    pub leader_epoch: i32,
+}
+
+impl Default for ListOffsetsResponseListOffsetsPartitionResponse {
+    fn default() -> Self {
+        ListOffsetsResponseListOffsetsPartitionResponse {
+            partition_index: Default::default(),
+            error_code: Default::default(),
+            old_style_offsets: Default::default(),
+            timestamp: -1,
+            offset: -1,
+            leader_epoch: -1,
+        }
+    }
+}
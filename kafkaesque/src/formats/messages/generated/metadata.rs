@@ -0,0 +1,159 @@
+// Generated by kafkaesque-codegen. Do not edit by hand.
+
+use crate::formats::codec::{Arbitrary, Read, Write};
+use crate::formats::request::RequestMessage;
+use crate::formats::ErrorCode;
+use crate::formats::NullableString;
+use uuid::Uuid;
+
+/// Metadata request (API key 3), versions 0-12
+#[derive(Debug, Clone, PartialEq, Read, Write, Arbitrary, RequestMessage)]
+#[request_message(versions = "0-12", key = "Metadata", response = "MetadataResponse")]
+#[kafka(flexible = "9+")]
+pub struct MetadataRequest {
+    /// The topics to fetch metadata for.
+    pub topics: Option<Vec<MetadataRequestTopic>>,
+    /// If this is true, the broker may auto-create topics that we requested which do not already exist, if it is configured to do so.
+    #[kafka(versions = "4+", default = "true")]
+    pub allow_auto_topic_creation: bool,
+    /// Whether to include cluster authorized operations.
+    #[kafka(versions = "8-10")]
+    pub include_cluster_authorized_operations: bool,
+    /// Whether to include topic authorized operations.
+    #[kafka(versions = "8+")]
+    pub include_topic_authorized_operations: bool,
+}
+
+impl Default for MetadataRequest {
+    fn default() -> Self {
+        MetadataRequest {
+            topics: Default::default(),
+            allow_auto_topic_creation: true,
+            include_cluster_authorized_operations: Default::default(),
+            include_topic_authorized_operations: Default::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Read, Write, Arbitrary)]
+pub struct MetadataRequestTopic {
+    /// The topic id.
+    #[kafka(versions = "10+")]
+    pub topic_id: Uuid,
+    /// The topic name.
+    pub name: NullableString,
+}
+
+/// Metadata response (API key 3), versions 0-12
+#[derive(Debug, Clone, PartialEq, Read, Write, Arbitrary)]
+#[kafka(flexible = "9+")]
+pub struct MetadataResponse {
+    /// The duration in milliseconds for which the request was throttled due to a quota violation, or zero if the request did not violate any quota.
+    #[kafka(versions = "3+")]
+    pub throttle_time_ms: i32,
+    /// Each broker in the response.
+    pub brokers: Vec<MetadataResponseBroker>,
+    /// The cluster ID that responding broker belongs to.
+    #[kafka(versions = "2+")]
+    pub cluster_id: NullableString,
+    /// The ID of the controller broker.
+    #[kafka(versions = "1+", default = "-1")]
+    pub controller_id: i32,
+    /// Each topic in the response.
+    pub topics: Vec<MetadataResponseTopic>,
+    /// 32-bit bitfield to represent authorized operations for this cluster.
+    #[kafka(versions = "8-10", default = "-2147483648")]
+    pub cluster_authorized_operations: i32,
+}
+
+impl Default for MetadataResponse {
+    fn default() -> Self {
+        MetadataResponse {
+            throttle_time_ms: Default::default(),
+            brokers: Default::default(),
+            cluster_id: Default::default(),
+            controller_id: -1,
+            topics: Default::default(),
+            cluster_authorized_operations: -2147483648,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Read, Write, Arbitrary)]
+pub struct MetadataResponseBroker {
+    /// The broker ID.
+    pub node_id: i32,
+    /// The broker hostname.
+    pub host: String,
+    /// The broker port.
+    pub port: i32,
+    /// The rack of the broker, or null if it has not been assigned to a rack.
+    #[kafka(versions = "1+")]
+    pub rack: NullableString,
+}
+
+#[derive(Debug, Clone, PartialEq, Read, Write, Arbitrary)]
+pub struct MetadataResponseTopic {
+    /// The topic error, or 0 if there was no error.
+    pub error_code: ErrorCode,
+    /// The topic name.
+    pub name: NullableString,
+    /// The topic id.
+    #[kafka(versions = "10+")]
+    pub topic_id: Uuid,
+    /// True if the topic is internal.
+    #[kafka(versions = "1+")]
+    pub is_internal: bool,
+    /// Each partition in the topic.
+    pub partitions: Vec<MetadataResponsePartition>,
+    /// 32-bit bitfield to represent authorized operations for this topic.
+    #[kafka(versions = "8+", default = "-2147483648")]
+    pub topic_authorized_operations: i32,
+}
+
+impl Default for MetadataResponseTopic {
+    fn default() -> Self {
+        MetadataResponseTopic {
+            error_code: Default::default(),
+            name: Default::default(),
+            topic_id: Default::default(),
+            is_internal: Default::default(),
+            partitions: Default::default(),
+            topic_authorized_operations: -2147483648,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Read, Write, Arbitrary)]
+pub struct MetadataResponsePartition {
+    /// The partition error, or 0 if there was no error.
+    pub error_code: ErrorCode,
+    /// The partition index.
+    pub partition_index: i32,
+    /// The ID of the leader broker.
+    pub leader_id: i32,
+    /// The leader epoch of this partition.
+    #[kafka(versions = "7+", default = "-1")]
+    pub leader_epoch: i32,
+    /// The set of all nodes that host this partition.
+    pub replica_nodes: Vec<i32>,
+    /// The set of nodes that are in sync with the leader for this partition.
+    pub isr_nodes: Vec<i32>,
+    /// The set of offline replicas of this partition.
+    #[kafka(versions = "5+")]
+    pub offline_replicas: Vec<i32>,
+}
+
+impl Default for MetadataResponsePartition {
+    fn default() -> Self {
+        MetadataResponsePartition {
+            error_code: Default::default(),
+            partition_index: Default::default(),
+            leader_id: Default::default(),
+            leader_epoch: -1,
+            replica_nodes: Default::default(),
+            isr_nodes: Default::default(),
+            offline_replicas: Default::default(),
+        }
+    }
+}
@@ -0,0 +1,44 @@
+// Generated by kafkaesque-codegen. Do not edit by hand.
+
+mod api_versions;
+mod fetch;
+mod list_offsets;
+mod metadata;
+mod produce;
+
+pub use api_versions::*;
+pub use fetch::*;
+pub use list_offsets::*;
+pub use metadata::*;
+pub use produce::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::arbitrary::assert_messages_round_trip;
+
+    #[test]
+    fn test_api_versions_round_trip() {
+        assert_messages_round_trip::<ApiVersionsRequest>();
+    }
+
+    #[test]
+    fn test_fetch_round_trip() {
+        assert_messages_round_trip::<FetchRequest>();
+    }
+
+    #[test]
+    fn test_list_offsets_round_trip() {
+        assert_messages_round_trip::<ListOffsetsRequest>();
+    }
+
+    #[test]
+    fn test_metadata_round_trip() {
+        assert_messages_round_trip::<MetadataRequest>();
+    }
+
+    #[test]
+    fn test_produce_round_trip() {
+        assert_messages_round_trip::<ProduceRequest>();
+    }
+}
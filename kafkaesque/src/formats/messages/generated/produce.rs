@@ -0,0 +1,107 @@
+// Generated by kafkaesque-codegen. Do not edit by hand.
+
+use crate::formats::codec::{Arbitrary, Read, Write};
+use crate::formats::records::RecordSet;
+use crate::formats::request::RequestMessage;
+use crate::formats::ErrorCode;
+use crate::formats::NullableString;
+
+/// Produce request (API key 0), versions 0-9
+#[derive(Debug, Clone, PartialEq, Default, Read, Write, Arbitrary, RequestMessage)]
+#[request_message(
+    versions = "0-9",
+    key = "Produce",
+    response = "ProduceResponse",
+    expects_response = "self.acks != 0"
+)]
+#[kafka(flexible = "9+")]
+pub struct ProduceRequest {
+    /// The transactional ID, or null if the producer is not transactional.
+    #[kafka(versions = "3+")]
+    pub transactional_id: NullableString,
+    /// The number of acknowledgments the producer requires the leader to have received before considering a request complete. Allowed values: 0 for no acknowledgments, 1 for only the leader and -1 for the full ISR.
+    pub acks: i16,
+    /// The timeout to await a response in milliseconds.
+    pub timeout_ms: i32,
+    /// Each topic to produce to.
+    pub topic_data: Vec<ProduceRequestTopicProduceData>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Read, Write, Arbitrary)]
+pub struct ProduceRequestTopicProduceData {
+    /// The topic name.
+    pub name: String,
+    /// Each partition to produce to.
+    pub partition_data: Vec<ProduceRequestPartitionProduceData>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Read, Write, Arbitrary)]
+pub struct ProduceRequestPartitionProduceData {
+    /// The partition index.
+    pub index: i32,
+    /// The record data to be produced.
+    pub records: RecordSet,
+}
+
+/// Produce response (API key 0), versions 0-9
+#[derive(Debug, Clone, PartialEq, Default, Read, Write, Arbitrary)]
+#[kafka(flexible = "9+")]
+pub struct ProduceResponse {
+    /// Each produce response
+    pub responses: Vec<ProduceResponseTopicProduceResponse>,
+    /// The duration in milliseconds for which the request was throttled due to a quota violation, or zero if the request did not violate any quota.
+    #[kafka(versions = "1+")]
+    pub throttle_time_ms: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Read, Write, Arbitrary)]
+pub struct ProduceResponseTopicProduceResponse {
+    /// The topic name
+    pub name: String,
+    /// Each partition that we produced to within the topic.
+    pub partition_responses: Vec<ProduceResponsePartitionProduceResponse>,
+}
+
+#[derive(Debug, Clone, PartialEq, Read, Write, Arbitrary)]
+pub struct ProduceResponsePartitionProduceResponse {
+    /// The partition index.
+    pub index: i32,
+    /// The error code, or 0 if there was no error.
+    pub error_code: ErrorCode,
+    /// The base offset.
+    pub base_offset: i64,
+    /// The timestamp returned by broker after appending the messages. If CreateTime is used for the topic, the timestamp will be -1.  If LogAppendTime is used for the topic, the timestamp will be the broker local time when the messages are appended.
+    #[kafka(versions = "2+", default = "-1")]
+    pub log_append_time_ms: i64,
+    /// The log start offset.
+    #[kafka(versions = "5+", default = "-1")]
+    pub log_start_offset: i64,
+    /// The batch indices of records that caused the batch to be dropped
+    #[kafka(versions = "8+")]
+    pub record_errors: Vec<ProduceResponseBatchIndexAndErrorMessage>,
+    /// The global error message summarizing the common root cause of the records that caused the batch to be dropped
+    #[kafka(versions = "8+")]
+    pub error_message: NullableString,
+}
+
+impl Default for ProduceResponsePartitionProduceResponse {
+    fn default() -> Self {
+        ProduceResponsePartitionProduceResponse {
+            index: Default::default(),
+            error_code: Default::default(),
+            base_offset: Default::default(),
+            log_append_time_ms: -1,
+            log_start_offset: -1,
+            record_errors: Default::default(),
+            error_message: Default::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Read, Write, Arbitrary)]
+pub struct ProduceResponseBatchIndexAndErrorMessage {
+    /// The batch index of the record that cause the batch to be dropped
+    pub batch_index: i32,
+    /// The error message of the record that caused the batch to be dropped
+    pub batch_index_error_message: NullableString,
+}
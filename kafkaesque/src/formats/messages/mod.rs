@@ -1,9 +1,169 @@
 mod api_versions;
 mod create_topics;
 mod delete_topics;
+mod generated;
 mod metadata;
 
 pub use api_versions::*;
 pub use create_topics::*;
 pub use delete_topics::*;
+pub use generated::*;
 pub use metadata::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::{
+        arbitrary::assert_messages_round_trip, codec::assert_golden, ApiVersion, RequestMessage,
+    };
+
+    #[test]
+    fn test_messages_round_trip() {
+        assert_messages_round_trip::<ApiVersionsReq>();
+        assert_messages_round_trip::<CreateTopicsReqV0>();
+        assert_messages_round_trip::<DeleteTopicsReqV0>();
+        assert_messages_round_trip::<MetadataRequestV0>();
+    }
+
+    #[tokio::test]
+    async fn test_produce_request_encoding() {
+        let req = ProduceRequest {
+            transactional_id: None.into(),
+            acks: -1,
+            timeout_ms: 1000,
+            topic_data: vec![ProduceRequestTopicProduceData {
+                name: "t".into(),
+                partition_data: vec![ProduceRequestPartitionProduceData {
+                    index: 0,
+                    records: Default::default(),
+                }],
+            }],
+        };
+        #[rustfmt::skip]
+        let v3 = [
+            0xff, 0xff, // null transactional id
+            0xff, 0xff, 0, 0, 0x03, 0xe8, // acks, timeout
+            0, 0, 0, 1, 0, 1, b't', // topic
+            0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, // partition with no records
+        ];
+        #[rustfmt::skip]
+        let v9 = [
+            0, // null transactional id
+            0xff, 0xff, 0, 0, 0x03, 0xe8, // acks, timeout
+            2, 2, b't', // topic
+            2, 0, 0, 0, 0, 1, 0, // partition with no records
+            0, 0, // tagged fields of the topic and the request
+        ];
+        assert_golden(req.clone(), ProduceRequest::version(ApiVersion(3)), &v3).await;
+        assert_golden(req, ProduceRequest::version(ApiVersion(9)), &v9).await;
+    }
+
+    #[tokio::test]
+    async fn test_fetch_request_encoding() {
+        let req = FetchRequest {
+            replica_id: -1,
+            max_wait_ms: 500,
+            min_bytes: 1,
+            isolation_level: 1,
+            topics: vec![FetchRequestFetchTopic {
+                topic: "t".into(),
+                partitions: vec![FetchRequestFetchPartition {
+                    partition: 0,
+                    fetch_offset: 5,
+                    partition_max_bytes: 1024,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        #[rustfmt::skip]
+        let v4 = [
+            0xff, 0xff, 0xff, 0xff, 0, 0, 0x01, 0xf4, 0, 0, 0, 1, // replica id, max wait, min bytes
+            0x7f, 0xff, 0xff, 0xff, 1, // max bytes, isolation level
+            0, 0, 0, 1, 0, 1, b't', // topic
+            0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 5, 0, 0, 0x04, 0, // partition
+        ];
+        #[rustfmt::skip]
+        let v12 = [
+            0xff, 0xff, 0xff, 0xff, 0, 0, 0x01, 0xf4, 0, 0, 0, 1, // replica id, max wait, min bytes
+            0x7f, 0xff, 0xff, 0xff, 1, // max bytes, isolation level
+            0, 0, 0, 0, 0xff, 0xff, 0xff, 0xff, // session id and epoch
+            2, 2, b't', // topic
+            2, 0, 0, 0, 0, 0xff, 0xff, 0xff, 0xff, // partition, current leader epoch
+            0, 0, 0, 0, 0, 0, 0, 5, 0xff, 0xff, 0xff, 0xff, // fetch offset, last fetched epoch
+            0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0, 0, 0x04, 0, // log start offset, max bytes
+            0, 0, // tagged fields of the partition and the topic
+            1, 1, // no forgotten topics, empty rack id
+            0, // tagged fields of the request
+        ];
+        assert_golden(req.clone(), FetchRequest::version(ApiVersion(4)), &v4).await;
+        assert_golden(req, FetchRequest::version(ApiVersion(12)), &v12).await;
+    }
+
+    #[tokio::test]
+    async fn test_list_offsets_request_encoding() {
+        let req = ListOffsetsRequest {
+            replica_id: -1,
+            isolation_level: 0,
+            topics: vec![ListOffsetsRequestListOffsetsTopic {
+                name: "t".into(),
+                partitions: vec![ListOffsetsRequestListOffsetsPartition {
+                    partition_index: 0,
+                    timestamp: -1,
+                    ..Default::default()
+                }],
+            }],
+        };
+        #[rustfmt::skip]
+        let v0 = [
+            0xff, 0xff, 0xff, 0xff, // replica id
+            0, 0, 0, 1, 0, 1, b't', // topic
+            0, 0, 0, 1, 0, 0, 0, 0, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // partition
+            0, 0, 0, 1, // max number of offsets
+        ];
+        #[rustfmt::skip]
+        let v7 = [
+            0xff, 0xff, 0xff, 0xff, 0, // replica id, isolation level
+            2, 2, b't', // topic
+            2, 0, 0, 0, 0, 0xff, 0xff, 0xff, 0xff, // partition, current leader epoch
+            0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // timestamp
+            0, 0, 0, // tagged fields of the partition, the topic and the request
+        ];
+        assert_golden(req.clone(), ListOffsetsRequest::version(ApiVersion(0)), &v0).await;
+        assert_golden(req, ListOffsetsRequest::version(ApiVersion(7)), &v7).await;
+    }
+
+    #[tokio::test]
+    async fn test_metadata_request_encoding() {
+        let req = MetadataRequest {
+            topics: Some(vec![MetadataRequestTopic {
+                name: "t".into(),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        };
+        let v1 = [0, 0, 0, 1, 0, 1, b't'];
+        assert_golden(req.clone(), MetadataRequest::version(ApiVersion(1)), &v1).await;
+        let req = MetadataRequest {
+            allow_auto_topic_creation: false,
+            include_topic_authorized_operations: true,
+            ..req
+        };
+        #[rustfmt::skip]
+        let v9 = [
+            2, 2, b't', 0, // topic
+            0, 0, 1, // allow auto topic creation, include cluster and topic authorized operations
+            0, // tagged fields
+        ];
+        assert_golden(req, MetadataRequest::version(ApiVersion(9)), &v9).await;
+
+        // Since version 1, a null array requests every topic
+        let all_topics = MetadataRequest {
+            topics: None,
+            ..Default::default()
+        };
+        let v1 = [0xff, 0xff, 0xff, 0xff];
+        assert_golden(all_topics, MetadataRequest::version(ApiVersion(1)), &v1).await;
+    }
+}
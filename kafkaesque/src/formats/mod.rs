@@ -1,21 +1,38 @@
+mod any_request;
 mod api_keys;
+#[cfg(any(test, feature = "proptest"))]
+pub mod arbitrary;
 mod broker_connection;
 mod codec;
 mod error_code;
 mod errors;
 mod fixed_lengths;
+mod limits;
 mod request;
+mod supported_versions;
 mod variable_lengths;
 
 pub mod messages;
+pub mod records;
 
+pub use any_request::{decode_request, encode_response, AnyRequest};
 pub use api_keys::ApiKey;
-pub use broker_connection::BrokerConnection;
-pub use codec::{FixedLength, Read, Write};
+#[cfg(any(test, feature = "testing"))]
+pub(crate) use broker_connection::read_frame;
+pub use broker_connection::{BrokerConnection, ConnectionConfig};
+pub use codec::{encode_to_vec, FixedLength, Read, Version, Write};
 pub use error_code::ErrorCode;
 pub use errors::{FormatError, Result};
-pub use request::{ApiVersion, RequestMessage};
-pub use variable_lengths::NullableString;
+pub use limits::{DecodeLimits, LengthKind};
+pub use request::{ApiVersion, CorrelationId, RequestHeader, RequestMessage};
+pub use supported_versions::{SupportedVersions, VersionRange};
+pub use variable_lengths::{
+    CompactArray, CompactBytes, CompactNullableString, CompactString, NullableString, TaggedField,
+    TaggedFields, UnsignedVarInt, VarInt, VarLong,
+};
 
 /// Default TCP buffer size for [BrokerConnection] in bytes
 pub static DEFAULT_BUF_SIZE: usize = 8 * 1024;
+
+/// Default maximum number of requests awaiting a response on a [BrokerConnection]
+pub static DEFAULT_MAX_IN_FLIGHT: usize = 32;
@@ -0,0 +1,600 @@
+use super::{Compression, Record, UNVERSIONED};
+use crate::formats::{
+    codec::{encode_to_vec, read_bytes, take_shared, FixedLength, Read, Version, Write},
+    DecodeLimits, FormatError, LengthKind, Result,
+};
+use bytes::{Buf, BufMut, Bytes};
+use derive_more::{From, Into};
+use std::{
+    fmt,
+    ops::{Deref, DerefMut},
+    sync::OnceLock,
+};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use tracing::trace;
+
+/// The only magic value supported by [RecordBatch]
+const MAGIC: i8 = 2;
+
+/// Size of the fields preceding the records in a batch, starting from the attributes, which is
+/// where the CRC starts counting.
+const CRC_COVERED_HEADER_SIZE: i32 = i16::SIZE // attributes
+    + i32::SIZE // last_offset_delta
+    + i64::SIZE // base_timestamp
+    + i64::SIZE // max_timestamp
+    + i64::SIZE // producer_id
+    + i16::SIZE // producer_epoch
+    + i32::SIZE // base_sequence
+    + i32::SIZE; // records count
+
+/// Size of the fields between batch_length and attributes
+const CRC_UNCOVERED_HEADER_SIZE: i32 = i32::SIZE // partition_leader_epoch
+    + i8::SIZE // magic
+    + u32::SIZE; // crc
+
+/// A record batch (magic v2), the unit in which records are produced, stored and fetched
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordBatch {
+    pub base_offset: i64,
+    pub partition_leader_epoch: i32,
+    pub attributes: RecordBatchAttributes,
+    /// Offset delta of the last record in the batch
+    pub last_offset_delta: i32,
+    /// Timestamp of the first record in the batch
+    pub base_timestamp: i64,
+    /// Largest timestamp in the batch
+    pub max_timestamp: i64,
+    /// -1 unless the producer is idempotent or transactional
+    pub producer_id: i64,
+    /// -1 unless the producer is idempotent or transactional
+    pub producer_epoch: i16,
+    /// -1 unless the producer is idempotent or transactional
+    pub base_sequence: i32,
+    pub records: BatchRecords,
+}
+
+/// The records of a [RecordBatch], which derefs to a `Vec<Record>`. Their compressed encoding is
+/// kept once computed, so that sizing and writing a compressed batch compress it only once, and
+/// dropped on any mutable access.
+#[derive(Clone, Default)]
+pub struct BatchRecords {
+    records: Vec<Record>,
+    compressed: OnceLock<(Compression, Bytes)>,
+}
+
+/// Bit flags of a [RecordBatch]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, From, Into)]
+pub struct RecordBatchAttributes(pub i16);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimestampType {
+    CreateTime,
+    LogAppendTime,
+}
+
+impl RecordBatchAttributes {
+    const COMPRESSION_MASK: i16 = 0x07;
+    const TIMESTAMP_TYPE_FLAG: i16 = 0x08;
+    const TRANSACTIONAL_FLAG: i16 = 0x10;
+    const CONTROL_FLAG: i16 = 0x20;
+    const DELETE_HORIZON_FLAG: i16 = 0x40;
+
+    /// The compression codec stored in the lowest 3 bits
+    pub fn compression(self) -> Result<Compression> {
+        Compression::from_id(self.0 & Self::COMPRESSION_MASK)
+    }
+
+    pub fn with_compression(self, compression: Compression) -> Self {
+        Self((self.0 & !Self::COMPRESSION_MASK) | compression.id())
+    }
+
+    pub fn timestamp_type(self) -> TimestampType {
+        if self.0 & Self::TIMESTAMP_TYPE_FLAG == 0 {
+            TimestampType::CreateTime
+        } else {
+            TimestampType::LogAppendTime
+        }
+    }
+
+    pub fn with_timestamp_type(self, timestamp_type: TimestampType) -> Self {
+        match timestamp_type {
+            TimestampType::CreateTime => Self(self.0 & !Self::TIMESTAMP_TYPE_FLAG),
+            TimestampType::LogAppendTime => Self(self.0 | Self::TIMESTAMP_TYPE_FLAG),
+        }
+    }
+
+    pub fn is_transactional(self) -> bool {
+        self.0 & Self::TRANSACTIONAL_FLAG != 0
+    }
+
+    pub fn with_transactional(self, transactional: bool) -> Self {
+        self.with_flag(Self::TRANSACTIONAL_FLAG, transactional)
+    }
+
+    /// Whether the batch holds control records (e.g. transaction markers) instead of data
+    pub fn is_control(self) -> bool {
+        self.0 & Self::CONTROL_FLAG != 0
+    }
+
+    pub fn with_control(self, control: bool) -> Self {
+        self.with_flag(Self::CONTROL_FLAG, control)
+    }
+
+    /// Whether the base timestamp is the delete horizon set by log compaction
+    pub fn has_delete_horizon(self) -> bool {
+        self.0 & Self::DELETE_HORIZON_FLAG != 0
+    }
+
+    fn with_flag(self, flag: i16, set: bool) -> Self {
+        if set {
+            Self(self.0 | flag)
+        } else {
+            Self(self.0 & !flag)
+        }
+    }
+}
+
+impl BatchRecords {
+    pub fn into_vec(self) -> Vec<Record> {
+        self.records
+    }
+
+    fn uncompressed_size(&self) -> i32 {
+        self.records
+            .iter()
+            .map(|r| r.calculate_size(UNVERSIONED))
+            .sum()
+    }
+
+    fn encode_uncompressed(&self) -> Result<Vec<u8>> {
+        let mut encoded = Vec::with_capacity(self.uncompressed_size() as usize);
+        for record in &self.records {
+            record.encode(&mut encoded, UNVERSIONED)?;
+        }
+        Ok(encoded)
+    }
+
+    /// The records compressed with `codec`, compressing them unless they already were
+    fn compressed(&self, codec: Compression) -> Result<Bytes> {
+        match self.compressed.get() {
+            Some((cached, compressed)) if *cached == codec => Ok(compressed.clone()),
+            _ => {
+                let compressed = Bytes::from(codec.compress(&self.encode_uncompressed()?)?);
+                // Only fails if another codec was cached, which is then recompressed every time
+                let _ = self.compressed.set((codec, compressed.clone()));
+                Ok(compressed)
+            }
+        }
+    }
+}
+
+impl Deref for BatchRecords {
+    type Target = Vec<Record>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.records
+    }
+}
+
+impl DerefMut for BatchRecords {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.compressed = OnceLock::new();
+        &mut self.records
+    }
+}
+
+impl PartialEq for BatchRecords {
+    fn eq(&self, other: &Self) -> bool {
+        self.records == other.records
+    }
+}
+
+impl Eq for BatchRecords {}
+
+impl fmt::Debug for BatchRecords {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.records.fmt(f)
+    }
+}
+
+impl From<Vec<Record>> for BatchRecords {
+    fn from(records: Vec<Record>) -> Self {
+        BatchRecords {
+            records,
+            compressed: OnceLock::new(),
+        }
+    }
+}
+
+impl FromIterator<Record> for BatchRecords {
+    fn from_iter<I: IntoIterator<Item = Record>>(records: I) -> Self {
+        Vec::from_iter(records).into()
+    }
+}
+
+impl<'a> IntoIterator for &'a BatchRecords {
+    type Item = &'a Record;
+    type IntoIter = std::slice::Iter<'a, Record>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.records.iter()
+    }
+}
+
+impl RecordBatch {
+    /// Offset of the last record in the batch
+    pub fn last_offset(&self) -> i64 {
+        self.base_offset + self.last_offset_delta as i64
+    }
+
+    /// Absolute offset of a record of this batch
+    pub fn offset_of(&self, record: &Record) -> i64 {
+        self.base_offset + record.offset_delta as i64
+    }
+
+    /// Absolute timestamp of a record of this batch, honoring [TimestampType::LogAppendTime]
+    pub fn timestamp_of(&self, record: &Record) -> i64 {
+        match self.attributes.timestamp_type() {
+            TimestampType::CreateTime => self.base_timestamp + record.timestamp_delta,
+            TimestampType::LogAppendTime => self.max_timestamp,
+        }
+    }
+
+    /// Encodes the records, compressed with the codec set in the attributes
+    fn encode_records(&self) -> Result<Bytes> {
+        match self.attributes.compression()? {
+            Compression::None => Ok(self.records.encode_uncompressed()?.into()),
+            codec => self.records.compressed(codec),
+        }
+    }
+
+    /// Size of the encoded records. This compresses them if the batch is compressed, as there
+    /// is no other way of knowing the compressed size, and keeps them for encoding.
+    fn encoded_records_size(&self) -> Result<i32> {
+        match self.attributes.compression()? {
+            Compression::None => Ok(self.records.uncompressed_size()),
+            _ => Ok(self.encode_records()?.len() as i32),
+        }
+    }
+
+    /// Size of the encoded batch, failing if its records can't be encoded. Containers writing
+    /// the size ahead of the batch check it with this rather than
+    /// [calculate_size](Write::calculate_size), so that they fail before writing anything.
+    pub(crate) fn encoded_size(&self) -> Result<i32> {
+        Ok(i64::SIZE
+            + i32::SIZE
+            + CRC_UNCOVERED_HEADER_SIZE
+            + CRC_COVERED_HEADER_SIZE
+            + self.encoded_records_size()?)
+    }
+
+    fn encode_crc_covered(&self, encoded_records: &[u8]) -> Vec<u8> {
+        let mut covered =
+            Vec::with_capacity(CRC_COVERED_HEADER_SIZE as usize + encoded_records.len());
+        covered.put_i16(self.attributes.0);
+        covered.put_i32(self.last_offset_delta);
+        covered.put_i64(self.base_timestamp);
+        covered.put_i64(self.max_timestamp);
+        covered.put_i64(self.producer_id);
+        covered.put_i16(self.producer_epoch);
+        covered.put_i32(self.base_sequence);
+        covered.put_i32(self.records.len() as i32);
+        covered.put_slice(encoded_records);
+        covered
+    }
+
+    /// Decodes the batch from everything following its length. Uncompressed records share
+    /// `body`, and compressed ones share the buffer they're decompressed into.
+    fn decode_body(base_offset: i64, mut body: Bytes, limits: DecodeLimits) -> Result<Self> {
+        let body = &mut body;
+        let partition_leader_epoch = i32::decode(body, UNVERSIONED)?;
+        let magic = i8::decode(body, UNVERSIONED)?;
+        if magic != MAGIC {
+            return Err(FormatError::UnsupportedMagic(magic));
+        }
+        let crc = u32::decode(body, UNVERSIONED)?;
+        let computed_crc = crc32c::crc32c(&body[..]);
+        if crc != computed_crc {
+            return Err(FormatError::CrcMismatch {
+                expected: crc,
+                computed: computed_crc,
+            });
+        }
+
+        let attributes = RecordBatchAttributes(i16::decode(body, UNVERSIONED)?);
+        let last_offset_delta = i32::decode(body, UNVERSIONED)?;
+        let base_timestamp = i64::decode(body, UNVERSIONED)?;
+        let max_timestamp = i64::decode(body, UNVERSIONED)?;
+        let producer_id = i64::decode(body, UNVERSIONED)?;
+        let producer_epoch = i16::decode(body, UNVERSIONED)?;
+        let base_sequence = i32::decode(body, UNVERSIONED)?;
+        let record_count = i32::decode(body, UNVERSIONED)?;
+
+        let encoded = std::mem::take(body);
+        let body = &mut match attributes.compression()? {
+            Compression::None => encoded,
+            codec => Bytes::from(codec.decompress(&encoded, limits)?),
+        };
+        let mut records = BatchRecords::default();
+        for _ in 0..record_count {
+            records
+                .records
+                .push(Record::decode(body, UNVERSIONED.with_limits(limits))?);
+        }
+        if !body.is_empty() {
+            return Err(FormatError::CorruptRecord(format!(
+                "{} unread bytes at the end of record batch",
+                body.len()
+            )));
+        }
+
+        Ok(RecordBatch {
+            base_offset,
+            partition_leader_epoch,
+            attributes,
+            last_offset_delta,
+            base_timestamp,
+            max_timestamp,
+            producer_id,
+            producer_epoch,
+            base_sequence,
+            records,
+        })
+    }
+}
+
+fn check_batch_length(batch_length: i32, version: Version) -> Result<usize> {
+    trace!("reading record batch of len {batch_length}");
+    if batch_length < CRC_UNCOVERED_HEADER_SIZE + CRC_COVERED_HEADER_SIZE {
+        return Err(FormatError::CorruptRecord(format!(
+            "record batch length {batch_length} is too small"
+        )));
+    }
+    version.limits.check(LengthKind::Bytes, batch_length.into())
+}
+
+impl Write for RecordBatch {
+    /// Size of the encoded batch. Batches whose records can't be encoded are sized as if
+    /// uncompressed, as encoding them fails anyway.
+    fn calculate_size(&self, _version: Version) -> i32 {
+        self.encoded_size().unwrap_or_else(|_| {
+            i64::SIZE
+                + i32::SIZE
+                + CRC_UNCOVERED_HEADER_SIZE
+                + CRC_COVERED_HEADER_SIZE
+                + self.records.uncompressed_size()
+        })
+    }
+    async fn write_to(
+        &self,
+        writer: &mut (dyn AsyncWrite + Send + Unpin),
+        _version: Version,
+    ) -> Result<()> {
+        writer.write_all(&encode_to_vec(self, UNVERSIONED)?).await?;
+        Ok(())
+    }
+    fn encode<B: BufMut>(&self, buf: &mut B, _version: Version) -> Result<()> {
+        let covered = self.encode_crc_covered(&self.encode_records()?);
+        buf.put_i64(self.base_offset);
+        buf.put_i32(CRC_UNCOVERED_HEADER_SIZE + covered.len() as i32);
+        buf.put_i32(self.partition_leader_epoch);
+        buf.put_i8(MAGIC);
+        buf.put_u32(crc32c::crc32c(&covered));
+        buf.put_slice(&covered);
+        Ok(())
+    }
+}
+
+impl Read for RecordBatch {
+    async fn read_from(
+        reader: &mut (dyn AsyncRead + Send + Unpin),
+        version: Version,
+    ) -> Result<Self> {
+        let base_offset = i64::read_from(reader, UNVERSIONED).await?;
+        let batch_length = check_batch_length(i32::read_from(reader, UNVERSIONED).await?, version)?;
+        let buf = read_bytes(reader, batch_length).await?;
+        Self::decode_body(base_offset, buf.into(), version.limits)
+    }
+    fn decode<B: Buf>(buf: &mut B, version: Version) -> Result<Self> {
+        let base_offset = i64::decode(buf, UNVERSIONED)?;
+        let batch_length = check_batch_length(i32::decode(buf, UNVERSIONED)?, version)?;
+        Self::decode_body(base_offset, take_shared(buf, batch_length)?, version.limits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::{
+        codec::{assert_huge_length_rejected, HUGE_LENGTH},
+        records::{Header, RecordHeaders},
+    };
+
+    fn sample_batch() -> RecordBatch {
+        RecordBatch {
+            base_offset: 42,
+            partition_leader_epoch: 3,
+            attributes: RecordBatchAttributes::default().with_transactional(true),
+            last_offset_delta: 2,
+            base_timestamp: 1_670_000_000_000,
+            max_timestamp: 1_670_000_000_500,
+            producer_id: 7,
+            producer_epoch: 1,
+            base_sequence: 100,
+            records: vec![
+                Record {
+                    attributes: 0,
+                    timestamp_delta: 0,
+                    offset_delta: 0,
+                    key: Some(Bytes::from_static(b"key")),
+                    value: Some(Bytes::from_static(b"value")),
+                    headers: vec![Header {
+                        key: "trace-id".into(),
+                        value: Some(Bytes::from_static(b"abc")),
+                    }]
+                    .into(),
+                },
+                Record {
+                    attributes: 0,
+                    timestamp_delta: 250,
+                    offset_delta: 1,
+                    key: None,
+                    value: Some(vec![0xff; 300].into()),
+                    headers: RecordHeaders::EMPTY,
+                },
+                Record {
+                    attributes: 0,
+                    timestamp_delta: 500,
+                    offset_delta: 2,
+                    key: Some(Bytes::new()),
+                    value: None,
+                    headers: vec![Header {
+                        key: "empty".into(),
+                        value: None,
+                    }]
+                    .into(),
+                },
+            ]
+            .into(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_record_batch_round_trip() {
+        let batch = sample_batch();
+        let mut buf = Vec::new();
+        batch.write_to(&mut buf, UNVERSIONED).await.unwrap();
+        assert_eq!(batch.calculate_size(UNVERSIONED), buf.len() as i32);
+
+        let read = RecordBatch::read_from(&mut buf.as_slice(), UNVERSIONED)
+            .await
+            .unwrap();
+        assert_eq!(read, batch);
+        assert_eq!(read.last_offset(), 44);
+        assert_eq!(read.offset_of(&read.records[1]), 43);
+        assert_eq!(read.timestamp_of(&read.records[2]), 1_670_000_000_500);
+    }
+
+    #[tokio::test]
+    async fn test_compressed_record_batch_round_trip() {
+        let codecs = [
+            Compression::Gzip,
+            Compression::Snappy,
+            Compression::Lz4,
+            Compression::Zstd,
+        ];
+        for codec in codecs.into_iter().filter(|c| c.is_enabled()) {
+            let batch = sample_batch();
+            let batch = RecordBatch {
+                attributes: batch.attributes.with_compression(codec),
+                ..batch
+            };
+            let mut buf = Vec::new();
+            batch.write_to(&mut buf, UNVERSIONED).await.unwrap();
+            assert_eq!(
+                batch.calculate_size(UNVERSIONED),
+                buf.len() as i32,
+                "{codec:?}"
+            );
+            assert!(buf.len() < sample_batch().calculate_size(UNVERSIONED) as usize);
+
+            let read = RecordBatch::read_from(&mut buf.as_slice(), UNVERSIONED)
+                .await
+                .unwrap();
+            assert_eq!(read, batch, "{codec:?}");
+        }
+    }
+
+    #[test]
+    fn test_compressed_records_are_kept() {
+        let codecs = [
+            Compression::Gzip,
+            Compression::Snappy,
+            Compression::Lz4,
+            Compression::Zstd,
+        ];
+        for codec in codecs.into_iter().filter(|c| c.is_enabled()) {
+            let batch = sample_batch();
+            let mut batch = RecordBatch {
+                attributes: batch.attributes.with_compression(codec),
+                ..batch
+            };
+            let size = batch.calculate_size(UNVERSIONED);
+            let compressed = batch.records.compressed.get().unwrap().1.clone();
+            let encoded = encode_to_vec(&batch, UNVERSIONED).unwrap();
+            assert_eq!(encoded.len() as i32, size, "{codec:?}");
+            assert_eq!(
+                batch.records.compressed(codec).unwrap().as_ptr(),
+                compressed.as_ptr(),
+                "{codec:?}"
+            );
+
+            // Only batches being written keep their compressed records
+            let decoded = RecordBatch::decode(&mut Bytes::from(encoded), UNVERSIONED).unwrap();
+            assert!(decoded.records.compressed.get().is_none(), "{codec:?}");
+
+            batch.records.truncate(1);
+            assert!(batch.records.compressed.get().is_none(), "{codec:?}");
+            let encoded = encode_to_vec(&batch, UNVERSIONED).unwrap();
+            assert_eq!(encoded.len() as i32, batch.calculate_size(UNVERSIONED));
+            let decoded = RecordBatch::decode(&mut Bytes::from(encoded), UNVERSIONED).unwrap();
+            assert_eq!(decoded.records.len(), 1, "{codec:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_record_batch_header_layout() {
+        let batch = RecordBatch {
+            records: BatchRecords::default(),
+            ..sample_batch()
+        };
+        let mut buf = Vec::new();
+        batch.write_to(&mut buf, UNVERSIONED).await.unwrap();
+        assert_eq!(buf.len(), 61);
+        assert_eq!(&buf[0..8], &42i64.to_be_bytes());
+        // batch_length counts everything after itself
+        assert_eq!(&buf[8..12], &49i32.to_be_bytes());
+        assert_eq!(buf[16], MAGIC as u8);
+        let crc = u32::from_be_bytes(buf[17..21].try_into().unwrap());
+        assert_eq!(crc, crc32c::crc32c(&buf[21..]));
+    }
+
+    #[tokio::test]
+    async fn test_record_batch_crc_mismatch() {
+        let mut buf = Vec::new();
+        sample_batch()
+            .write_to(&mut buf, UNVERSIONED)
+            .await
+            .unwrap();
+        let last = buf.len() - 1;
+        buf[last] ^= 0x01;
+
+        let err = RecordBatch::read_from(&mut buf.as_slice(), UNVERSIONED)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, FormatError::CrcMismatch { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_record_batch_unsupported_magic() {
+        let mut buf = Vec::new();
+        sample_batch()
+            .write_to(&mut buf, UNVERSIONED)
+            .await
+            .unwrap();
+        buf[16] = 1;
+
+        let err = RecordBatch::read_from(&mut buf.as_slice(), UNVERSIONED)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, FormatError::UnsupportedMagic(1)));
+    }
+
+    #[test]
+    fn test_huge_length_with_short_body() {
+        let mut buf = 42i64.to_be_bytes().to_vec();
+        buf.extend((HUGE_LENGTH as i32).to_be_bytes());
+        buf.extend([0; 64]);
+        assert_huge_length_rejected::<RecordBatch>(&buf, UNVERSIONED);
+    }
+}
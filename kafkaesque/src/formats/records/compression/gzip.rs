@@ -0,0 +1,14 @@
+use super::read_bounded;
+use crate::formats::{DecodeLimits, Result};
+use flate2::{read::GzDecoder, write::GzEncoder};
+use std::io::Write;
+
+pub fn compress(data: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(data)?;
+    Ok(encoder.finish()?)
+}
+
+pub fn decompress(data: &[u8], limits: DecodeLimits) -> Result<Vec<u8>> {
+    read_bounded(GzDecoder::new(data), limits)
+}
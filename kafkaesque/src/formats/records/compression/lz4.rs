@@ -0,0 +1,18 @@
+use super::read_bounded;
+use crate::formats::{DecodeLimits, Result};
+use lz4_flex::frame::{BlockMode, BlockSize, FrameDecoder, FrameEncoder, FrameInfo};
+use std::io::Write;
+
+/// Kafka uses the LZ4 frame format with 64KB independent blocks
+pub fn compress(data: &[u8]) -> Result<Vec<u8>> {
+    let frame_info = FrameInfo::new()
+        .block_size(BlockSize::Max64KB)
+        .block_mode(BlockMode::Independent);
+    let mut encoder = FrameEncoder::with_frame_info(frame_info, Vec::new());
+    encoder.write_all(data)?;
+    Ok(encoder.finish().map_err(std::io::Error::from)?)
+}
+
+pub fn decompress(data: &[u8], limits: DecodeLimits) -> Result<Vec<u8>> {
+    read_bounded(FrameDecoder::new(data), limits)
+}
@@ -0,0 +1,209 @@
+#[cfg(feature = "gzip")]
+mod gzip;
+#[cfg(feature = "lz4")]
+mod lz4;
+#[cfg(feature = "snappy")]
+mod snappy;
+#[cfg(feature = "zstd")]
+mod zstd;
+
+use crate::formats::{DecodeLimits, FormatError, Result};
+
+/// Compression codecs of record batches and legacy message sets. Every codec other than
+/// [Compression::None] is only available with its cargo feature enabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Snappy,
+    Lz4,
+    Zstd,
+}
+
+impl Compression {
+    /// Parses the codec id stored in the lowest 3 bits of batch or message attributes
+    pub fn from_id(id: i16) -> Result<Self> {
+        match id {
+            0 => Ok(Compression::None),
+            1 => Ok(Compression::Gzip),
+            2 => Ok(Compression::Snappy),
+            3 => Ok(Compression::Lz4),
+            4 => Ok(Compression::Zstd),
+            n => Err(FormatError::UnsupportedCompression(n)),
+        }
+    }
+
+    pub fn id(self) -> i16 {
+        match self {
+            Compression::None => 0,
+            Compression::Gzip => 1,
+            Compression::Snappy => 2,
+            Compression::Lz4 => 3,
+            Compression::Zstd => 4,
+        }
+    }
+
+    /// Whether the codec's cargo feature is enabled
+    pub fn is_enabled(self) -> bool {
+        match self {
+            Compression::None => true,
+            Compression::Gzip => cfg!(feature = "gzip"),
+            Compression::Snappy => cfg!(feature = "snappy"),
+            Compression::Lz4 => cfg!(feature = "lz4"),
+            Compression::Zstd => cfg!(feature = "zstd"),
+        }
+    }
+
+    pub fn compress(self, data: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            Compression::None => Ok(data.to_vec()),
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => gzip::compress(data),
+            #[cfg(feature = "snappy")]
+            Compression::Snappy => snappy::compress(data),
+            #[cfg(feature = "lz4")]
+            Compression::Lz4 => lz4::compress(data),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => zstd::compress(data),
+            #[allow(unreachable_patterns)]
+            codec => Err(FormatError::DisabledCompression(codec)),
+        }
+    }
+
+    /// Decompresses `data`, failing with [FormatError::LengthOutOfBounds] rather than
+    /// decompressing more than the frame size of `limits`
+    pub fn decompress(self, data: &[u8], limits: DecodeLimits) -> Result<Vec<u8>> {
+        match self {
+            Compression::None => {
+                check_size(data.len(), limits)?;
+                Ok(data.to_vec())
+            }
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => gzip::decompress(data, limits),
+            #[cfg(feature = "snappy")]
+            Compression::Snappy => snappy::decompress(data, limits),
+            #[cfg(feature = "lz4")]
+            Compression::Lz4 => lz4::decompress(data, limits),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => zstd::decompress(data, limits),
+            #[allow(unreachable_patterns)]
+            codec => Err(FormatError::DisabledCompression(codec)),
+        }
+    }
+}
+
+/// Reads a decompressing stream to its end, stopping one byte past the frame size of `limits`
+/// to tell whether it's exceeded
+#[cfg(any(feature = "gzip", feature = "lz4", feature = "zstd"))]
+fn read_bounded(decoder: impl std::io::Read, limits: DecodeLimits) -> Result<Vec<u8>> {
+    use std::io::Read;
+
+    let mut decompressed = Vec::new();
+    decoder
+        .take(limits.max_frame_size as u64 + 1)
+        .read_to_end(&mut decompressed)?;
+    check_size(decompressed.len(), limits)?;
+    Ok(decompressed)
+}
+
+/// Checks the size of decompressed data against the frame size of `limits`
+fn check_size(size: usize, limits: DecodeLimits) -> Result<usize> {
+    let size = size.try_into().unwrap_or(i64::MAX);
+    limits.check(crate::formats::LengthKind::Bytes, size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_of_enabled_codecs() {
+        let data = b"kafkaesque ".repeat(10_000);
+        for codec in [
+            Compression::None,
+            Compression::Gzip,
+            Compression::Snappy,
+            Compression::Lz4,
+            Compression::Zstd,
+        ] {
+            if codec.is_enabled() {
+                let compressed = codec.compress(&data).unwrap();
+                assert_eq!(
+                    codec
+                        .decompress(&compressed, DecodeLimits::DEFAULT)
+                        .unwrap(),
+                    data,
+                    "{codec:?}"
+                );
+            } else {
+                assert!(matches!(
+                    codec.compress(&data),
+                    Err(FormatError::DisabledCompression(c)) if c == codec
+                ));
+            }
+        }
+    }
+
+    /// Checks that a small input expanding past a low limit fails to decompress, while the
+    /// same input decompresses under a limit matching its size
+    #[cfg(any(
+        feature = "gzip",
+        feature = "lz4",
+        feature = "snappy",
+        feature = "zstd"
+    ))]
+    fn assert_bounded(codec: Compression, compressed: &[u8], size: usize) {
+        let low = DecodeLimits {
+            max_frame_size: 1024,
+            ..DecodeLimits::DEFAULT
+        };
+        assert!(compressed.len() < low.max_frame_size, "{codec:?}");
+        assert!(matches!(
+            codec.decompress(compressed, low),
+            Err(FormatError::LengthOutOfBounds {
+                kind: crate::formats::LengthKind::Bytes,
+                limit: 1024,
+                ..
+            })
+        ));
+        let exact = DecodeLimits {
+            max_frame_size: size,
+            ..DecodeLimits::DEFAULT
+        };
+        assert_eq!(codec.decompress(compressed, exact).unwrap().len(), size);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_gzip_decompression_limit() {
+        let data = vec![0; 16 * 1024];
+        let compressed = Compression::Gzip.compress(&data).unwrap();
+        assert_bounded(Compression::Gzip, &compressed, data.len());
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_lz4_decompression_limit() {
+        let data = vec![0; 16 * 1024];
+        let compressed = Compression::Lz4.compress(&data).unwrap();
+        assert_bounded(Compression::Lz4, &compressed, data.len());
+    }
+
+    #[cfg(feature = "snappy")]
+    #[test]
+    fn test_snappy_decompression_limit() {
+        let data = vec![0; 16 * 1024];
+        let xerial = Compression::Snappy.compress(&data).unwrap();
+        assert_bounded(Compression::Snappy, &xerial, data.len());
+        let raw = snap::raw::Encoder::new().compress_vec(&data).unwrap();
+        assert_bounded(Compression::Snappy, &raw, data.len());
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_zstd_decompression_limit() {
+        let data = vec![0; 16 * 1024];
+        let compressed = Compression::Zstd.compress(&data).unwrap();
+        assert_bounded(Compression::Zstd, &compressed, data.len());
+    }
+}
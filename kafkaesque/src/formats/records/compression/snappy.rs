@@ -0,0 +1,63 @@
+use super::check_size;
+use crate::formats::{DecodeLimits, FormatError, Result};
+use snap::raw::{decompress_len, Decoder, Encoder};
+
+/// Header of the framing used by the Java client (xerial snappy-java), followed by two i32s:
+/// the framing version and the minimum compatible version.
+const XERIAL_MAGIC: [u8; 8] = [0x82, b'S', b'N', b'A', b'P', b'P', b'Y', 0];
+const XERIAL_VERSION: i32 = 1;
+const XERIAL_HEADER_SIZE: usize = XERIAL_MAGIC.len() + 8;
+
+/// Uncompressed size of each block written in xerial framing
+const XERIAL_BLOCK_SIZE: usize = 32 * 1024;
+
+/// Compresses into xerial framing, which is the only snappy framing the Java client reads
+pub fn compress(data: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = Encoder::new();
+    let mut compressed = Vec::with_capacity(XERIAL_HEADER_SIZE + data.len());
+    compressed.extend_from_slice(&XERIAL_MAGIC);
+    compressed.extend_from_slice(&XERIAL_VERSION.to_be_bytes());
+    compressed.extend_from_slice(&XERIAL_VERSION.to_be_bytes());
+    for chunk in data.chunks(XERIAL_BLOCK_SIZE) {
+        let block = encoder.compress_vec(chunk).map_err(std::io::Error::from)?;
+        compressed.extend_from_slice(&(block.len() as i32).to_be_bytes());
+        compressed.extend_from_slice(&block);
+    }
+    Ok(compressed)
+}
+
+/// Decompresses either xerial framing or a single raw snappy block, as some non-Java
+/// producers write the latter. Block headers give their decompressed size, which is checked
+/// against the frame size of `limits` before allocating.
+pub fn decompress(data: &[u8], limits: DecodeLimits) -> Result<Vec<u8>> {
+    let mut decoder = Decoder::new();
+    if !data.starts_with(&XERIAL_MAGIC) || data.len() < XERIAL_HEADER_SIZE {
+        check_size(block_len(data)?, limits)?;
+        return Ok(decoder.decompress_vec(data).map_err(std::io::Error::from)?);
+    }
+
+    let mut decompressed = Vec::new();
+    let mut rest = &data[XERIAL_HEADER_SIZE..];
+    while !rest.is_empty() {
+        let block_size = rest
+            .get(..4)
+            .map(|size| i32::from_be_bytes(size.try_into().expect("4 bytes")))
+            .and_then(|size| usize::try_from(size).ok())
+            .filter(|&size| rest.len() >= 4 + size)
+            .ok_or_else(|| FormatError::CorruptRecord("truncated snappy block".into()))?;
+        let block = &rest[4..4 + block_size];
+        check_size(decompressed.len().saturating_add(block_len(block)?), limits)?;
+        decompressed.extend(
+            decoder
+                .decompress_vec(block)
+                .map_err(std::io::Error::from)?,
+        );
+        rest = &rest[4 + block_size..];
+    }
+    Ok(decompressed)
+}
+
+/// Decompressed size of a raw snappy block, read from its header
+fn block_len(block: &[u8]) -> Result<usize> {
+    Ok(decompress_len(block).map_err(std::io::Error::from)?)
+}
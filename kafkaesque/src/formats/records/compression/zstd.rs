@@ -0,0 +1,10 @@
+use super::read_bounded;
+use crate::formats::{DecodeLimits, Result};
+
+pub fn compress(data: &[u8]) -> Result<Vec<u8>> {
+    Ok(::zstd::encode_all(data, ::zstd::DEFAULT_COMPRESSION_LEVEL)?)
+}
+
+pub fn decompress(data: &[u8], limits: DecodeLimits) -> Result<Vec<u8>> {
+    read_bounded(::zstd::Decoder::new(data)?, limits)
+}
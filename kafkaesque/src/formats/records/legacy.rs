@@ -0,0 +1,254 @@
+use super::{Compression, UNVERSIONED};
+use crate::formats::{
+    codec::{ensure_remaining, read_bytes, take_shared, FixedLength, Read, Version, Write},
+    DecodeLimits, FormatError, LengthKind, Result,
+};
+use bytes::{Buf, BufMut, Bytes};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tracing::trace;
+
+/// A message of the legacy message set format (magic v0 and v1)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    pub magic: i8,
+    pub attributes: i8,
+    /// Only present with magic v1
+    pub timestamp: Option<i64>,
+    pub key: Option<Bytes>,
+    /// The compressed inner message set when the message is a wrapper
+    pub value: Option<Bytes>,
+}
+
+/// An entry of a legacy message set
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageSetEntry {
+    pub offset: i64,
+    pub message: Message,
+}
+
+impl Message {
+    const COMPRESSION_MASK: i8 = 0x07;
+    const TIMESTAMP_TYPE_FLAG: i8 = 0x08;
+
+    pub fn compression(&self) -> Result<Compression> {
+        Compression::from_id((self.attributes & Self::COMPRESSION_MASK) as i16)
+    }
+
+    /// Whether the message timestamp was set by the broker when the message was appended
+    pub fn is_log_append_time(&self) -> bool {
+        self.magic > 0 && self.attributes & Self::TIMESTAMP_TYPE_FLAG != 0
+    }
+
+    /// Size of everything the CRC is computed over
+    fn crc_covered_size(&self) -> i32 {
+        i8::SIZE
+            + i8::SIZE
+            + self.timestamp.map(|_| i64::SIZE).unwrap_or(0)
+            + bytes_size(self.key.as_deref())
+            + bytes_size(self.value.as_deref())
+    }
+
+    fn encode_crc_covered(&self) -> Vec<u8> {
+        let mut covered = Vec::with_capacity(self.crc_covered_size() as usize);
+        covered.put_i8(self.magic);
+        covered.put_i8(self.attributes);
+        if let Some(timestamp) = self.timestamp {
+            covered.put_i64(timestamp);
+        }
+        put_bytes(&mut covered, self.key.as_deref());
+        put_bytes(&mut covered, self.value.as_deref());
+        covered
+    }
+
+    /// Decodes the message after checking `covered` against its CRC
+    fn decode_crc_covered(crc: u32, covered: Bytes) -> Result<Self> {
+        let computed_crc = crc32fast::hash(&covered);
+        if crc != computed_crc {
+            return Err(FormatError::CrcMismatch {
+                expected: crc,
+                computed: computed_crc,
+            });
+        }
+        let body = &mut covered.clone();
+
+        let magic = i8::decode(body, UNVERSIONED)?;
+        let attributes = i8::decode(body, UNVERSIONED)?;
+        let timestamp = match magic {
+            0 => None,
+            1 => Some(i64::decode(body, UNVERSIONED)?),
+            m => return Err(FormatError::UnsupportedMagic(m)),
+        };
+        let key = decode_bytes(body)?;
+        let value = decode_bytes(body)?;
+        if !body.is_empty() {
+            return Err(FormatError::CorruptRecord(format!(
+                "{} unread bytes at the end of message",
+                body.len()
+            )));
+        }
+
+        Ok(Message {
+            magic,
+            attributes,
+            timestamp,
+            key,
+            value,
+        })
+    }
+}
+
+impl Write for Message {
+    fn calculate_size(&self, _version: Version) -> i32 {
+        u32::SIZE + self.crc_covered_size()
+    }
+    async fn write_to(
+        &self,
+        writer: &mut (dyn AsyncWrite + Send + Unpin),
+        _version: Version,
+    ) -> Result<()> {
+        let covered = self.encode_crc_covered();
+        crc32fast::hash(&covered)
+            .write_to(writer, UNVERSIONED)
+            .await?;
+        writer.write_all(&covered).await?;
+        Ok(())
+    }
+    fn encode<B: BufMut>(&self, buf: &mut B, _version: Version) -> Result<()> {
+        let covered = self.encode_crc_covered();
+        buf.put_u32(crc32fast::hash(&covered));
+        buf.put_slice(&covered);
+        Ok(())
+    }
+}
+
+impl Read for Message {
+    /// Reads a message until the reader is exhausted, so the reader must be bounded by the
+    /// message size of the enclosing [MessageSetEntry]
+    async fn read_from(
+        reader: &mut (dyn AsyncRead + Send + Unpin),
+        _version: Version,
+    ) -> Result<Self> {
+        let crc = u32::read_from(reader, UNVERSIONED).await?;
+        let mut covered = Vec::new();
+        reader.read_to_end(&mut covered).await?;
+        Self::decode_crc_covered(crc, covered.into())
+    }
+    /// Decodes a message spanning the rest of the buffer
+    fn decode<B: Buf>(buf: &mut B, _version: Version) -> Result<Self> {
+        let crc = u32::decode(buf, UNVERSIONED)?;
+        let covered = buf.copy_to_bytes(buf.remaining());
+        Self::decode_crc_covered(crc, covered)
+    }
+}
+
+impl MessageSetEntry {
+    /// Decompresses wrapper messages into their inner messages, with absolute offsets and
+    /// timestamps. Uncompressed messages are returned as is, and decompressed ones may not
+    /// exceed the frame size of `limits`.
+    pub fn flatten(self, limits: DecodeLimits) -> Result<Vec<MessageSetEntry>> {
+        let compression = self.message.compression()?;
+        if compression == Compression::None {
+            return Ok(vec![self]);
+        }
+
+        let compressed = self.message.value.as_deref().unwrap_or_default();
+        let mut rest = Bytes::from(compression.decompress(compressed, limits)?);
+        let mut inner = Vec::new();
+        while !rest.is_empty() {
+            let entry = MessageSetEntry::decode(&mut rest, UNVERSIONED)?;
+            if entry.message.compression()? != Compression::None {
+                return Err(FormatError::CorruptRecord(
+                    "compressed messages must not be nested".into(),
+                ));
+            }
+            inner.push(entry);
+        }
+
+        // Since magic v1, inner offsets are relative and the wrapper carries the absolute
+        // offset of the last inner message
+        if self.message.magic > 0 {
+            let last_relative_offset = inner.last().map(|e| e.offset).unwrap_or(0);
+            for entry in &mut inner {
+                entry.offset += self.offset - last_relative_offset;
+            }
+        }
+        if self.message.is_log_append_time() {
+            for entry in &mut inner {
+                entry.message.timestamp = self.message.timestamp;
+            }
+        }
+        Ok(inner)
+    }
+}
+
+impl Write for MessageSetEntry {
+    fn calculate_size(&self, _version: Version) -> i32 {
+        i64::SIZE + i32::SIZE + self.message.calculate_size(UNVERSIONED)
+    }
+    async fn write_to(
+        &self,
+        writer: &mut (dyn AsyncWrite + Send + Unpin),
+        _version: Version,
+    ) -> Result<()> {
+        self.offset.write_to(writer, UNVERSIONED).await?;
+        self.message
+            .calculate_size(UNVERSIONED)
+            .write_to(writer, UNVERSIONED)
+            .await?;
+        self.message.write_to(writer, UNVERSIONED).await
+    }
+    fn encode<B: BufMut>(&self, buf: &mut B, _version: Version) -> Result<()> {
+        buf.put_i64(self.offset);
+        buf.put_i32(self.message.calculate_size(UNVERSIONED));
+        self.message.encode(buf, UNVERSIONED)
+    }
+}
+
+impl Read for MessageSetEntry {
+    async fn read_from(
+        reader: &mut (dyn AsyncRead + Send + Unpin),
+        version: Version,
+    ) -> Result<Self> {
+        let offset = i64::read_from(reader, UNVERSIONED).await?;
+        let message_size = i32::read_from(reader, UNVERSIONED).await?;
+        trace!("reading legacy message of size {message_size}");
+        let buf = read_bytes(reader, message_size_of(message_size, version)?).await?;
+        let message = Message::decode(&mut Bytes::from(buf), UNVERSIONED)?;
+        Ok(MessageSetEntry { offset, message })
+    }
+    fn decode<B: Buf>(buf: &mut B, version: Version) -> Result<Self> {
+        let offset = i64::decode(buf, UNVERSIONED)?;
+        let message_size = message_size_of(i32::decode(buf, UNVERSIONED)?, version)?;
+        ensure_remaining(buf, message_size)?;
+        let message = Message::decode(&mut buf.take(message_size), UNVERSIONED)?;
+        Ok(MessageSetEntry { offset, message })
+    }
+}
+
+fn message_size_of(message_size: i32, version: Version) -> Result<usize> {
+    version.limits.check(LengthKind::Bytes, message_size.into())
+}
+
+fn bytes_size(bytes: Option<&[u8]>) -> i32 {
+    i32::SIZE + bytes.map(|b| b.len() as i32).unwrap_or(0)
+}
+
+/// Puts BYTES where -1 means null
+fn put_bytes(buf: &mut impl BufMut, bytes: Option<&[u8]>) {
+    match bytes {
+        Some(bytes) => {
+            buf.put_i32(bytes.len() as i32);
+            buf.put_slice(bytes);
+        }
+        None => buf.put_i32(-1),
+    }
+}
+
+/// Decodes BYTES where -1 means null, sharing `buf` when it's [Bytes]
+fn decode_bytes(buf: &mut impl Buf) -> Result<Option<Bytes>> {
+    let len = i32::decode(buf, UNVERSIONED)?;
+    if len < 0 {
+        return Ok(None);
+    }
+    Ok(Some(take_shared(buf, len as usize)?))
+}
@@ -0,0 +1,16 @@
+mod batch;
+mod compression;
+mod legacy;
+mod record;
+mod record_set;
+
+use super::codec::Version;
+
+pub use batch::{BatchRecords, RecordBatch, RecordBatchAttributes, TimestampType};
+pub use compression::Compression;
+pub use legacy::{Message, MessageSetEntry};
+pub use record::{Header, Record, RecordHeaders};
+pub use record_set::{RecordRef, RecordSet, RecordSetEntry};
+
+/// Records are encoded the same way whatever the API version of the message carrying them
+const UNVERSIONED: Version = Version::new(0, false);
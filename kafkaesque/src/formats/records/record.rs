@@ -0,0 +1,285 @@
+use super::UNVERSIONED;
+use crate::formats::{
+    codec::{
+        encode_to_vec, ensure_remaining, read_bytes, take_shared, FixedLength, Read, Version, Write,
+    },
+    DecodeLimits, FormatError, LengthKind, Result, VarInt, VarLong,
+};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+
+/// A single record inside a [RecordBatch](super::RecordBatch).
+///
+/// Records decoded from a [Bytes] buffer, such as the frames read by
+/// [BrokerConnection](crate::formats::BrokerConnection), share it instead of copying their keys,
+/// values and headers out of it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Record {
+    /// Unused by the protocol at the moment
+    pub attributes: i8,
+    /// Difference between the record's timestamp and the batch's base timestamp
+    pub timestamp_delta: i64,
+    /// Difference between the record's offset and the batch's base offset
+    pub offset_delta: i32,
+    pub key: Option<Bytes>,
+    pub value: Option<Bytes>,
+    pub headers: RecordHeaders,
+}
+
+/// A record header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    pub key: String,
+    pub value: Option<Bytes>,
+}
+
+/// The headers of a [Record], kept encoded until they're iterated over so that consumers which
+/// never look at them don't pay for parsing them
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecordHeaders {
+    count: usize,
+    encoded: Bytes,
+}
+
+impl RecordHeaders {
+    pub const EMPTY: RecordHeaders = RecordHeaders {
+        count: 0,
+        encoded: Bytes::new(),
+    };
+
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Parses the headers one at a time, failing on the first corrupt one
+    pub fn iter(&self) -> impl Iterator<Item = Result<Header>> + '_ {
+        let mut encoded = self.encoded.clone();
+        (0..self.count).map(move |_| Header::decode(&mut encoded, UNVERSIONED))
+    }
+
+    /// Parses all headers
+    pub fn parse(&self) -> Result<Vec<Header>> {
+        self.iter().collect()
+    }
+}
+
+impl FromIterator<Header> for RecordHeaders {
+    fn from_iter<I: IntoIterator<Item = Header>>(headers: I) -> Self {
+        let mut encoded = BytesMut::new();
+        let mut count = 0;
+        for header in headers {
+            header.put(&mut encoded);
+            count += 1;
+        }
+        RecordHeaders {
+            count,
+            encoded: encoded.freeze(),
+        }
+    }
+}
+
+impl From<Vec<Header>> for RecordHeaders {
+    fn from(headers: Vec<Header>) -> Self {
+        headers.into_iter().collect()
+    }
+}
+
+impl Header {
+    fn put(&self, buf: &mut impl BufMut) {
+        put_varint_bytes(buf, Some(self.key.as_bytes()));
+        put_varint_bytes(buf, self.value.as_deref());
+    }
+}
+
+impl Record {
+    /// Size of the record's body, excluding its VARINT length prefix
+    fn body_size(&self) -> i32 {
+        i8::SIZE
+            + VarLong(self.timestamp_delta).calculate_size(UNVERSIONED)
+            + VarInt(self.offset_delta).calculate_size(UNVERSIONED)
+            + varint_bytes_size(self.key.as_deref())
+            + varint_bytes_size(self.value.as_deref())
+            + VarInt(self.headers.count as i32).calculate_size(UNVERSIONED)
+            + self.headers.encoded.len() as i32
+    }
+
+    /// Decodes the record's body, which must span all of `body`. The headers take up whatever
+    /// follows the value, and are only checked when parsed.
+    fn decode_body(body: &mut impl Buf, limits: DecodeLimits) -> Result<Self> {
+        let attributes = i8::decode(body, UNVERSIONED)?;
+        let timestamp_delta = VarLong::decode(body, UNVERSIONED)?.0;
+        let offset_delta = VarInt::decode(body, UNVERSIONED)?.0;
+        let key = decode_varint_bytes(body)?;
+        let value = decode_varint_bytes(body)?;
+        let header_count = VarInt::decode(body, UNVERSIONED)?.0;
+        let header_count = limits.check(LengthKind::Array, header_count.into())?;
+        let headers = RecordHeaders {
+            count: header_count,
+            encoded: body.copy_to_bytes(body.remaining()),
+        };
+
+        Ok(Record {
+            attributes,
+            timestamp_delta,
+            offset_delta,
+            key,
+            value,
+            headers,
+        })
+    }
+}
+
+fn record_length(length: i32, version: Version) -> Result<usize> {
+    version.limits.check(LengthKind::Bytes, length.into())
+}
+
+impl Write for Record {
+    fn calculate_size(&self, _version: Version) -> i32 {
+        let body_size = self.body_size();
+        VarInt(body_size).calculate_size(UNVERSIONED) + body_size
+    }
+    async fn write_to(
+        &self,
+        writer: &mut (dyn AsyncWrite + Send + Unpin),
+        _version: Version,
+    ) -> Result<()> {
+        writer.write_all(&encode_to_vec(self, UNVERSIONED)?).await?;
+        Ok(())
+    }
+    fn encode<B: BufMut>(&self, buf: &mut B, _version: Version) -> Result<()> {
+        put_varint(buf, self.body_size());
+        buf.put_i8(self.attributes);
+        put_varint(buf, self.timestamp_delta);
+        put_varint(buf, self.offset_delta);
+        put_varint_bytes(buf, self.key.as_deref());
+        put_varint_bytes(buf, self.value.as_deref());
+        put_varint(buf, self.headers.count as i32);
+        buf.put_slice(&self.headers.encoded);
+        Ok(())
+    }
+}
+
+impl Read for Record {
+    async fn read_from(
+        reader: &mut (dyn AsyncRead + Send + Unpin),
+        version: Version,
+    ) -> Result<Self> {
+        let length = record_length(VarInt::read_from(reader, UNVERSIONED).await?.0, version)?;
+        let buf = read_bytes(reader, length).await?;
+        Self::decode_body(&mut Bytes::from(buf), version.limits)
+    }
+    fn decode<B: Buf>(buf: &mut B, version: Version) -> Result<Self> {
+        let length = record_length(VarInt::decode(buf, UNVERSIONED)?.0, version)?;
+        ensure_remaining(buf, length)?;
+        Self::decode_body(&mut buf.take(length), version.limits)
+    }
+}
+
+impl Write for Header {
+    fn calculate_size(&self, _version: Version) -> i32 {
+        varint_bytes_size(Some(self.key.as_bytes())) + varint_bytes_size(self.value.as_deref())
+    }
+    async fn write_to(
+        &self,
+        writer: &mut (dyn AsyncWrite + Send + Unpin),
+        _version: Version,
+    ) -> Result<()> {
+        write_varint_bytes(writer, Some(self.key.as_bytes())).await?;
+        write_varint_bytes(writer, self.value.as_deref()).await
+    }
+    fn encode<B: BufMut>(&self, buf: &mut B, _version: Version) -> Result<()> {
+        self.put(buf);
+        Ok(())
+    }
+}
+
+impl Read for Header {
+    async fn read_from(
+        reader: &mut (dyn AsyncRead + Send + Unpin),
+        version: Version,
+    ) -> Result<Self> {
+        let key = read_varint_bytes(reader, version)
+            .await?
+            .ok_or_else(|| FormatError::CorruptRecord("null header key".into()))?;
+        let value = read_varint_bytes(reader, version).await?;
+        Ok(Header {
+            key: String::from_utf8(key)?,
+            value: value.map(Bytes::from),
+        })
+    }
+    fn decode<B: Buf>(buf: &mut B, _version: Version) -> Result<Self> {
+        let key = decode_varint_bytes(buf)?
+            .ok_or_else(|| FormatError::CorruptRecord("null header key".into()))?;
+        let value = decode_varint_bytes(buf)?;
+        Ok(Header {
+            key: String::from_utf8(key.to_vec())?,
+            value,
+        })
+    }
+}
+
+fn varint_bytes_size(bytes: Option<&[u8]>) -> i32 {
+    match bytes {
+        Some(bytes) => VarInt(bytes.len() as i32).calculate_size(UNVERSIONED) + bytes.len() as i32,
+        None => VarInt(-1).calculate_size(UNVERSIONED),
+    }
+}
+
+fn put_varint(buf: &mut impl BufMut, n: impl integer_encoding::VarInt) {
+    let mut encoded = [0u8; 10];
+    let len = n.encode_var(&mut encoded);
+    buf.put_slice(&encoded[..len]);
+}
+
+fn put_varint_bytes(buf: &mut impl BufMut, bytes: Option<&[u8]>) {
+    match bytes {
+        Some(bytes) => {
+            put_varint(buf, bytes.len() as i32);
+            buf.put_slice(bytes);
+        }
+        None => put_varint(buf, -1i32),
+    }
+}
+
+/// Synchronous counterpart of [read_varint_bytes], sharing `buf` when it's [Bytes]
+fn decode_varint_bytes(buf: &mut impl Buf) -> Result<Option<Bytes>> {
+    let len = VarInt::decode(buf, UNVERSIONED)?.0;
+    if len < 0 {
+        return Ok(None);
+    }
+    Ok(Some(take_shared(buf, len as usize)?))
+}
+
+/// Writes a byte sequence prefixed by its VARINT length, where -1 means null
+async fn write_varint_bytes(
+    writer: &mut (dyn AsyncWrite + Send + Unpin),
+    bytes: Option<&[u8]>,
+) -> Result<()> {
+    match bytes {
+        Some(bytes) => {
+            VarInt(bytes.len() as i32)
+                .write_to(writer, UNVERSIONED)
+                .await?;
+            writer.write_all(bytes).await?;
+            Ok(())
+        }
+        None => VarInt(-1).write_to(writer, UNVERSIONED).await,
+    }
+}
+
+/// Reads a byte sequence prefixed by its VARINT length, where -1 means null
+async fn read_varint_bytes(
+    reader: &mut (dyn AsyncRead + Send + Unpin),
+    version: Version,
+) -> Result<Option<Vec<u8>>> {
+    let len = VarInt::read_from(reader, UNVERSIONED).await?.0;
+    if len < 0 {
+        return Ok(None);
+    }
+    let len = version.limits.check(LengthKind::Bytes, len.into())?;
+    Ok(Some(read_bytes(reader, len).await?))
+}
@@ -0,0 +1,491 @@
+use super::{MessageSetEntry, RecordBatch, RecordHeaders, UNVERSIONED};
+use crate::formats::{
+    codec::{read_bytes, take_shared, FixedLength, Read, Version, Write},
+    variable_lengths::{
+        compact_length_size, decode_compact_length, encode_compact_length, read_compact_length,
+        write_compact_length,
+    },
+    DecodeLimits, FormatError, LengthKind, Result,
+};
+use bytes::{Buf, BufMut, Bytes};
+use derive_more::{From, Into};
+use itertools::Either;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tracing::trace;
+
+/// Size of the offset and length fields preceding every entry, regardless of its magic
+const LOG_OVERHEAD: usize = (i64::SIZE + i32::SIZE) as usize;
+
+/// Position of the magic byte in an entry, which is the same for every magic
+const MAGIC_OFFSET: usize = LOG_OVERHEAD + i32::SIZE as usize;
+
+/// Headers of legacy messages, which have none
+static NO_HEADERS: RecordHeaders = RecordHeaders::EMPTY;
+
+/// RECORDS: a sequence of record batches, each stored with any supported magic
+#[derive(Debug, Clone, Default, PartialEq, Eq, From, Into)]
+pub struct RecordSet(pub Vec<RecordSetEntry>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordSetEntry {
+    /// A magic v0 or v1 message. Wrapper messages are decompressed when read, so every entry
+    /// decoded from the wire holds a single record.
+    Legacy(MessageSetEntry),
+    /// A magic v2 record batch
+    Batch(RecordBatch),
+}
+
+/// A record with its absolute offset and timestamp, regardless of the magic it was stored with.
+/// Its key and value can be cloned without copying them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordRef<'a> {
+    pub offset: i64,
+    /// Missing for magic v0 messages
+    pub timestamp: Option<i64>,
+    pub key: Option<&'a Bytes>,
+    pub value: Option<&'a Bytes>,
+    /// Always empty for legacy messages
+    pub headers: &'a RecordHeaders,
+}
+
+impl RecordSet {
+    /// Iterates over all data records in the set, skipping control batches
+    pub fn records(&self) -> impl Iterator<Item = RecordRef<'_>> {
+        self.0.iter().flat_map(|entry| match entry {
+            RecordSetEntry::Legacy(entry) => Either::Left(std::iter::once(RecordRef {
+                offset: entry.offset,
+                timestamp: entry.message.timestamp,
+                key: entry.message.key.as_ref(),
+                value: entry.message.value.as_ref(),
+                headers: &NO_HEADERS,
+            })),
+            RecordSetEntry::Batch(batch) => Either::Right(
+                batch
+                    .records
+                    .iter()
+                    .filter(|_| !batch.attributes.is_control())
+                    .map(|record| RecordRef {
+                        offset: batch.offset_of(record),
+                        timestamp: Some(batch.timestamp_of(record)),
+                        key: record.key.as_ref(),
+                        value: record.value.as_ref(),
+                        headers: &record.headers,
+                    }),
+            ),
+        })
+    }
+
+    /// Decodes all complete entries of `buf`, which their records share
+    fn decode_entries(buf: Bytes, limits: DecodeLimits) -> Result<Self> {
+        let version = UNVERSIONED.with_limits(limits);
+        let mut entries = Vec::new();
+        let mut rest = buf;
+        while rest.len() > MAGIC_OFFSET {
+            let length = i32::from_be_bytes(rest[8..12].try_into().expect("4 bytes"));
+            let entry_size = usize::try_from(length)
+                .ok()
+                .and_then(|length| length.checked_add(LOG_OVERHEAD))
+                .filter(|&size| size > MAGIC_OFFSET)
+                .ok_or_else(|| {
+                    FormatError::CorruptRecord(format!("invalid entry length {length}"))
+                })?;
+            if rest.len() < entry_size {
+                trace!("dropping partial record set entry");
+                break;
+            }
+            let mut entry = rest.split_to(entry_size);
+
+            match entry[MAGIC_OFFSET] as i8 {
+                0 | 1 => {
+                    let entry = MessageSetEntry::decode(&mut entry, version)?;
+                    let flattened = entry.flatten(limits)?;
+                    entries.extend(flattened.into_iter().map(RecordSetEntry::Legacy));
+                }
+                2 => entries.push(RecordSetEntry::Batch(RecordBatch::decode(
+                    &mut entry, version,
+                )?)),
+                magic => return Err(FormatError::UnsupportedMagic(magic)),
+            }
+        }
+        Ok(RecordSet(entries))
+    }
+
+    /// Size of the encoded entries, failing if a batch can't be encoded
+    fn entries_size(&self) -> Result<i32> {
+        self.0
+            .iter()
+            .map(|entry| match entry {
+                RecordSetEntry::Legacy(entry) => Ok(entry.calculate_size(UNVERSIONED)),
+                RecordSetEntry::Batch(batch) => batch.encoded_size(),
+            })
+            .sum()
+    }
+}
+
+impl Write for RecordSet {
+    fn calculate_size(&self, version: Version) -> i32 {
+        let size: i32 = self
+            .0
+            .iter()
+            .map(|entry| match entry {
+                RecordSetEntry::Legacy(entry) => entry.calculate_size(UNVERSIONED),
+                RecordSetEntry::Batch(batch) => batch.calculate_size(UNVERSIONED),
+            })
+            .sum();
+        let size_prefix = if version.flexible {
+            compact_length_size(Some(size as usize))
+        } else {
+            i32::SIZE
+        };
+        size_prefix + size
+    }
+    async fn write_to(
+        &self,
+        writer: &mut (dyn AsyncWrite + Send + Unpin),
+        version: Version,
+    ) -> Result<()> {
+        let size = self.entries_size()?;
+        if version.flexible {
+            write_compact_length(writer, Some(size as usize)).await?;
+        } else {
+            size.write_to(writer, UNVERSIONED).await?;
+        }
+        for entry in &self.0 {
+            match entry {
+                RecordSetEntry::Legacy(entry) => entry.write_to(writer, UNVERSIONED).await?,
+                RecordSetEntry::Batch(batch) => batch.write_to(writer, UNVERSIONED).await?,
+            }
+        }
+        Ok(())
+    }
+    fn encode<B: BufMut>(&self, buf: &mut B, version: Version) -> Result<()> {
+        let size = self.entries_size()?;
+        if version.flexible {
+            encode_compact_length(buf, Some(size as usize));
+        } else {
+            buf.put_i32(size);
+        }
+        for entry in &self.0 {
+            match entry {
+                RecordSetEntry::Legacy(entry) => entry.encode(buf, UNVERSIONED)?,
+                RecordSetEntry::Batch(batch) => batch.encode(buf, UNVERSIONED)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Read for RecordSet {
+    /// Reads all complete entries. Brokers may truncate the last entry of a fetched set, which
+    /// is silently dropped.
+    async fn read_from(
+        reader: &mut (dyn AsyncRead + Send + Unpin),
+        version: Version,
+    ) -> Result<Self> {
+        let size = if version.flexible {
+            read_compact_length(reader).await?.map(|size| size as i64)
+        } else {
+            nullable_size(i32::read_from(reader, version).await?)
+        };
+        trace!("reading record set of size {size:?}");
+        let Some(size) = size else {
+            return Ok(RecordSet::default());
+        };
+        let size = version.limits.check(LengthKind::Bytes, size)?;
+        let buf = read_bytes(reader, size).await?;
+        Self::decode_entries(buf.into(), version.limits)
+    }
+    fn decode<B: Buf>(buf: &mut B, version: Version) -> Result<Self> {
+        let size = if version.flexible {
+            decode_compact_length(buf)?.map(|size| size as i64)
+        } else {
+            nullable_size(i32::decode(buf, version)?)
+        };
+        match size {
+            Some(size) => {
+                let size = version.limits.check(LengthKind::Bytes, size)?;
+                Self::decode_entries(take_shared(buf, size)?, version.limits)
+            }
+            None => Ok(RecordSet::default()),
+        }
+    }
+}
+
+/// Size of a non-compact record set, where -1 means null
+fn nullable_size(size: i32) -> Option<i64> {
+    (size != -1).then_some(size.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::{
+        codec::{assert_huge_length_rejected, encode_to_vec, HUGE_LENGTH},
+        records::{Header, Message, Record, RecordBatchAttributes},
+        VarInt,
+    };
+
+    fn legacy_entry(magic: i8, offset: i64, value: &[u8]) -> MessageSetEntry {
+        MessageSetEntry {
+            offset,
+            message: Message {
+                magic,
+                attributes: 0,
+                timestamp: (magic > 0).then_some(1_000 + offset),
+                key: Some(format!("key-{offset}").into()),
+                value: Some(Bytes::copy_from_slice(value)),
+            },
+        }
+    }
+
+    fn batch(base_offset: i64, values: &[&[u8]]) -> RecordBatch {
+        RecordBatch {
+            base_offset,
+            partition_leader_epoch: 0,
+            attributes: RecordBatchAttributes::default(),
+            last_offset_delta: values.len() as i32 - 1,
+            base_timestamp: 2_000,
+            max_timestamp: 2_000 + values.len() as i64 - 1,
+            producer_id: -1,
+            producer_epoch: -1,
+            base_sequence: -1,
+            records: values
+                .iter()
+                .enumerate()
+                .map(|(i, value)| Record {
+                    timestamp_delta: i as i64,
+                    offset_delta: i as i32,
+                    value: Some(Bytes::copy_from_slice(value)),
+                    ..Default::default()
+                })
+                .collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_legacy_message_layout() {
+        let entry = legacy_entry(0, 5, b"v");
+        let mut buf = Vec::new();
+        entry.write_to(&mut buf, UNVERSIONED).await.unwrap();
+        assert_eq!(entry.calculate_size(UNVERSIONED), buf.len() as i32);
+        assert_eq!(&buf[0..8], &5i64.to_be_bytes());
+        assert_eq!(&buf[8..12], &(buf.len() as i32 - 12).to_be_bytes());
+        assert_eq!(buf[MAGIC_OFFSET], 0);
+        let crc = u32::from_be_bytes(buf[12..16].try_into().unwrap());
+        assert_eq!(crc, crc32fast::hash(&buf[16..]));
+    }
+
+    #[tokio::test]
+    async fn test_mixed_magic_record_set() {
+        let set = RecordSet(vec![
+            RecordSetEntry::Legacy(legacy_entry(0, 0, b"zero")),
+            RecordSetEntry::Legacy(legacy_entry(1, 1, b"one")),
+            RecordSetEntry::Batch(batch(2, &[b"two", b"three"])),
+        ]);
+        let mut buf = Vec::new();
+        set.write_to(&mut buf, UNVERSIONED).await.unwrap();
+        assert_eq!(set.calculate_size(UNVERSIONED), buf.len() as i32);
+
+        let read = RecordSet::read_from(&mut buf.as_slice(), UNVERSIONED)
+            .await
+            .unwrap();
+        assert_eq!(read, set);
+        assert_eq!(encode_to_vec(&set, UNVERSIONED).unwrap(), buf);
+        assert_eq!(
+            RecordSet::decode(&mut buf.as_slice(), UNVERSIONED).unwrap(),
+            set
+        );
+
+        let records = read.records().collect::<Vec<_>>();
+        let offsets = records.iter().map(|r| r.offset).collect::<Vec<_>>();
+        let timestamps = records.iter().map(|r| r.timestamp).collect::<Vec<_>>();
+        let values = records
+            .iter()
+            .map(|r| &r.value.unwrap()[..])
+            .collect::<Vec<_>>();
+        assert_eq!(offsets, [0, 1, 2, 3]);
+        assert_eq!(timestamps, [None, Some(1_001), Some(2_000), Some(2_001)]);
+        assert_eq!(values, [&b"zero"[..], b"one", b"two", b"three"]);
+        assert_eq!(records[1].key.map(|key| &key[..]), Some(&b"key-1"[..]));
+    }
+
+    #[tokio::test]
+    async fn test_unencodable_batch_writes_nothing() {
+        // Compression id 7 isn't assigned to any codec
+        let unencodable = RecordBatch {
+            attributes: RecordBatchAttributes(7),
+            ..batch(2, &[b"two"])
+        };
+        let set = RecordSet(vec![
+            RecordSetEntry::Batch(batch(0, &[b"zero", b"one"])),
+            RecordSetEntry::Batch(unencodable),
+        ]);
+        let mut buf = Vec::new();
+        let err = set.write_to(&mut buf, UNVERSIONED).await.unwrap_err();
+        assert!(
+            matches!(err, FormatError::UnsupportedCompression(7)),
+            "{err}"
+        );
+        assert!(buf.is_empty());
+        let err = set.encode(&mut buf, UNVERSIONED).unwrap_err();
+        assert!(
+            matches!(err, FormatError::UnsupportedCompression(7)),
+            "{err}"
+        );
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_decoded_records_share_the_source_buffer() {
+        let mut record_batch = batch(0, &[b"shared"]);
+        record_batch.records[0].headers = vec![Header {
+            key: "trace-id".into(),
+            value: Some(Bytes::from_static(b"abc")),
+        }]
+        .into();
+        let set = RecordSet(vec![RecordSetEntry::Batch(record_batch)]);
+        let buf = Bytes::from(encode_to_vec(&set, UNVERSIONED).unwrap());
+
+        let read = RecordSet::decode(&mut buf.clone(), UNVERSIONED).unwrap();
+        let record = read.records().next().unwrap();
+        let value = record.value.unwrap();
+        assert_eq!(value, &b"shared"[..]);
+        let source = buf.as_ptr_range();
+        assert!(source.contains(&value.as_ptr()));
+
+        assert_eq!(record.headers.len(), 1);
+        let headers = record.headers.parse().unwrap();
+        assert_eq!(headers[0].key, "trace-id");
+        assert!(source.contains(&headers[0].value.as_ref().unwrap().as_ptr()));
+    }
+
+    #[tokio::test]
+    async fn test_partial_trailing_entry_is_dropped() {
+        let set = RecordSet(vec![
+            RecordSetEntry::Batch(batch(0, &[b"complete"])),
+            RecordSetEntry::Batch(batch(1, &[b"truncated"])),
+        ]);
+        let mut buf = Vec::new();
+        set.write_to(&mut buf, UNVERSIONED).await.unwrap();
+        let truncated_len = buf.len() - 10;
+        buf.truncate(truncated_len);
+        buf[0..4].copy_from_slice(&(truncated_len as i32 - 4).to_be_bytes());
+
+        let read = RecordSet::read_from(&mut buf.as_slice(), UNVERSIONED)
+            .await
+            .unwrap();
+        assert_eq!(read.0, set.0[..1]);
+    }
+
+    #[tokio::test]
+    async fn test_control_batches_are_skipped() {
+        let mut control = batch(1, &[b"marker"]);
+        control.attributes = control.attributes.with_control(true);
+        let set = RecordSet(vec![
+            RecordSetEntry::Batch(batch(0, &[b"data"])),
+            RecordSetEntry::Batch(control),
+        ]);
+        let offsets = set.records().map(|r| r.offset).collect::<Vec<_>>();
+        assert_eq!(offsets, [0]);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[tokio::test]
+    async fn test_compressed_legacy_wrappers() {
+        use crate::formats::records::Compression;
+
+        for (magic, inner_offsets, wrapper_offset) in [(0, [10, 11, 12], 10), (1, [0, 1, 2], 12)] {
+            let mut inner = Vec::new();
+            for (i, offset) in inner_offsets.into_iter().enumerate() {
+                legacy_entry(magic, offset, format!("value-{i}").as_bytes())
+                    .write_to(&mut inner, UNVERSIONED)
+                    .await
+                    .unwrap();
+            }
+            let wrapper = MessageSetEntry {
+                offset: wrapper_offset,
+                message: Message {
+                    magic,
+                    attributes: Compression::Gzip.id() as i8,
+                    timestamp: (magic > 0).then_some(5_000),
+                    key: None,
+                    value: Some(Compression::Gzip.compress(&inner).unwrap().into()),
+                },
+            };
+            let set = RecordSet(vec![RecordSetEntry::Legacy(wrapper)]);
+            let mut buf = Vec::new();
+            set.write_to(&mut buf, UNVERSIONED).await.unwrap();
+
+            let read = RecordSet::read_from(&mut buf.as_slice(), UNVERSIONED)
+                .await
+                .unwrap();
+            let offsets = read.records().map(|r| r.offset).collect::<Vec<_>>();
+            let values = read
+                .records()
+                .map(|r| r.value.unwrap().clone())
+                .collect::<Vec<_>>();
+            assert_eq!(offsets, [10, 11, 12], "magic {magic}");
+            assert_eq!(values, [&b"value-0"[..], b"value-1", b"value-2"]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_legacy_crc_mismatch() {
+        let mut buf = Vec::new();
+        legacy_entry(1, 0, b"value")
+            .write_to(&mut buf, UNVERSIONED)
+            .await
+            .unwrap();
+        let last = buf.len() - 1;
+        buf[last] ^= 0x01;
+        let err = MessageSetEntry::read_from(&mut buf.as_slice(), UNVERSIONED)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, FormatError::CrcMismatch { .. }));
+    }
+
+    #[test]
+    fn test_huge_lengths_with_short_bodies() {
+        let mut buf = (HUGE_LENGTH as i32).to_be_bytes().to_vec();
+        buf.extend([0; 64]);
+        assert_huge_length_rejected::<RecordSet>(&buf, UNVERSIONED);
+
+        let mut buf = 10i64.to_be_bytes().to_vec();
+        buf.extend((HUGE_LENGTH as i32).to_be_bytes());
+        buf.extend([0; 64]);
+        assert_huge_length_rejected::<MessageSetEntry>(&buf, UNVERSIONED);
+
+        let mut buf = encode_to_vec(&VarInt(HUGE_LENGTH as i32), UNVERSIONED).unwrap();
+        buf.extend([0; 64]);
+        assert_huge_length_rejected::<Record>(&buf, UNVERSIONED);
+        assert_huge_length_rejected::<Header>(&buf, UNVERSIONED);
+    }
+
+    #[tokio::test]
+    async fn test_header_count_out_of_bounds() {
+        // No key or value, then more headers than an array may hold
+        let mut body = vec![0x00, 0x00, 0x00, 0x01, 0x01];
+        VarInt(1 << 30).encode(&mut body, UNVERSIONED).unwrap();
+        let mut buf = encode_to_vec(&VarInt(body.len() as i32), UNVERSIONED).unwrap();
+        buf.extend(body);
+
+        let err = Record::read_from(&mut buf.as_slice(), UNVERSIONED)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(
+                err,
+                FormatError::LengthOutOfBounds {
+                    kind: LengthKind::Array,
+                    ..
+                }
+            ),
+            "{err}"
+        );
+        let err = Record::decode(&mut buf.as_slice(), UNVERSIONED).unwrap_err();
+        assert!(
+            matches!(err, FormatError::LengthOutOfBounds { .. }),
+            "{err}"
+        );
+    }
+}
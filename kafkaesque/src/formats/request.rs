@@ -1,16 +1,18 @@
 use super::api_keys::ApiKey;
-use super::codec::{Read, Write};
+use super::codec::{Read, Version, Write};
+use super::variable_lengths::TaggedFields;
 use derive_more::{From, Into};
 
 pub use kafkaesque_macros::RequestMessage;
 
-#[derive(Debug, From, Into, Clone, Copy, Read, Write)]
+#[derive(Debug, From, Into, Clone, Copy, PartialEq, Eq, Read, Write)]
 pub struct CorrelationId(i32);
 
-#[derive(From, Into, Copy, Clone, Debug, Write)]
+#[derive(From, Into, Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Read, Write)]
 pub struct ApiVersion(pub i16);
 
-#[derive(Debug, Write)]
+/// Request header v1
+#[derive(Debug, Clone, PartialEq, Read, Write)]
 pub struct RequestHeader {
     pub api_key: ApiKey,
     pub api_version: ApiVersion,
@@ -18,7 +20,50 @@ pub struct RequestHeader {
     pub client_id: String,
 }
 
+/// Request header v2, sent with flexible request versions
+#[derive(Debug, Read, Write)]
+pub struct FlexibleRequestHeader {
+    pub header: RequestHeader,
+    pub tagged_fields: TaggedFields,
+}
+
+/// Response header v1, received for flexible request versions
+#[derive(Debug, Read, Write)]
+pub struct FlexibleResponseHeader {
+    pub cid: CorrelationId,
+    pub tagged_fields: TaggedFields,
+}
+
 pub trait RequestMessage {
     const API_KEY: ApiKey;
-    const API_VERSION: ApiVersion;
+    /// Lowest version the message can be encoded in
+    const MIN_VERSION: ApiVersion;
+    /// Highest version the message can be encoded in
+    const MAX_VERSION: ApiVersion;
+    /// First flexible version of the message (KIP-482), if any
+    const FLEXIBLE_VERSION: Option<ApiVersion> = None;
+    /// The response brokers send back for this request
+    type Response: Read;
+
+    /// Whether `api_version` of the message is a flexible version
+    fn is_flexible(api_version: ApiVersion) -> bool {
+        matches!(Self::FLEXIBLE_VERSION, Some(flexible) if api_version >= flexible)
+    }
+
+    /// Encoding of the message, and its response, in `api_version`
+    fn version(api_version: ApiVersion) -> Version {
+        Version::new(api_version.0, Self::is_flexible(api_version))
+    }
+
+    /// Whether brokers send a response to this message. Produce requests with `acks = 0`
+    /// get none, so they have to be sent with `BrokerConnection::send_without_response`.
+    fn expects_response(&self) -> bool {
+        true
+    }
+}
+
+/// Whether responses to [M] carry response header v1. ApiVersions responses always use
+/// header v0 so that clients can parse them before knowing which versions the broker supports.
+pub(crate) fn has_flexible_response_header<M: RequestMessage>(version: Version) -> bool {
+    version.flexible && !matches!(M::API_KEY, ApiKey::ApiVersions)
 }
@@ -0,0 +1,105 @@
+use super::{
+    api_keys::ApiKey,
+    errors::{FormatError, Result},
+    request::{ApiVersion, RequestMessage},
+};
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+
+/// An inclusive range of API versions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VersionRange {
+    pub min: ApiVersion,
+    pub max: ApiVersion,
+}
+
+impl VersionRange {
+    pub fn new(min: impl Into<ApiVersion>, max: impl Into<ApiVersion>) -> Self {
+        VersionRange {
+            min: min.into(),
+            max: max.into(),
+        }
+    }
+
+    /// Range of versions a [RequestMessage] can be encoded in
+    pub fn of<M: RequestMessage>() -> Self {
+        VersionRange::new(M::MIN_VERSION, M::MAX_VERSION)
+    }
+}
+
+impl Display for VersionRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.min.0, self.max.0)
+    }
+}
+
+/// Versions of every API a broker supports, as reported by its ApiVersions response
+#[derive(Debug, Clone, Default)]
+pub struct SupportedVersions(HashMap<ApiKey, VersionRange>);
+
+impl SupportedVersions {
+    pub fn get(&self, api_key: ApiKey) -> Option<VersionRange> {
+        self.0.get(&api_key).copied()
+    }
+
+    /// Highest version of [M] supported by both the client and the broker
+    pub fn negotiate<M: RequestMessage>(&self) -> Result<ApiVersion> {
+        let client = VersionRange::of::<M>();
+        let broker = self.get(M::API_KEY);
+        broker
+            .map(|broker| (client.min.max(broker.min), client.max.min(broker.max)))
+            .filter(|(min, max)| min <= max)
+            .map(|(_, max)| max)
+            .ok_or(FormatError::UnsupportedVersion {
+                api_key: M::API_KEY,
+                client,
+                broker,
+            })
+    }
+}
+
+impl FromIterator<(ApiKey, VersionRange)> for SupportedVersions {
+    fn from_iter<T: IntoIterator<Item = (ApiKey, VersionRange)>>(iter: T) -> Self {
+        SupportedVersions(iter.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::messages::ApiVersionsRequest;
+
+    #[test]
+    fn test_negotiate() {
+        let negotiate = |broker: Option<VersionRange>| {
+            broker
+                .map(|broker| (ApiKey::ApiVersions, broker))
+                .into_iter()
+                .collect::<SupportedVersions>()
+                .negotiate::<ApiVersionsRequest>()
+        };
+        assert_eq!(
+            negotiate(Some(VersionRange::new(0, 2))).unwrap(),
+            ApiVersion(2)
+        );
+        assert_eq!(
+            negotiate(Some(VersionRange::new(1, 9))).unwrap(),
+            ApiVersion(3)
+        );
+        match negotiate(Some(VersionRange::new(4, 9))) {
+            Err(FormatError::UnsupportedVersion {
+                api_key: ApiKey::ApiVersions,
+                client,
+                broker: Some(broker),
+            }) => {
+                assert_eq!(client, VersionRange::new(0, 3));
+                assert_eq!(broker, VersionRange::new(4, 9));
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+        assert!(matches!(
+            negotiate(None),
+            Err(FormatError::UnsupportedVersion { broker: None, .. })
+        ));
+    }
+}
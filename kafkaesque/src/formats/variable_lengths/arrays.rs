@@ -1,37 +1,346 @@
+use super::strings::{
+    compact_length_size, decode_compact_length, encode_compact_length, read_compact_length,
+    write_compact_length,
+};
 use crate::formats::{
-    codec::{FixedLength, Read, Write},
-    Result,
+    codec::{FixedLength, Read, Version, Write},
+    FormatError, LengthKind, Result,
 };
-use tokio::io::AsyncRead;
+use bytes::{Buf, BufMut};
+use derive_more::{From, Into};
+use tokio::io::{AsyncRead, AsyncWrite};
 use tracing::trace;
 
-// Reader for ARRAY type
+// Reader for ARRAY type, or COMPACT_ARRAY in flexible versions
 impl<A: Read> Read for Vec<A> {
-    async fn read_from(reader: &mut (dyn AsyncRead + Send + Unpin)) -> Result<Self> {
-        trace!("reading array len");
-        let length = i32::read_from(reader).await?;
-        trace!("reading array of len {length}");
-        let mut output = Vec::with_capacity(length as usize);
-        for _ in 0..length {
-            output.push(A::read_from(reader).await?);
-        }
-        Ok(output)
+    async fn read_from(
+        reader: &mut (dyn AsyncRead + Send + Unpin),
+        version: Version,
+    ) -> Result<Self> {
+        Option::<Vec<A>>::read_from(reader, version)
+            .await?
+            .ok_or(FormatError::UnexpectedNull(LengthKind::Array))
+    }
+    fn decode<B: Buf>(buf: &mut B, version: Version) -> Result<Self> {
+        Option::<Vec<A>>::decode(buf, version)?
+            .ok_or(FormatError::UnexpectedNull(LengthKind::Array))
     }
 }
 
 impl<A: Write> Write for Vec<A> {
-    fn calculate_size(&self) -> i32 {
-        i32::SIZE + self.iter().map(|a| a.calculate_size()).sum::<i32>()
+    fn calculate_size(&self, version: Version) -> i32 {
+        array_length_size(Some(self.len()), version)
+            + self.iter().map(|a| a.calculate_size(version)).sum::<i32>()
     }
     async fn write_to(
         &self,
         writer: &mut (dyn tokio::io::AsyncWrite + Send + Unpin),
+        version: Version,
     ) -> Result<()> {
-        let len: i32 = self.len() as i32;
-        len.write_to(writer).await?;
+        write_array_length(writer, Some(self.len()), version).await?;
+        for a in self {
+            a.write_to(writer, version).await?;
+        }
+        Ok(())
+    }
+    fn encode<B: BufMut>(&self, buf: &mut B, version: Version) -> Result<()> {
+        encode_array_length(buf, Some(self.len()), version)?;
         for a in self {
-            a.write_to(writer).await?;
+            a.encode(buf, version)?;
+        }
+        Ok(())
+    }
+}
+
+// Reader for nullable ARRAY type, or nullable COMPACT_ARRAY in flexible versions, where a length
+// of -1 means null
+impl<A: Read> Read for Option<Vec<A>> {
+    async fn read_from(
+        reader: &mut (dyn AsyncRead + Send + Unpin),
+        version: Version,
+    ) -> Result<Self> {
+        trace!("reading array len");
+        let length = if version.flexible {
+            read_compact_length(reader)
+                .await?
+                .map(|length| length as i64)
+        } else {
+            nullable_length(i32::read_from(reader, version).await?)
+        };
+        match check_length(length, version)? {
+            Some(length) => {
+                trace!("reading array of len {length}");
+                Ok(Some(read_elements(reader, length, version).await?))
+            }
+            None => Ok(None),
+        }
+    }
+    fn decode<B: Buf>(buf: &mut B, version: Version) -> Result<Self> {
+        let length = if version.flexible {
+            decode_compact_length(buf)?.map(|length| length as i64)
+        } else {
+            nullable_length(i32::decode(buf, version)?)
+        };
+        check_length(length, version)?
+            .map(|length| decode_elements(buf, length, version))
+            .transpose()
+    }
+}
+
+impl<A: Write> Write for Option<Vec<A>> {
+    fn calculate_size(&self, version: Version) -> i32 {
+        match self {
+            Some(elements) => elements.calculate_size(version),
+            None => array_length_size(None, version),
         }
+    }
+    async fn write_to(
+        &self,
+        writer: &mut (dyn AsyncWrite + Send + Unpin),
+        version: Version,
+    ) -> Result<()> {
+        match self {
+            Some(elements) => elements.write_to(writer, version).await,
+            None => write_array_length(writer, None, version).await,
+        }
+    }
+    fn encode<B: BufMut>(&self, buf: &mut B, version: Version) -> Result<()> {
+        match self {
+            Some(elements) => elements.encode(buf, version),
+            None => encode_array_length(buf, None, version),
+        }
+    }
+}
+
+fn array_length_size(length: Option<usize>, version: Version) -> i32 {
+    if version.flexible {
+        compact_length_size(length)
+    } else {
+        i32::SIZE
+    }
+}
+
+async fn write_array_length(
+    writer: &mut (dyn AsyncWrite + Send + Unpin),
+    length: Option<usize>,
+    version: Version,
+) -> Result<()> {
+    if version.flexible {
+        write_compact_length(writer, length).await
+    } else {
+        length
+            .map_or(-1, |length| length as i32)
+            .write_to(writer, version)
+            .await
+    }
+}
+
+fn encode_array_length(
+    buf: &mut impl BufMut,
+    length: Option<usize>,
+    version: Version,
+) -> Result<()> {
+    if version.flexible {
+        encode_compact_length(buf, length);
         Ok(())
+    } else {
+        length
+            .map_or(-1, |length| length as i32)
+            .encode(buf, version)
+    }
+}
+
+/// COMPACT_ARRAY: an array prefixed by its length + 1 as an UNSIGNED_VARINT
+#[derive(Debug, Clone, PartialEq, Eq, From, Into)]
+pub struct CompactArray<A>(pub Vec<A>);
+
+impl<A: Read> Read for CompactArray<A> {
+    async fn read_from(
+        reader: &mut (dyn AsyncRead + Send + Unpin),
+        version: Version,
+    ) -> Result<Self> {
+        trace!("reading compact_array len");
+        let length = check_length(
+            read_compact_length(reader)
+                .await?
+                .map(|length| length as i64),
+            version,
+        )?
+        .ok_or(FormatError::UnexpectedNull(LengthKind::Array))?;
+        trace!("reading compact_array of len {length}");
+        Ok(CompactArray(read_elements(reader, length, version).await?))
+    }
+    fn decode<B: Buf>(buf: &mut B, version: Version) -> Result<Self> {
+        let length = check_length(
+            decode_compact_length(buf)?.map(|length| length as i64),
+            version,
+        )?
+        .ok_or(FormatError::UnexpectedNull(LengthKind::Array))?;
+        Ok(CompactArray(decode_elements(buf, length, version)?))
+    }
+}
+
+impl<A: Write> Write for CompactArray<A> {
+    fn calculate_size(&self, version: Version) -> i32 {
+        compact_length_size(Some(self.0.len()))
+            + self
+                .0
+                .iter()
+                .map(|a| a.calculate_size(version))
+                .sum::<i32>()
+    }
+    async fn write_to(
+        &self,
+        writer: &mut (dyn AsyncWrite + Send + Unpin),
+        version: Version,
+    ) -> Result<()> {
+        write_compact_length(writer, Some(self.0.len())).await?;
+        for a in &self.0 {
+            a.write_to(writer, version).await?;
+        }
+        Ok(())
+    }
+    fn encode<B: BufMut>(&self, buf: &mut B, version: Version) -> Result<()> {
+        encode_compact_length(buf, Some(self.0.len()));
+        for a in &self.0 {
+            a.encode(buf, version)?;
+        }
+        Ok(())
+    }
+}
+
+/// Number of elements allocated upfront when reading an array, as a length within the limits
+/// may still be far more than what the reader holds
+const MAX_PREALLOCATED_ELEMENTS: usize = 1024;
+
+/// Length of a non-compact array, where -1 means null. Other negative lengths are kept as is
+/// for [check_length] to reject.
+fn nullable_length(length: i32) -> Option<i64> {
+    (length != -1).then_some(length.into())
+}
+
+/// Checks the length of an array against the limits, unless the array is null
+fn check_length(length: Option<i64>, version: Version) -> Result<Option<usize>> {
+    length
+        .map(|length| version.limits.check(LengthKind::Array, length))
+        .transpose()
+}
+
+async fn read_elements<A: Read>(
+    reader: &mut (dyn AsyncRead + Send + Unpin),
+    length: usize,
+    version: Version,
+) -> Result<Vec<A>> {
+    let mut output = Vec::with_capacity(length.min(MAX_PREALLOCATED_ELEMENTS));
+    for _ in 0..length {
+        output.push(A::read_from(reader, version).await?);
+    }
+    Ok(output)
+}
+
+fn decode_elements<A: Read, B: Buf>(
+    buf: &mut B,
+    length: usize,
+    version: Version,
+) -> Result<Vec<A>> {
+    // Every element takes at least a byte, which bounds the allocation of corrupt lengths
+    let mut output = Vec::with_capacity(length.min(buf.remaining()));
+    for _ in 0..length {
+        output.push(A::decode(buf, version)?);
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::{encode_to_vec, DecodeLimits, FormatError};
+
+    #[tokio::test]
+    async fn test_compact_array() {
+        let a = CompactArray(vec![1i16, 2, 3]);
+        let mut buf = Vec::new();
+        a.write_to(&mut buf, Version::default()).await.unwrap();
+        assert_eq!(buf, [0x04, 0x00, 0x01, 0x00, 0x02, 0x00, 0x03]);
+        assert_eq!(a.calculate_size(Version::default()), buf.len() as i32);
+        let read = CompactArray::<i16>::read_from(&mut buf.as_slice(), Version::default())
+            .await
+            .unwrap();
+        assert_eq!(read, a);
+    }
+
+    #[tokio::test]
+    async fn test_array_lengths_out_of_bounds() {
+        let version = Version::default();
+        for length in [-2i32, i32::MIN] {
+            let buf = length.to_be_bytes();
+            let err = Vec::<i16>::decode(&mut buf.as_slice(), version).unwrap_err();
+            assert!(
+                matches!(err, FormatError::LengthOutOfBounds { .. }),
+                "{err}"
+            );
+            let err = Vec::<i16>::read_from(&mut buf.as_slice(), version)
+                .await
+                .unwrap_err();
+            assert!(
+                matches!(err, FormatError::LengthOutOfBounds { .. }),
+                "{err}"
+            );
+        }
+
+        // Only nullable arrays may be null
+        let buf = (-1i32).to_be_bytes();
+        let err = Vec::<i16>::decode(&mut buf.as_slice(), version).unwrap_err();
+        assert!(
+            matches!(err, FormatError::UnexpectedNull(LengthKind::Array)),
+            "{err}"
+        );
+        let err = Vec::<i16>::read_from(&mut buf.as_slice(), version)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, FormatError::UnexpectedNull(LengthKind::Array)),
+            "{err}"
+        );
+
+        let version = version.with_limits(DecodeLimits {
+            max_array_length: 2,
+            ..DecodeLimits::default()
+        });
+        let buf = encode_to_vec(&vec![1i16, 2, 3], version).unwrap();
+        let err = Vec::<i16>::decode(&mut buf.as_slice(), version).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                FormatError::LengthOutOfBounds {
+                    kind: LengthKind::Array,
+                    length: 3,
+                    limit: 2
+                }
+            ),
+            "{err}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_nullable_array() {
+        for (array, version, expected) in [
+            (Some(vec![1i8]), Version::new(0, false), vec![0, 0, 0, 1, 1]),
+            (None, Version::new(0, false), vec![0xff, 0xff, 0xff, 0xff]),
+            (Some(vec![1i8]), Version::new(0, true), vec![2, 1]),
+            (None, Version::new(0, true), vec![0]),
+        ] {
+            let mut buf = Vec::new();
+            array.write_to(&mut buf, version).await.unwrap();
+            assert_eq!(buf, expected);
+            assert_eq!(array.calculate_size(version), buf.len() as i32);
+            assert_eq!(encode_to_vec(&array, version).unwrap(), expected);
+            let read = Option::<Vec<i8>>::read_from(&mut buf.as_slice(), version)
+                .await
+                .unwrap();
+            assert_eq!(read, array);
+            let decoded = Option::<Vec<i8>>::decode(&mut buf.as_slice(), version).unwrap();
+            assert_eq!(decoded, array);
+        }
     }
 }
@@ -0,0 +1,243 @@
+use super::strings::{
+    compact_length_size, decode_compact_length, encode_compact_length, read_compact_length,
+    write_compact_length,
+};
+use crate::formats::{
+    codec::{read_bytes, take_bytes, take_shared, FixedLength, Read, Version, Write},
+    FormatError, LengthKind, Result,
+};
+use bytes::{Buf, BufMut, Bytes};
+use derive_more::{From, Into};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use tracing::trace;
+
+// Reader for BYTES type, or COMPACT_BYTES in flexible versions. Decoding from a [Bytes] buffer
+// shares it rather than copying out of it.
+impl Read for Bytes {
+    async fn read_from(
+        reader: &mut (dyn AsyncRead + Send + Unpin),
+        version: Version,
+    ) -> Result<Self> {
+        Option::<Bytes>::read_from(reader, version)
+            .await?
+            .ok_or(FormatError::UnexpectedNull(LengthKind::Bytes))
+    }
+    fn decode<B: Buf>(buf: &mut B, version: Version) -> Result<Self> {
+        Option::<Bytes>::decode(buf, version)?.ok_or(FormatError::UnexpectedNull(LengthKind::Bytes))
+    }
+}
+
+impl Write for Bytes {
+    fn calculate_size(&self, version: Version) -> i32 {
+        bytes_length_size(Some(self.len()), version) + self.len() as i32
+    }
+    async fn write_to(
+        &self,
+        writer: &mut (dyn AsyncWrite + Send + Unpin),
+        version: Version,
+    ) -> Result<()> {
+        if version.flexible {
+            write_compact_length(writer, Some(self.len())).await?;
+        } else {
+            (self.len() as i32).write_to(writer, version).await?;
+        }
+        writer.write_all(self).await?;
+        Ok(())
+    }
+    fn encode<B: BufMut>(&self, buf: &mut B, version: Version) -> Result<()> {
+        if version.flexible {
+            encode_compact_length(buf, Some(self.len()));
+        } else {
+            (self.len() as i32).encode(buf, version)?;
+        }
+        buf.put_slice(self);
+        Ok(())
+    }
+}
+
+// Reader for NULLABLE_BYTES type, or COMPACT_NULLABLE_BYTES in flexible versions
+impl Read for Option<Bytes> {
+    async fn read_from(
+        reader: &mut (dyn AsyncRead + Send + Unpin),
+        version: Version,
+    ) -> Result<Self> {
+        trace!("reading bytes len");
+        let len = if version.flexible {
+            read_compact_length(reader).await?.map(|len| len as i64)
+        } else {
+            nullable_length(i32::read_from(reader, version).await?)
+        };
+        match check_length(len, version)? {
+            Some(len) => {
+                trace!("reading bytes of len {len}");
+                Ok(Some(read_bytes(reader, len).await?.into()))
+            }
+            None => Ok(None),
+        }
+    }
+    fn decode<B: Buf>(buf: &mut B, version: Version) -> Result<Self> {
+        let len = if version.flexible {
+            decode_compact_length(buf)?.map(|len| len as i64)
+        } else {
+            nullable_length(i32::decode(buf, version)?)
+        };
+        check_length(len, version)?
+            .map(|len| take_shared(buf, len))
+            .transpose()
+    }
+}
+
+impl Write for Option<Bytes> {
+    fn calculate_size(&self, version: Version) -> i32 {
+        match self {
+            Some(bytes) => bytes.calculate_size(version),
+            None => bytes_length_size(None, version),
+        }
+    }
+    async fn write_to(
+        &self,
+        writer: &mut (dyn AsyncWrite + Send + Unpin),
+        version: Version,
+    ) -> Result<()> {
+        match self {
+            Some(bytes) => bytes.write_to(writer, version).await,
+            None if version.flexible => write_compact_length(writer, None).await,
+            None => (-1i32).write_to(writer, version).await,
+        }
+    }
+    fn encode<B: BufMut>(&self, buf: &mut B, version: Version) -> Result<()> {
+        match self {
+            Some(bytes) => bytes.encode(buf, version),
+            None if version.flexible => {
+                encode_compact_length(buf, None);
+                Ok(())
+            }
+            None => (-1i32).encode(buf, version),
+        }
+    }
+}
+
+fn bytes_length_size(len: Option<usize>, version: Version) -> i32 {
+    if version.flexible {
+        compact_length_size(len)
+    } else {
+        i32::SIZE
+    }
+}
+
+/// Length of non-compact bytes, where -1 means null. Other negative lengths are kept as is for
+/// [check_length] to reject.
+fn nullable_length(len: i32) -> Option<i64> {
+    (len != -1).then_some(len.into())
+}
+
+/// Checks the length of a byte sequence against the limits, unless it's null
+fn check_length(len: Option<i64>, version: Version) -> Result<Option<usize>> {
+    len.map(|len| version.limits.check(LengthKind::Bytes, len))
+        .transpose()
+}
+
+/// COMPACT_BYTES: a byte sequence prefixed by its length + 1 as an UNSIGNED_VARINT
+#[derive(Debug, Clone, Default, PartialEq, Eq, From, Into)]
+pub struct CompactBytes(pub Vec<u8>);
+
+impl Write for CompactBytes {
+    fn calculate_size(&self, _version: Version) -> i32 {
+        compact_length_size(Some(self.0.len())) + self.0.len() as i32
+    }
+    async fn write_to(
+        &self,
+        writer: &mut (dyn AsyncWrite + Send + Unpin),
+        _version: Version,
+    ) -> Result<()> {
+        write_compact_length(writer, Some(self.0.len())).await?;
+        writer.write_all(&self.0).await?;
+        Ok(())
+    }
+    fn encode<B: BufMut>(&self, buf: &mut B, _version: Version) -> Result<()> {
+        encode_compact_length(buf, Some(self.0.len()));
+        buf.put_slice(&self.0);
+        Ok(())
+    }
+}
+
+impl Read for CompactBytes {
+    async fn read_from(
+        reader: &mut (dyn AsyncRead + Send + Unpin),
+        version: Version,
+    ) -> Result<Self> {
+        trace!("reading compact_bytes len");
+        let len = read_compact_length(reader)
+            .await?
+            .ok_or(FormatError::UnexpectedNull(LengthKind::Bytes))?;
+        let len = version.limits.check(LengthKind::Bytes, len as i64)?;
+        trace!("reading compact_bytes of len {len}");
+        Ok(CompactBytes(read_bytes(reader, len).await?))
+    }
+    fn decode<B: Buf>(buf: &mut B, version: Version) -> Result<Self> {
+        let len =
+            decode_compact_length(buf)?.ok_or(FormatError::UnexpectedNull(LengthKind::Bytes))?;
+        let len = version.limits.check(LengthKind::Bytes, len as i64)?;
+        Ok(CompactBytes(take_bytes(buf, len)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::{
+        codec::{assert_huge_length_rejected, HUGE_LENGTH},
+        encode_to_vec, FormatError,
+    };
+
+    #[tokio::test]
+    async fn test_bytes() {
+        for (bytes, version, expected) in [
+            (
+                Some(Bytes::from_static(b"kafka")),
+                Version::new(0, false),
+                b"\x00\x00\x00\x05kafka".to_vec(),
+            ),
+            (None, Version::new(0, false), b"\xff\xff\xff\xff".to_vec()),
+            (
+                Some(Bytes::from_static(b"kafka")),
+                Version::new(0, true),
+                b"\x06kafka".to_vec(),
+            ),
+            (None, Version::new(0, true), b"\x00".to_vec()),
+        ] {
+            let mut buf = Vec::new();
+            bytes.write_to(&mut buf, version).await.unwrap();
+            assert_eq!(buf, expected);
+            assert_eq!(bytes.calculate_size(version), buf.len() as i32);
+            assert_eq!(encode_to_vec(&bytes, version).unwrap(), expected);
+            let read = Option::<Bytes>::read_from(&mut buf.as_slice(), version)
+                .await
+                .unwrap();
+            assert_eq!(read, bytes);
+            let decoded = Option::<Bytes>::decode(&mut Bytes::from(buf), version).unwrap();
+            assert_eq!(decoded, bytes);
+        }
+
+        let buf = (-2i32).to_be_bytes();
+        let err = Bytes::decode(&mut buf.as_slice(), Version::default()).unwrap_err();
+        assert!(
+            matches!(err, FormatError::LengthOutOfBounds { .. }),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_huge_length_with_short_body() {
+        let mut buf = (HUGE_LENGTH as i32).to_be_bytes().to_vec();
+        buf.extend(b"kafka");
+        assert_huge_length_rejected::<Option<Bytes>>(&buf, Version::new(0, false));
+        assert_huge_length_rejected::<Bytes>(&buf, Version::new(0, false));
+
+        let mut buf = Vec::new();
+        encode_compact_length(&mut buf, Some(HUGE_LENGTH));
+        buf.extend(b"kafka");
+        assert_huge_length_rejected::<Option<Bytes>>(&buf, Version::new(0, true));
+        assert_huge_length_rejected::<CompactBytes>(&buf, Version::default());
+    }
+}
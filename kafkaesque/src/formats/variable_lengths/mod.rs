@@ -1,5 +1,154 @@
 mod arrays;
+mod bytes;
 mod numbers;
 mod strings;
+mod tagged_fields;
 
-pub use strings::NullableString;
+pub use arrays::CompactArray;
+pub use bytes::CompactBytes;
+pub use numbers::{UnsignedVarInt, VarInt, VarLong};
+pub(crate) use strings::{
+    compact_length_size, decode_compact_length, encode_compact_length, read_compact_length,
+    write_compact_length,
+};
+pub use strings::{CompactNullableString, CompactString, NullableString};
+pub use tagged_fields::{TaggedField, TaggedFields};
+
+/// Encodings of every variable-length primitive, written out by hand from the protocol guide
+/// (<https://kafka.apache.org/protocol#protocol_types>). Varints are covered by the tests of
+/// [numbers].
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::{
+        codec::{assert_golden, Read, Version},
+        FormatError, LengthKind,
+    };
+    use ::bytes::Bytes;
+
+    const V: Version = Version::new(0, false);
+    const FLEXIBLE: Version = Version::new(0, true);
+
+    #[tokio::test]
+    async fn test_string() {
+        assert_golden(String::new(), V, &[0x00, 0x00]).await;
+        assert_golden("kafka".to_string(), V, b"\x00\x05kafka").await;
+        assert_golden("é".to_string(), V, &[0x00, 0x02, 0xc3, 0xa9]).await;
+        assert_golden(String::new(), FLEXIBLE, &[0x01]).await;
+        assert_golden("kafka".to_string(), FLEXIBLE, b"\x06kafka").await;
+    }
+
+    #[tokio::test]
+    async fn test_nullable_string() {
+        assert_golden(NullableString(None), V, &[0xff, 0xff]).await;
+        assert_golden(NullableString::from(""), V, &[0x00, 0x00]).await;
+        assert_golden(NullableString::from("kafka"), V, b"\x00\x05kafka").await;
+        assert_golden(NullableString(None), FLEXIBLE, &[0x00]).await;
+        assert_golden(NullableString::from(""), FLEXIBLE, &[0x01]).await;
+        assert_golden(NullableString::from("kafka"), FLEXIBLE, b"\x06kafka").await;
+    }
+
+    #[tokio::test]
+    async fn test_compact_strings() {
+        assert_golden(CompactString(String::new()), V, &[0x01]).await;
+        assert_golden(CompactString("kafka".into()), V, b"\x06kafka").await;
+        assert_golden(CompactNullableString(None), V, &[0x00]).await;
+        assert_golden(CompactNullableString(Some(String::new())), V, &[0x01]).await;
+        assert_golden(CompactNullableString(Some("kafka".into())), V, b"\x06kafka").await;
+    }
+
+    #[tokio::test]
+    async fn test_bytes() {
+        assert_golden(Bytes::new(), V, &[0x00, 0x00, 0x00, 0x00]).await;
+        assert_golden(
+            Bytes::from_static(&[0xca, 0xfe]),
+            V,
+            &[0, 0, 0, 2, 0xca, 0xfe],
+        )
+        .await;
+        assert_golden(Bytes::new(), FLEXIBLE, &[0x01]).await;
+        assert_golden(
+            Bytes::from_static(&[0xca, 0xfe]),
+            FLEXIBLE,
+            &[0x03, 0xca, 0xfe],
+        )
+        .await;
+        assert_golden(CompactBytes(vec![0xca, 0xfe]), V, &[0x03, 0xca, 0xfe]).await;
+    }
+
+    #[tokio::test]
+    async fn test_nullable_bytes() {
+        assert_golden(None::<Bytes>, V, &[0xff, 0xff, 0xff, 0xff]).await;
+        assert_golden(Some(Bytes::new()), V, &[0x00, 0x00, 0x00, 0x00]).await;
+        assert_golden(None::<Bytes>, FLEXIBLE, &[0x00]).await;
+        assert_golden(Some(Bytes::new()), FLEXIBLE, &[0x01]).await;
+    }
+
+    #[tokio::test]
+    async fn test_array() {
+        assert_golden(Vec::<i32>::new(), V, &[0x00, 0x00, 0x00, 0x00]).await;
+        assert_golden(
+            vec![1i32, -1],
+            V,
+            &[0, 0, 0, 2, 0, 0, 0, 1, 0xff, 0xff, 0xff, 0xff],
+        )
+        .await;
+        assert_golden(vec!["a".to_string()], V, &[0, 0, 0, 1, 0, 1, b'a']).await;
+        assert_golden(Vec::<i32>::new(), FLEXIBLE, &[0x01]).await;
+        assert_golden(vec!["a".to_string()], FLEXIBLE, &[0x02, 0x02, b'a']).await;
+        assert_golden(CompactArray(vec![7i8]), V, &[0x02, 0x07]).await;
+    }
+
+    #[tokio::test]
+    async fn test_nullable_array() {
+        assert_golden(None::<Vec<i32>>, V, &[0xff, 0xff, 0xff, 0xff]).await;
+        assert_golden(Some(Vec::<i32>::new()), V, &[0x00, 0x00, 0x00, 0x00]).await;
+        assert_golden(None::<Vec<i32>>, FLEXIBLE, &[0x00]).await;
+        assert_golden(Some(Vec::<i32>::new()), FLEXIBLE, &[0x01]).await;
+    }
+
+    #[tokio::test]
+    async fn test_tagged_fields() {
+        assert_golden(TaggedFields::default(), FLEXIBLE, &[0x00]).await;
+        assert_golden(
+            TaggedFields(vec![TaggedField {
+                tag: 1,
+                data: vec![0xab],
+            }]),
+            FLEXIBLE,
+            &[0x01, 0x01, 0x01, 0xab],
+        )
+        .await;
+    }
+
+    /// Checks that the null `encoded` fails to read as the non-nullable `T`
+    async fn assert_null_rejected<T: Read + std::fmt::Debug>(
+        encoded: &[u8],
+        version: Version,
+        kind: LengthKind,
+    ) {
+        let err = T::read_from(&mut &encoded[..], version).await.unwrap_err();
+        assert!(
+            matches!(err, FormatError::UnexpectedNull(k) if k == kind),
+            "{err}"
+        );
+        let err = T::decode(&mut &encoded[..], version).unwrap_err();
+        assert!(
+            matches!(err, FormatError::UnexpectedNull(k) if k == kind),
+            "{err}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_null_of_non_nullable_types() {
+        assert_null_rejected::<String>(&[0xff, 0xff], V, LengthKind::String).await;
+        assert_null_rejected::<String>(&[0x00], FLEXIBLE, LengthKind::String).await;
+        assert_null_rejected::<CompactString>(&[0x00], V, LengthKind::String).await;
+        assert_null_rejected::<Bytes>(&[0xff; 4], V, LengthKind::Bytes).await;
+        assert_null_rejected::<Bytes>(&[0x00], FLEXIBLE, LengthKind::Bytes).await;
+        assert_null_rejected::<CompactBytes>(&[0x00], V, LengthKind::Bytes).await;
+        assert_null_rejected::<Vec<i32>>(&[0xff; 4], V, LengthKind::Array).await;
+        assert_null_rejected::<Vec<i32>>(&[0x00], FLEXIBLE, LengthKind::Array).await;
+        assert_null_rejected::<CompactArray<i32>>(&[0x00], V, LengthKind::Array).await;
+    }
+}
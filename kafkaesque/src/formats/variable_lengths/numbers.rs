@@ -1 +1,159 @@
+use crate::formats::{
+    codec::{ensure_remaining, Read, Version, Write},
+    Result,
+};
+use bytes::{Buf, BufMut};
+use derive_more::{Display, From, Into};
+use integer_encoding::VarIntAsyncReader;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
 
+/// VARINT: a zigzag-encoded variable-length 32-bit signed integer
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Display, From, Into)]
+pub struct VarInt(pub i32);
+
+/// VARLONG: a zigzag-encoded variable-length 64-bit signed integer
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Display, From, Into)]
+pub struct VarLong(pub i64);
+
+/// UNSIGNED_VARINT: a variable-length 32-bit unsigned integer
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Display, From, Into)]
+pub struct UnsignedVarInt(pub u32);
+
+macro_rules! var_number_io_impl {
+    ($SelfT:ty, $n:ty, $max_bytes:expr, $from_u64:expr) => {
+        impl Write for $SelfT {
+            fn calculate_size(&self, _version: Version) -> i32 {
+                integer_encoding::VarInt::required_space(self.0) as i32
+            }
+            async fn write_to(
+                &self,
+                writer: &mut (dyn AsyncWrite + Send + Unpin),
+                _version: Version,
+            ) -> Result<()> {
+                write_varint_bytes(writer, self.0).await
+            }
+            fn encode<B: BufMut>(&self, buf: &mut B, _version: Version) -> Result<()> {
+                let mut bytes = [0_u8; 10];
+                let b = integer_encoding::VarInt::encode_var(self.0, &mut bytes);
+                buf.put_slice(&bytes[0..b]);
+                Ok(())
+            }
+        }
+
+        impl Read for $SelfT {
+            async fn read_from(
+                mut reader: &mut (dyn AsyncRead + Send + Unpin),
+                _version: Version,
+            ) -> Result<Self> {
+                let n: $n = reader.read_varint_async().await?;
+                Ok(n.into())
+            }
+            #[allow(clippy::redundant_closure_call)]
+            fn decode<B: Buf>(buf: &mut B, _version: Version) -> Result<Self> {
+                let n: $n = ($from_u64)(decode_varint(buf, $max_bytes)?);
+                Ok(n.into())
+            }
+        }
+    };
+}
+
+var_number_io_impl!(VarInt, i32, 5, |n| zigzag_decode(n) as i32);
+var_number_io_impl!(VarLong, i64, 10, zigzag_decode);
+var_number_io_impl!(UnsignedVarInt, u32, 5, |n| n as u32);
+
+async fn write_varint_bytes(
+    writer: &mut (dyn AsyncWrite + Send + Unpin),
+    n: impl integer_encoding::VarInt,
+) -> Result<()> {
+    let mut buf = [0_u8; 10];
+    let b = n.encode_var(&mut buf);
+    writer.write_all(&buf[0..b]).await?;
+    Ok(())
+}
+
+/// Decodes the unsigned LEB128 encoding shared by every varint, spanning at most `max_bytes`
+fn decode_varint(buf: &mut impl Buf, max_bytes: usize) -> Result<u64> {
+    let mut n = 0u64;
+    for i in 0..max_bytes {
+        ensure_remaining(buf, 1)?;
+        let byte = buf.get_u8();
+        n |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok(n);
+        }
+    }
+    Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "Unterminated varint").into())
+}
+
+fn zigzag_decode(n: u64) -> i64 {
+    ((n >> 1) as i64) ^ -((n & 1) as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::codec::assert_golden;
+    use std::fmt::Debug;
+
+    async fn assert_round_trip<N: Read + Write + PartialEq + Debug>(n: N, expected: &[u8]) {
+        assert_golden(n, Version::default(), expected).await
+    }
+
+    #[tokio::test]
+    async fn test_var_int_round_trip() {
+        assert_round_trip(VarInt(0), &[0x00]).await;
+        assert_round_trip(VarInt(-1), &[0x01]).await;
+        assert_round_trip(VarInt(1), &[0x02]).await;
+        assert_round_trip(VarInt(63), &[0x7e]).await;
+        assert_round_trip(VarInt(-64), &[0x7f]).await;
+        assert_round_trip(VarInt(64), &[0x80, 0x01]).await;
+        assert_round_trip(VarInt(150), &[0xac, 0x02]).await;
+        assert_round_trip(VarInt(i32::MAX), &[0xfe, 0xff, 0xff, 0xff, 0x0f]).await;
+        assert_round_trip(VarInt(i32::MIN), &[0xff, 0xff, 0xff, 0xff, 0x0f]).await;
+    }
+
+    #[tokio::test]
+    async fn test_var_long_round_trip() {
+        assert_round_trip(VarLong(0), &[0x00]).await;
+        assert_round_trip(VarLong(-1), &[0x01]).await;
+        assert_round_trip(VarLong(8191), &[0xfe, 0x7f]).await;
+        assert_round_trip(VarLong(-8193), &[0x81, 0x80, 0x01]).await;
+        assert_round_trip(VarLong(i32::MIN as i64), &[0xff, 0xff, 0xff, 0xff, 0x0f]).await;
+        assert_round_trip(
+            VarLong(i64::MAX),
+            &[0xfe, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01],
+        )
+        .await;
+        assert_round_trip(
+            VarLong(i64::MIN),
+            &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01],
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_unsigned_var_int_round_trip() {
+        assert_round_trip(UnsignedVarInt(0), &[0x00]).await;
+        assert_round_trip(UnsignedVarInt(1), &[0x01]).await;
+        assert_round_trip(UnsignedVarInt(127), &[0x7f]).await;
+        assert_round_trip(UnsignedVarInt(128), &[0x80, 0x01]).await;
+        assert_round_trip(UnsignedVarInt(300), &[0xac, 0x02]).await;
+        assert_round_trip(UnsignedVarInt(16384), &[0x80, 0x80, 0x01]).await;
+        assert_round_trip(UnsignedVarInt(u32::MAX), &[0xff, 0xff, 0xff, 0xff, 0x0f]).await;
+    }
+
+    #[tokio::test]
+    async fn test_unterminated_var_int_fails() {
+        let buf = [0xff_u8; 6];
+        assert!(VarInt::read_from(&mut buf.as_slice(), Version::default())
+            .await
+            .is_err());
+        assert!(
+            VarInt::read_from(&mut [0x80_u8].as_slice(), Version::default())
+                .await
+                .is_err()
+        );
+        assert!(VarInt::decode(&mut buf.as_slice(), Version::default()).is_err());
+        assert!(VarInt::decode(&mut [0x80_u8].as_slice(), Version::default()).is_err());
+    }
+}
@@ -1,68 +1,383 @@
+use super::numbers::UnsignedVarInt;
 use crate::formats::{
-    codec::{FixedLength, Read, Write},
-    Result,
+    codec::{read_bytes, take_bytes, FixedLength, Read, Version, Write},
+    FormatError, LengthKind, Result,
 };
+use bytes::{Buf, BufMut};
 use derive_more::{From, Into};
-use tokio::io::{AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
 use tracing::trace;
 
-impl<'a> Write for &'a str {
-    fn calculate_size(&self) -> i32 {
-        i16::SIZE + self.len() as i32
+impl Write for &str {
+    fn calculate_size(&self, version: Version) -> i32 {
+        if version.flexible {
+            compact_length_size(Some(self.len())) + self.len() as i32
+        } else {
+            i16::SIZE + self.len() as i32
+        }
     }
-    async fn write_to(&self, writer: &mut (dyn AsyncWrite + Send + Unpin)) -> Result<()> {
-        let len = self.len() as i16;
-        len.write_to(writer).await?;
+    async fn write_to(
+        &self,
+        writer: &mut (dyn AsyncWrite + Send + Unpin),
+        version: Version,
+    ) -> Result<()> {
+        if version.flexible {
+            write_compact_length(writer, Some(self.len())).await?;
+        } else {
+            (self.len() as i16).write_to(writer, version).await?;
+        }
         writer.write_all(self.as_bytes()).await?;
         Ok(())
     }
+    fn encode<B: BufMut>(&self, buf: &mut B, version: Version) -> Result<()> {
+        if version.flexible {
+            encode_compact_length(buf, Some(self.len()));
+        } else {
+            (self.len() as i16).encode(buf, version)?;
+        }
+        buf.put_slice(self.as_bytes());
+        Ok(())
+    }
 }
 
 impl Write for String {
-    fn calculate_size(&self) -> i32 {
-        self.as_str().calculate_size()
+    fn calculate_size(&self, version: Version) -> i32 {
+        self.as_str().calculate_size(version)
     }
-    async fn write_to(&self, writer: &mut (dyn AsyncWrite + Send + Unpin)) -> Result<()> {
-        self.as_str().write_to(writer).await
+    async fn write_to(
+        &self,
+        writer: &mut (dyn AsyncWrite + Send + Unpin),
+        version: Version,
+    ) -> Result<()> {
+        self.as_str().write_to(writer, version).await
+    }
+    fn encode<B: BufMut>(&self, buf: &mut B, version: Version) -> Result<()> {
+        self.as_str().encode(buf, version)
     }
 }
 
 impl Read for String {
-    async fn read_from(reader: &mut (dyn tokio::io::AsyncRead + Send + Unpin)) -> Result<Self> {
-        trace!("reading string len");
-        let len = i16::read_from(reader).await?;
-        let mut buf = vec![0u8; len as usize];
-        trace!("reading a string of len {len}");
-        reader.read_exact(&mut buf).await?;
-        Ok(String::from_utf8(buf)?)
+    async fn read_from(
+        reader: &mut (dyn tokio::io::AsyncRead + Send + Unpin),
+        version: Version,
+    ) -> Result<Self> {
+        NullableString::read_from(reader, version)
+            .await?
+            .0
+            .ok_or(FormatError::UnexpectedNull(LengthKind::String))
+    }
+    fn decode<B: Buf>(buf: &mut B, version: Version) -> Result<Self> {
+        NullableString::decode(buf, version)?
+            .0
+            .ok_or(FormatError::UnexpectedNull(LengthKind::String))
+    }
+}
+
+/// NULLABLE_STRING, or COMPACT_NULLABLE_STRING in flexible versions, where a length of -1 means
+/// null. Unlike an empty string, [None] is encoded as null.
+#[derive(Debug, Clone, Default, PartialEq, Eq, From, Into)]
+pub struct NullableString(pub Option<String>);
+
+impl From<String> for NullableString {
+    fn from(s: String) -> Self {
+        NullableString(Some(s))
     }
 }
 
-#[derive(Debug, Clone, From, Into)]
-pub struct NullableString(String);
+impl From<&str> for NullableString {
+    fn from(s: &str) -> Self {
+        NullableString(Some(s.to_string()))
+    }
+}
 
 impl Write for NullableString {
-    fn calculate_size(&self) -> i32 {
-        i16::SIZE + self.0.len() as i32
+    fn calculate_size(&self, version: Version) -> i32 {
+        match &self.0 {
+            Some(s) => s.calculate_size(version),
+            None if version.flexible => compact_length_size(None),
+            None => i16::SIZE,
+        }
     }
-    async fn write_to(&self, writer: &mut (dyn AsyncWrite + Send + Unpin)) -> Result<()> {
-        if self.0.is_empty() {
-            (-1i16).write_to(writer).await
-        } else {
-            (self.0.len() as i16).write_to(writer).await?;
-            writer.write_all(self.0.as_bytes()).await?;
-            Ok(())
+    async fn write_to(
+        &self,
+        writer: &mut (dyn AsyncWrite + Send + Unpin),
+        version: Version,
+    ) -> Result<()> {
+        match &self.0 {
+            Some(s) => s.write_to(writer, version).await,
+            None if version.flexible => write_compact_length(writer, None).await,
+            None => (-1i16).write_to(writer, version).await,
+        }
+    }
+    fn encode<B: BufMut>(&self, buf: &mut B, version: Version) -> Result<()> {
+        match &self.0 {
+            Some(s) => s.encode(buf, version),
+            None if version.flexible => {
+                encode_compact_length(buf, None);
+                Ok(())
+            }
+            None => (-1i16).encode(buf, version),
         }
     }
 }
 
 impl Read for NullableString {
-    async fn read_from(reader: &mut (dyn tokio::io::AsyncRead + Send + Unpin)) -> Result<Self> {
-        trace!("reading nullable_string len");
-        let len = i16::read_from(reader).await?.max(0);
-        let mut buf = vec![0u8; len as usize];
-        trace!("reading a nullable_string of len {len}");
-        reader.read_exact(&mut buf).await?;
-        Ok(String::from_utf8(buf)?.into())
+    async fn read_from(
+        reader: &mut (dyn tokio::io::AsyncRead + Send + Unpin),
+        version: Version,
+    ) -> Result<Self> {
+        trace!("reading string len");
+        let len = if version.flexible {
+            read_compact_length(reader).await?.map(|len| len as i64)
+        } else {
+            nullable_length(i16::read_from(reader, version).await?)
+        };
+        let Some(len) = string_length(len, version)? else {
+            return Ok(NullableString(None));
+        };
+        trace!("reading a string of len {len}");
+        let buf = read_bytes(reader, len).await?;
+        Ok(NullableString(Some(String::from_utf8(buf)?)))
+    }
+    fn decode<B: Buf>(buf: &mut B, version: Version) -> Result<Self> {
+        let len = if version.flexible {
+            decode_compact_length(buf)?.map(|len| len as i64)
+        } else {
+            nullable_length(i16::decode(buf, version)?)
+        };
+        match string_length(len, version)? {
+            Some(len) => Ok(NullableString(Some(String::from_utf8(take_bytes(
+                buf, len,
+            )?)?))),
+            None => Ok(NullableString(None)),
+        }
+    }
+}
+
+/// COMPACT_STRING: a string prefixed by its length + 1 as an UNSIGNED_VARINT
+#[derive(Debug, Clone, PartialEq, Eq, From, Into)]
+pub struct CompactString(pub String);
+
+impl Write for CompactString {
+    fn calculate_size(&self, _version: Version) -> i32 {
+        compact_length_size(Some(self.0.len())) + self.0.len() as i32
+    }
+    async fn write_to(
+        &self,
+        writer: &mut (dyn AsyncWrite + Send + Unpin),
+        _version: Version,
+    ) -> Result<()> {
+        write_compact_length(writer, Some(self.0.len())).await?;
+        writer.write_all(self.0.as_bytes()).await?;
+        Ok(())
+    }
+    fn encode<B: BufMut>(&self, buf: &mut B, _version: Version) -> Result<()> {
+        encode_compact_length(buf, Some(self.0.len()));
+        buf.put_slice(self.0.as_bytes());
+        Ok(())
+    }
+}
+
+impl Read for CompactString {
+    async fn read_from(
+        reader: &mut (dyn AsyncRead + Send + Unpin),
+        version: Version,
+    ) -> Result<Self> {
+        trace!("reading compact_string len");
+        let len = string_length(
+            read_compact_length(reader).await?.map(|len| len as i64),
+            version,
+        )?
+        .ok_or(FormatError::UnexpectedNull(LengthKind::String))?;
+        trace!("reading a compact_string of len {len}");
+        let buf = read_bytes(reader, len).await?;
+        Ok(CompactString(String::from_utf8(buf)?))
+    }
+    fn decode<B: Buf>(buf: &mut B, version: Version) -> Result<Self> {
+        let len = string_length(decode_compact_length(buf)?.map(|len| len as i64), version)?
+            .ok_or(FormatError::UnexpectedNull(LengthKind::String))?;
+        Ok(CompactString(String::from_utf8(take_bytes(buf, len)?)?))
+    }
+}
+
+/// COMPACT_NULLABLE_STRING: a [CompactString] where a length of -1 (encoded as 0) means null
+#[derive(Debug, Clone, PartialEq, Eq, From, Into)]
+pub struct CompactNullableString(pub Option<String>);
+
+impl Write for CompactNullableString {
+    fn calculate_size(&self, _version: Version) -> i32 {
+        let len = self.0.as_ref().map(String::len);
+        compact_length_size(len) + len.unwrap_or(0) as i32
+    }
+    async fn write_to(
+        &self,
+        writer: &mut (dyn AsyncWrite + Send + Unpin),
+        _version: Version,
+    ) -> Result<()> {
+        write_compact_length(writer, self.0.as_ref().map(String::len)).await?;
+        if let Some(s) = &self.0 {
+            writer.write_all(s.as_bytes()).await?;
+        }
+        Ok(())
+    }
+    fn encode<B: BufMut>(&self, buf: &mut B, _version: Version) -> Result<()> {
+        encode_compact_length(buf, self.0.as_ref().map(String::len));
+        if let Some(s) = &self.0 {
+            buf.put_slice(s.as_bytes());
+        }
+        Ok(())
+    }
+}
+
+impl Read for CompactNullableString {
+    async fn read_from(
+        reader: &mut (dyn AsyncRead + Send + Unpin),
+        version: Version,
+    ) -> Result<Self> {
+        trace!("reading compact_nullable_string len");
+        match read_compact_length(reader).await? {
+            None => Ok(CompactNullableString(None)),
+            Some(len) => {
+                let len = version.limits.check(LengthKind::String, len as i64)?;
+                trace!("reading a compact_nullable_string of len {len}");
+                let buf = read_bytes(reader, len).await?;
+                Ok(CompactNullableString(Some(String::from_utf8(buf)?)))
+            }
+        }
+    }
+    fn decode<B: Buf>(buf: &mut B, version: Version) -> Result<Self> {
+        match decode_compact_length(buf)? {
+            None => Ok(CompactNullableString(None)),
+            Some(len) => {
+                let len = version.limits.check(LengthKind::String, len as i64)?;
+                Ok(CompactNullableString(Some(String::from_utf8(take_bytes(
+                    buf, len,
+                )?)?)))
+            }
+        }
+    }
+}
+
+/// Length of a non-compact string, where -1 means null. Other negative lengths are kept as is
+/// for [string_length] to reject.
+fn nullable_length(len: i16) -> Option<i64> {
+    (len != -1).then_some(len.into())
+}
+
+/// Checks the length of a string against the limits, unless the string is null
+fn string_length(len: Option<i64>, version: Version) -> Result<Option<usize>> {
+    len.map(|len| version.limits.check(LengthKind::String, len))
+        .transpose()
+}
+
+/// Size of the UNSIGNED_VARINT length prefix of a compact type, where [None] is null
+pub(crate) fn compact_length_size(len: Option<usize>) -> i32 {
+    UnsignedVarInt(len.map(|l| l as u32 + 1).unwrap_or(0)).calculate_size(Version::default())
+}
+
+/// Writes the UNSIGNED_VARINT length prefix of a compact type, where [None] is null
+pub(crate) async fn write_compact_length(
+    writer: &mut (dyn AsyncWrite + Send + Unpin),
+    len: Option<usize>,
+) -> Result<()> {
+    UnsignedVarInt(len.map(|l| l as u32 + 1).unwrap_or(0))
+        .write_to(writer, Version::default())
+        .await
+}
+
+/// Reads the UNSIGNED_VARINT length prefix of a compact type, where [None] is null
+pub(crate) async fn read_compact_length(
+    reader: &mut (dyn AsyncRead + Send + Unpin),
+) -> Result<Option<usize>> {
+    let n = UnsignedVarInt::read_from(reader, Version::default())
+        .await?
+        .0;
+    Ok(n.checked_sub(1).map(|len| len as usize))
+}
+
+/// Synchronous counterpart of [write_compact_length]
+pub(crate) fn encode_compact_length(buf: &mut impl BufMut, len: Option<usize>) {
+    UnsignedVarInt(len.map(|l| l as u32 + 1).unwrap_or(0))
+        .encode(buf, Version::default())
+        .expect("varints always encode")
+}
+
+/// Synchronous counterpart of [read_compact_length]
+pub(crate) fn decode_compact_length(buf: &mut impl Buf) -> Result<Option<usize>> {
+    let n = UnsignedVarInt::decode(buf, Version::default())?.0;
+    Ok(n.checked_sub(1).map(|len| len as usize))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::FormatError;
+
+    #[tokio::test]
+    async fn test_compact_string() {
+        let s = CompactString("kafka".into());
+        let mut buf = Vec::new();
+        s.write_to(&mut buf, Version::default()).await.unwrap();
+        assert_eq!(buf, b"\x06kafka");
+        assert_eq!(s.calculate_size(Version::default()), buf.len() as i32);
+        let read = CompactString::read_from(&mut buf.as_slice(), Version::default())
+            .await
+            .unwrap();
+        assert_eq!(read, s);
+    }
+
+    #[tokio::test]
+    async fn test_compact_nullable_string() {
+        for (s, expected) in [
+            (CompactNullableString(None), b"\x00".to_vec()),
+            (CompactNullableString(Some("".into())), b"\x01".to_vec()),
+            (
+                CompactNullableString(Some("kafka".into())),
+                b"\x06kafka".to_vec(),
+            ),
+        ] {
+            let mut buf = Vec::new();
+            s.write_to(&mut buf, Version::default()).await.unwrap();
+            assert_eq!(buf, expected);
+            assert_eq!(s.calculate_size(Version::default()), buf.len() as i32);
+            let read = CompactNullableString::read_from(&mut buf.as_slice(), Version::default())
+                .await
+                .unwrap();
+            assert_eq!(read, s);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_string_lengths_out_of_bounds() {
+        let buf = [0xff, 0xfe, b'a'];
+        let err = String::decode(&mut buf.as_slice(), Version::default()).unwrap_err();
+        assert!(
+            matches!(err, FormatError::LengthOutOfBounds { .. }),
+            "{err}"
+        );
+        let err = String::read_from(&mut buf.as_slice(), Version::default())
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, FormatError::LengthOutOfBounds { .. }),
+            "{err}"
+        );
+
+        // Compact lengths go up to u32::MAX, beyond what brokers ever send
+        let mut buf = Vec::new();
+        UnsignedVarInt(u32::MAX)
+            .encode(&mut buf, Version::default())
+            .unwrap();
+        let err = CompactString::decode(&mut buf.as_slice(), Version::default()).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                FormatError::LengthOutOfBounds {
+                    kind: LengthKind::String,
+                    ..
+                }
+            ),
+            "{err}"
+        );
     }
 }
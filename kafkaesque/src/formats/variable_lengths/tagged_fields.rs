@@ -0,0 +1,144 @@
+use super::numbers::UnsignedVarInt;
+use crate::formats::{
+    codec::{read_bytes, take_bytes, Read, Version, Write},
+    LengthKind, Result,
+};
+use bytes::{Buf, BufMut};
+use derive_more::{From, Into};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use tracing::trace;
+
+/// A single entry of a [TaggedFields] section, with its data left undecoded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaggedField {
+    pub tag: u32,
+    pub data: Vec<u8>,
+}
+
+/// TAGGED_FIELDS: the section terminating every structure in a flexible version (KIP-482)
+#[derive(Debug, Clone, Default, PartialEq, Eq, From, Into)]
+pub struct TaggedFields(pub Vec<TaggedField>);
+
+impl TaggedField {
+    /// Encoded size of a field with the given tag and data size
+    pub fn encoded_size(tag: u32, data_size: i32) -> i32 {
+        UnsignedVarInt(tag).calculate_size(Version::default())
+            + UnsignedVarInt(data_size as u32).calculate_size(Version::default())
+            + data_size
+    }
+}
+
+impl Write for TaggedFields {
+    fn calculate_size(&self, version: Version) -> i32 {
+        UnsignedVarInt(self.0.len() as u32).calculate_size(version)
+            + self
+                .0
+                .iter()
+                .map(|f| TaggedField::encoded_size(f.tag, f.data.len() as i32))
+                .sum::<i32>()
+    }
+    async fn write_to(
+        &self,
+        writer: &mut (dyn AsyncWrite + Send + Unpin),
+        version: Version,
+    ) -> Result<()> {
+        UnsignedVarInt(self.0.len() as u32)
+            .write_to(writer, version)
+            .await?;
+        for f in &self.0 {
+            UnsignedVarInt(f.tag).write_to(writer, version).await?;
+            UnsignedVarInt(f.data.len() as u32)
+                .write_to(writer, version)
+                .await?;
+            writer.write_all(&f.data).await?;
+        }
+        Ok(())
+    }
+    fn encode<B: BufMut>(&self, buf: &mut B, version: Version) -> Result<()> {
+        UnsignedVarInt(self.0.len() as u32).encode(buf, version)?;
+        for f in &self.0 {
+            UnsignedVarInt(f.tag).encode(buf, version)?;
+            UnsignedVarInt(f.data.len() as u32).encode(buf, version)?;
+            buf.put_slice(&f.data);
+        }
+        Ok(())
+    }
+}
+
+impl Read for TaggedFields {
+    async fn read_from(
+        reader: &mut (dyn AsyncRead + Send + Unpin),
+        version: Version,
+    ) -> Result<Self> {
+        let count = UnsignedVarInt::read_from(reader, version).await?.0;
+        let count = version.limits.check(LengthKind::Array, count.into())?;
+        trace!("reading {count} tagged fields");
+        let mut fields = Vec::new();
+        for _ in 0..count {
+            let tag = UnsignedVarInt::read_from(reader, version).await?.0;
+            let len = UnsignedVarInt::read_from(reader, version).await?.0;
+            let len = version.limits.check(LengthKind::Bytes, len.into())?;
+            let data = read_bytes(reader, len).await?;
+            fields.push(TaggedField { tag, data });
+        }
+        Ok(TaggedFields(fields))
+    }
+    fn decode<B: Buf>(buf: &mut B, version: Version) -> Result<Self> {
+        let count = UnsignedVarInt::decode(buf, version)?.0;
+        let count = version.limits.check(LengthKind::Array, count.into())?;
+        let mut fields = Vec::with_capacity(count.min(buf.remaining()));
+        for _ in 0..count {
+            let tag = UnsignedVarInt::decode(buf, version)?.0;
+            let len = UnsignedVarInt::decode(buf, version)?.0;
+            let len = version.limits.check(LengthKind::Bytes, len.into())?;
+            let data = take_bytes(buf, len)?;
+            fields.push(TaggedField { tag, data });
+        }
+        Ok(TaggedFields(fields))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::codec::{assert_huge_length_rejected, HUGE_LENGTH};
+
+    #[tokio::test]
+    async fn test_tagged_fields() {
+        for (fields, expected) in [
+            (TaggedFields::default(), vec![0x00]),
+            (
+                TaggedFields(vec![
+                    TaggedField {
+                        tag: 0,
+                        data: vec![0x01, 0x02],
+                    },
+                    TaggedField {
+                        tag: 300,
+                        data: vec![],
+                    },
+                ]),
+                vec![0x02, 0x00, 0x02, 0x01, 0x02, 0xac, 0x02, 0x00],
+            ),
+        ] {
+            let mut buf = Vec::new();
+            fields.write_to(&mut buf, Version::default()).await.unwrap();
+            assert_eq!(buf, expected);
+            assert_eq!(fields.calculate_size(Version::default()), buf.len() as i32);
+            let read = TaggedFields::read_from(&mut buf.as_slice(), Version::default())
+                .await
+                .unwrap();
+            assert_eq!(read, fields);
+        }
+    }
+
+    #[test]
+    fn test_huge_length_with_short_body() {
+        let mut buf = vec![0x01, 0x00];
+        UnsignedVarInt(HUGE_LENGTH as u32)
+            .encode(&mut buf, Version::default())
+            .unwrap();
+        buf.extend([0x01, 0x02]);
+        assert_huge_length_rejected::<TaggedFields>(&buf, Version::default());
+    }
+}
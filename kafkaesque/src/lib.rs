@@ -1,12 +1,15 @@
 //! Unofficial Kafka implementations
 
-#![feature(async_fn_in_trait)]
 #![deny(warnings)]
 #![deny(clippy::all)]
-#![allow(incomplete_features)]
+#![allow(async_fn_in_trait)]
 
 /// Clients to Kafka brokers
 pub mod clients;
 
 /// Low-level formats and their codecs
 pub mod formats;
+
+/// An in-process mock broker, to test clients without a Kafka cluster
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
@@ -0,0 +1,549 @@
+use crate::formats::{
+    encode_response,
+    messages::{
+        ApiVersionsRequest, ApiVersionsResponse, ApiVersionsResponseApiVersion, CreateTopicsReqV0,
+        CreateTopicsRespV0, CreateTopicsRespV0Topic, DeleteTopicsReqV0, DeleteTopicsRespV0,
+        DeleteTopicsRespV0Topic, FetchRequest, FetchResponse, FetchResponseFetchableTopicResponse,
+        FetchResponsePartitionData, ListOffsetsRequest, ListOffsetsResponse,
+        ListOffsetsResponseListOffsetsPartitionResponse,
+        ListOffsetsResponseListOffsetsTopicResponse, MetadataRequest, MetadataRequestTopic,
+        MetadataResponse, MetadataResponseBroker, MetadataResponsePartition, MetadataResponseTopic,
+        ProduceRequest, ProduceResponse, ProduceResponsePartitionProduceResponse,
+        ProduceResponseTopicProduceResponse,
+    },
+    records::{RecordBatch, RecordSet, RecordSetEntry},
+    AnyRequest, ApiKey, ApiVersion, ErrorCode, NullableString, RequestHeader, Result, Version,
+    Write,
+};
+use std::collections::{BTreeMap, HashMap};
+use uuid::Uuid;
+
+/// Id of the only broker of the cluster
+const NODE_ID: i32 = 0;
+
+const CLUSTER_ID: &str = "mock-cluster";
+
+/// Every operation applying to topics, from read to alter configs, as a bitfield of ACL
+/// operation codes
+const TOPIC_OPERATIONS: i32 = 0b1101_1111_1000;
+
+/// ListOffsets timestamps looking up the log start offset, the high watermark and the offset of
+/// the record with the highest timestamp
+const EARLIEST_TIMESTAMP: i64 = -2;
+const LATEST_TIMESTAMP: i64 = -1;
+const MAX_TIMESTAMP: i64 = -3;
+
+/// In-memory state of a single-broker cluster
+#[derive(Debug)]
+pub(super) struct Cluster {
+    host: String,
+    port: i32,
+    /// Partitions of the topics, by name
+    topics: BTreeMap<String, Vec<Partition>>,
+    /// Ids of the topics, by name, assigned in order of creation
+    topic_ids: BTreeMap<String, Uuid>,
+    /// Number of topics created so far
+    created_topics: u128,
+    /// Highest versions advertised for the APIs capped below what [AnyRequest] supports
+    max_versions: HashMap<ApiKey, ApiVersion>,
+}
+
+/// Log of a partition
+#[derive(Debug, Default)]
+pub(super) struct Partition {
+    /// Batches appended so far, with their offsets assigned
+    pub(super) batches: Vec<RecordBatch>,
+    /// Offset of the next record appended
+    next_offset: i64,
+}
+
+impl Partition {
+    /// Appends the batches of `records`, returning the offset of the first record. Legacy
+    /// message sets aren't supported.
+    fn append(&mut self, records: RecordSet) -> std::result::Result<i64, ErrorCode> {
+        let mut batches = Vec::with_capacity(records.0.len());
+        for entry in records.0 {
+            match entry {
+                RecordSetEntry::Batch(batch) => batches.push(batch),
+                RecordSetEntry::Legacy(_) => return Err(ErrorCode::UnsupportedForMessageFormat),
+            }
+        }
+        let base_offset = self.next_offset;
+        for mut batch in batches {
+            batch.base_offset = self.next_offset;
+            self.next_offset = batch.last_offset() + 1;
+            self.batches.push(batch);
+        }
+        Ok(base_offset)
+    }
+
+    /// Reads the batches holding offsets from `fetch_offset` on, stopping before the batch that
+    /// would exceed `max_bytes` unless it's the first one, as brokers do
+    fn read(&self, fetch_offset: i64, max_bytes: i32) -> std::result::Result<RecordSet, ErrorCode> {
+        if !(0..=self.next_offset).contains(&fetch_offset) {
+            return Err(ErrorCode::OffsetOutOfRange);
+        }
+        let mut size = 0;
+        let mut entries = Vec::new();
+        for batch in self
+            .batches
+            .iter()
+            .filter(|b| b.last_offset() >= fetch_offset)
+        {
+            size += batch.calculate_size(Version::default());
+            if size > max_bytes && !entries.is_empty() {
+                break;
+            }
+            entries.push(RecordSetEntry::Batch(batch.clone()));
+        }
+        Ok(RecordSet(entries))
+    }
+
+    /// Offset and timestamp answering a ListOffsets lookup of `timestamp`, which is either a
+    /// timestamp or one of [EARLIEST_TIMESTAMP], [LATEST_TIMESTAMP] and [MAX_TIMESTAMP]. Lookups
+    /// matching no record return an offset and timestamp of -1.
+    fn list_offset(&self, timestamp: i64) -> (i64, i64) {
+        let mut records = self.batches.iter().flat_map(|batch| {
+            batch.records.iter().map(|record| {
+                (
+                    batch.base_offset + i64::from(record.offset_delta),
+                    batch.base_timestamp + record.timestamp_delta,
+                )
+            })
+        });
+        let found = match timestamp {
+            EARLIEST_TIMESTAMP => return (0, -1),
+            LATEST_TIMESTAMP => return (self.next_offset, -1),
+            // The first of the records with the highest timestamp
+            MAX_TIMESTAMP => records.rev().max_by_key(|&(_, timestamp)| timestamp),
+            timestamp => records.find(|&(_, t)| t >= timestamp),
+        };
+        found.unwrap_or((-1, -1))
+    }
+}
+
+impl Cluster {
+    pub(super) fn new(host: String, port: u16) -> Self {
+        Cluster {
+            host,
+            port: port.into(),
+            topics: BTreeMap::new(),
+            topic_ids: BTreeMap::new(),
+            created_topics: 0,
+            max_versions: HashMap::new(),
+        }
+    }
+
+    /// Advertises at most `max_version` of an API from now on
+    pub(super) fn cap_version(&mut self, api_key: ApiKey, max_version: ApiVersion) {
+        self.max_versions.insert(api_key, max_version);
+    }
+
+    pub(super) fn topics(&self) -> &BTreeMap<String, Vec<Partition>> {
+        &self.topics
+    }
+
+    pub(super) fn topic_id(&self, name: &str) -> Option<Uuid> {
+        self.topic_ids.get(name).copied()
+    }
+
+    fn topic_name(&self, id: Uuid) -> Option<&String> {
+        self.topic_ids
+            .iter()
+            .find(|(_, &topic_id)| topic_id == id)
+            .map(|(name, _)| name)
+    }
+
+    /// Creates a topic, or replaces it with an empty one with a new id
+    pub(super) fn create_topic(&mut self, name: String, partitions: i32) {
+        let partitions = (0..partitions).map(|_| Partition::default()).collect();
+        self.created_topics += 1;
+        self.topic_ids
+            .insert(name.clone(), Uuid::from_u128(self.created_topics));
+        self.topics.insert(name, partitions);
+    }
+
+    /// Removes a topic, returning whether it existed
+    fn delete_topic(&mut self, name: &str) -> bool {
+        self.topic_ids.remove(name);
+        self.topics.remove(name).is_some()
+    }
+
+    /// Handles a request, returning the frame of its response, which is empty for requests
+    /// brokers don't respond to. A scripted `error` replaces the error codes of the response and
+    /// prevents any change to the cluster.
+    pub(super) fn handle(
+        &mut self,
+        header: &RequestHeader,
+        request: AnyRequest,
+        error: Option<ErrorCode>,
+    ) -> Result<Vec<u8>> {
+        let (cid, api_version) = (header.cid, header.api_version);
+        match request {
+            AnyRequest::ApiVersions(_) => {
+                encode_response::<ApiVersionsRequest>(cid, api_version, &self.api_versions(error))
+            }
+            AnyRequest::CreateTopics(req) => {
+                let resp = self.create_topics(req, error);
+                encode_response::<CreateTopicsReqV0>(cid, api_version, &resp)
+            }
+            AnyRequest::DeleteTopics(req) => {
+                let resp = self.delete_topics(req, error);
+                encode_response::<DeleteTopicsReqV0>(cid, api_version, &resp)
+            }
+            AnyRequest::Fetch(req) => {
+                let resp = self.fetch(api_version, req, error);
+                encode_response::<FetchRequest>(cid, api_version, &resp)
+            }
+            AnyRequest::ListOffsets(req) => {
+                let resp = self.list_offsets(api_version, req, error);
+                encode_response::<ListOffsetsRequest>(cid, api_version, &resp)
+            }
+            AnyRequest::Metadata(req) => {
+                let resp = self.metadata(api_version, req, error);
+                encode_response::<MetadataRequest>(cid, api_version, &resp)
+            }
+            AnyRequest::Produce(req) => {
+                // Brokers don't respond to requests without acknowledgments
+                let acks = req.acks;
+                let resp = self.produce(req, error);
+                if acks == 0 {
+                    return Ok(vec![]);
+                }
+                encode_response::<ProduceRequest>(cid, api_version, &resp)
+            }
+        }
+    }
+
+    /// Every API [AnyRequest] can hold, with the versions it supports up to their caps
+    fn api_versions(&self, error: Option<ErrorCode>) -> ApiVersionsResponse {
+        ApiVersionsResponse {
+            error_code: error.unwrap_or_default(),
+            api_keys: AnyRequest::VERSIONS
+                .iter()
+                .map(|&(api_key, versions)| {
+                    let max_version = match self.max_versions.get(&api_key) {
+                        Some(&cap) => versions.max.min(cap),
+                        None => versions.max,
+                    };
+                    ApiVersionsResponseApiVersion {
+                        api_key: api_key.into(),
+                        min_version: versions.min.0,
+                        max_version: max_version.0,
+                    }
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn create_topics(
+        &mut self,
+        req: CreateTopicsReqV0,
+        error: Option<ErrorCode>,
+    ) -> CreateTopicsRespV0 {
+        let topics = req
+            .topics
+            .into_iter()
+            .map(|topic| {
+                let err_code = error.unwrap_or_else(|| {
+                    if self.topics.contains_key(&topic.name) {
+                        ErrorCode::TopicAlreadyExists
+                    } else if topic.num_partitions <= 0 {
+                        ErrorCode::InvalidPartitions
+                    } else if topic.replication_factor != 1 {
+                        ErrorCode::InvalidReplicationFactor
+                    } else {
+                        self.create_topic(topic.name.clone(), topic.num_partitions);
+                        ErrorCode::None
+                    }
+                });
+                CreateTopicsRespV0Topic {
+                    name: topic.name,
+                    err_code,
+                }
+            })
+            .collect();
+        CreateTopicsRespV0 { topics }
+    }
+
+    fn delete_topics(
+        &mut self,
+        req: DeleteTopicsReqV0,
+        error: Option<ErrorCode>,
+    ) -> DeleteTopicsRespV0 {
+        let topics = req
+            .topic_names
+            .into_iter()
+            .map(|name| {
+                let err_code = error.unwrap_or_else(|| {
+                    if self.delete_topic(&name) {
+                        ErrorCode::None
+                    } else {
+                        ErrorCode::UnknownTopicOrPartition
+                    }
+                });
+                DeleteTopicsRespV0Topic { name, err_code }
+            })
+            .collect();
+        DeleteTopicsRespV0 { topics }
+    }
+
+    fn produce(&mut self, req: ProduceRequest, error: Option<ErrorCode>) -> ProduceResponse {
+        let valid_acks = matches!(req.acks, -1..=1);
+        let responses = req
+            .topic_data
+            .into_iter()
+            .map(|topic| {
+                let mut partitions = self.topics.get_mut(&topic.name);
+                let partition_responses = topic
+                    .partition_data
+                    .into_iter()
+                    .map(|data| {
+                        let partition = usize::try_from(data.index)
+                            .ok()
+                            .and_then(|index| partitions.as_mut()?.get_mut(index));
+                        let appended = match (error, partition) {
+                            (Some(error_code), _) => Err(error_code),
+                            (None, _) if !valid_acks => Err(ErrorCode::InvalidRequiredAcks),
+                            (None, None) => Err(ErrorCode::UnknownTopicOrPartition),
+                            (None, Some(partition)) => partition.append(data.records),
+                        };
+                        let (error_code, base_offset) = match appended {
+                            Ok(base_offset) => (ErrorCode::None, base_offset),
+                            Err(error_code) => (error_code, -1),
+                        };
+                        ProduceResponsePartitionProduceResponse {
+                            index: data.index,
+                            error_code,
+                            base_offset,
+                            log_start_offset: 0,
+                            ..Default::default()
+                        }
+                    })
+                    .collect();
+                ProduceResponseTopicProduceResponse {
+                    name: topic.name,
+                    partition_responses,
+                }
+            })
+            .collect();
+        ProduceResponse {
+            responses,
+            throttle_time_ms: 0,
+        }
+    }
+
+    /// Reads the requested partitions right away, regardless of `min_bytes`. Topics are named
+    /// before version 13 and identified by their ids since.
+    fn fetch(
+        &self,
+        api_version: ApiVersion,
+        req: FetchRequest,
+        error: Option<ErrorCode>,
+    ) -> FetchResponse {
+        let by_name = api_version < ApiVersion(13);
+        let responses = req
+            .topics
+            .into_iter()
+            .map(|topic| {
+                let name = if by_name {
+                    Some(&topic.topic)
+                } else {
+                    self.topic_name(topic.topic_id)
+                };
+                let partitions = name.and_then(|name| self.topics.get(name));
+                let unknown = if by_name {
+                    ErrorCode::UnknownTopicOrPartition
+                } else {
+                    ErrorCode::UnknownTopicId
+                };
+                let partitions = topic
+                    .partitions
+                    .into_iter()
+                    .map(|fetch| {
+                        let partition = usize::try_from(fetch.partition)
+                            .ok()
+                            .and_then(|index| partitions?.get(index));
+                        let read = match (error, partition) {
+                            (Some(error_code), _) => Err(error_code),
+                            (None, None) => Err(unknown),
+                            (None, Some(partition)) => partition
+                                .read(fetch.fetch_offset, fetch.partition_max_bytes)
+                                .map(|records| (partition.next_offset, records)),
+                        };
+                        match read {
+                            Ok((high_watermark, records)) => FetchResponsePartitionData {
+                                partition_index: fetch.partition,
+                                high_watermark,
+                                last_stable_offset: high_watermark,
+                                log_start_offset: 0,
+                                records,
+                                ..Default::default()
+                            },
+                            Err(error_code) => FetchResponsePartitionData {
+                                partition_index: fetch.partition,
+                                error_code,
+                                high_watermark: -1,
+                                ..Default::default()
+                            },
+                        }
+                    })
+                    .collect();
+                FetchResponseFetchableTopicResponse {
+                    topic: topic.topic,
+                    topic_id: topic.topic_id,
+                    partitions,
+                }
+            })
+            .collect();
+        FetchResponse {
+            error_code: ErrorCode::None,
+            session_id: 0,
+            responses,
+            ..Default::default()
+        }
+    }
+
+    fn list_offsets(
+        &self,
+        api_version: ApiVersion,
+        req: ListOffsetsRequest,
+        error: Option<ErrorCode>,
+    ) -> ListOffsetsResponse {
+        let topics = req
+            .topics
+            .into_iter()
+            .map(|topic| {
+                let partitions = self.topics.get(&topic.name);
+                let partitions = topic
+                    .partitions
+                    .into_iter()
+                    .map(|lookup| {
+                        let partition = usize::try_from(lookup.partition_index)
+                            .ok()
+                            .and_then(|index| partitions?.get(index));
+                        let found = match (error, partition) {
+                            (Some(error_code), _) => Err(error_code),
+                            (None, None) => Err(ErrorCode::UnknownTopicOrPartition),
+                            (None, Some(partition)) => Ok(partition.list_offset(lookup.timestamp)),
+                        };
+                        match found {
+                            Ok((offset, timestamp)) => {
+                                ListOffsetsResponseListOffsetsPartitionResponse {
+                                    partition_index: lookup.partition_index,
+                                    // Version 0 responds with a list of offsets instead
+                                    old_style_offsets: if api_version == ApiVersion(0) {
+                                        vec![offset]
+                                    } else {
+                                        vec![]
+                                    },
+                                    timestamp,
+                                    offset,
+                                    leader_epoch: 0,
+                                    ..Default::default()
+                                }
+                            }
+                            Err(error_code) => ListOffsetsResponseListOffsetsPartitionResponse {
+                                partition_index: lookup.partition_index,
+                                error_code,
+                                ..Default::default()
+                            },
+                        }
+                    })
+                    .collect();
+                ListOffsetsResponseListOffsetsTopicResponse {
+                    name: topic.name,
+                    partitions,
+                }
+            })
+            .collect();
+        ListOffsetsResponse {
+            throttle_time_ms: 0,
+            topics,
+        }
+    }
+
+    /// Describes the requested topics, or all of them if the request has no topic list, or an
+    /// empty one in version 0. Topics are never created automatically.
+    fn metadata(
+        &self,
+        api_version: ApiVersion,
+        req: MetadataRequest,
+        error: Option<ErrorCode>,
+    ) -> MetadataResponse {
+        let requested = match req.topics {
+            Some(topics) if !topics.is_empty() || api_version > ApiVersion(0) => topics,
+            _ => self
+                .topics
+                .keys()
+                .map(|name| MetadataRequestTopic {
+                    name: name.clone().into(),
+                    ..Default::default()
+                })
+                .collect(),
+        };
+        let topic_authorized_operations = if req.include_topic_authorized_operations {
+            TOPIC_OPERATIONS
+        } else {
+            i32::MIN
+        };
+        let topics = requested
+            .into_iter()
+            .map(|topic| {
+                // Since version 12, topics may be identified by id only
+                let name = match topic.name.0 {
+                    Some(name) => Some(name),
+                    None => self.topic_name(topic.topic_id).cloned(),
+                };
+                let partitions = name.as_ref().and_then(|name| self.topics.get(name));
+                match (name, partitions) {
+                    (Some(name), Some(partitions)) => MetadataResponseTopic {
+                        error_code: error.unwrap_or_default(),
+                        topic_id: self.topic_id(&name).unwrap_or_default(),
+                        name: name.into(),
+                        partitions: (0..partitions.len() as i32).map(partition).collect(),
+                        topic_authorized_operations,
+                        ..Default::default()
+                    },
+                    (name, _) => MetadataResponseTopic {
+                        error_code: error.unwrap_or(if name.is_some() {
+                            ErrorCode::UnknownTopicOrPartition
+                        } else {
+                            ErrorCode::UnknownTopicId
+                        }),
+                        name: NullableString(name),
+                        topic_id: topic.topic_id,
+                        ..Default::default()
+                    },
+                }
+            })
+            .collect();
+        MetadataResponse {
+            brokers: vec![MetadataResponseBroker {
+                node_id: NODE_ID,
+                host: self.host.clone(),
+                port: self.port,
+                rack: None.into(),
+            }],
+            cluster_id: CLUSTER_ID.into(),
+            controller_id: NODE_ID,
+            topics,
+            ..Default::default()
+        }
+    }
+}
+
+/// Supports every version of every API [AnyRequest] can decode
+/// A partition led by the only broker
+fn partition(partition_index: i32) -> MetadataResponsePartition {
+    MetadataResponsePartition {
+        error_code: ErrorCode::None,
+        partition_index,
+        leader_id: NODE_ID,
+        leader_epoch: 0,
+        replica_nodes: vec![NODE_ID],
+        isr_nodes: vec![NODE_ID],
+        offline_replicas: vec![],
+    }
+}
@@ -0,0 +1,597 @@
+use super::cluster::Cluster;
+use crate::formats::{
+    decode_request, read_frame, records::RecordBatch, AnyRequest, ApiKey, ApiVersion, DecodeLimits,
+    ErrorCode, RequestHeader, Result,
+};
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::{
+    io::{AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    task::{JoinHandle, JoinSet},
+};
+use tracing::debug;
+use uuid::Uuid;
+
+/// What a [MockBroker] does with the next request of an API, instead of simply responding
+#[derive(Debug, Clone, PartialEq)]
+pub enum Script {
+    /// Respond with this error code wherever the response has one, without changing any state
+    Error(ErrorCode),
+    /// Wait before handling the request. Requests are handled in order on each connection, so
+    /// later requests on the same connection wait too.
+    Delay(Duration),
+    /// Close the connection without responding
+    Disconnect,
+}
+
+/// A single Kafka broker running in-process on an ephemeral port of the loopback interface,
+/// keeping its topics in memory.
+///
+/// It answers every request [AnyRequest] can hold, in every version [AnyRequest::VERSIONS]
+/// lists unless [capped](MockBroker::cap_version), and can be scripted to fail, delay or drop the
+/// next requests of an API. The broker stops when dropped, closing its connections.
+#[derive(Debug)]
+pub struct MockBroker {
+    addr: SocketAddr,
+    shared: Arc<Mutex<Shared>>,
+    server: JoinHandle<()>,
+}
+
+#[derive(Debug)]
+struct Shared {
+    cluster: Cluster,
+    scripts: HashMap<ApiKey, VecDeque<Script>>,
+    requests: Vec<(RequestHeader, AnyRequest)>,
+}
+
+impl MockBroker {
+    /// Starts a broker, which accepts connections as soon as this returns
+    pub async fn start() -> Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let shared = Arc::new(Mutex::new(Shared {
+            cluster: Cluster::new(addr.ip().to_string(), addr.port()),
+            scripts: HashMap::new(),
+            requests: Vec::new(),
+        }));
+        let server = tokio::spawn(serve(listener, shared.clone()));
+        debug!("Mock broker listening on {addr}");
+        Ok(MockBroker {
+            addr,
+            shared,
+            server,
+        })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// `host:port` of the broker, as listed in bootstrap broker lists
+    pub fn address(&self) -> String {
+        self.addr.to_string()
+    }
+
+    /// Creates a topic, or changes its partition count if it exists
+    pub fn create_topic(&self, name: impl Into<String>, partitions: i32) {
+        self.lock().cluster.create_topic(name.into(), partitions);
+    }
+
+    /// Names of the existing topics, in order
+    pub fn topics(&self) -> Vec<String> {
+        self.lock().cluster.topics().keys().cloned().collect()
+    }
+
+    /// Id of a topic, which fetch requests refer to since version 13
+    pub fn topic_id(&self, topic: &str) -> Option<Uuid> {
+        self.lock().cluster.topic_id(topic)
+    }
+
+    /// Batches produced to a partition so far, with their offsets assigned, or [None] if the
+    /// partition doesn't exist
+    pub fn batches(&self, topic: &str, partition: i32) -> Option<Vec<RecordBatch>> {
+        let shared = self.lock();
+        let partition = shared
+            .cluster
+            .topics()
+            .get(topic)?
+            .get(usize::try_from(partition).ok()?)?;
+        Some(partition.batches.clone())
+    }
+
+    /// Queues `script` for the next request of `api_key` that isn't scripted already
+    pub fn script(&self, api_key: ApiKey, script: Script) {
+        self.lock()
+            .scripts
+            .entry(api_key)
+            .or_default()
+            .push_back(script);
+    }
+
+    /// Advertises at most `max_version` of an API in ApiVersions responses from now on, as
+    /// older brokers do. Connections negotiate versions when they open, so only later ones see
+    /// the cap.
+    pub fn cap_version(&self, api_key: ApiKey, max_version: impl Into<ApiVersion>) {
+        self.lock().cluster.cap_version(api_key, max_version.into());
+    }
+
+    /// Every request received so far, in order
+    pub fn requests(&self) -> Vec<(RequestHeader, AnyRequest)> {
+        self.lock().requests.clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Shared> {
+        self.shared.lock().expect("mock broker lock poisoned")
+    }
+}
+
+impl Drop for MockBroker {
+    fn drop(&mut self) {
+        // Connections are aborted along with the set holding them
+        self.server.abort();
+    }
+}
+
+async fn serve(listener: TcpListener, shared: Arc<Mutex<Shared>>) {
+    let mut connections = JoinSet::new();
+    loop {
+        match listener.accept().await {
+            Ok((stream, peer)) => {
+                debug!("Mock broker accepted a connection from {peer}");
+                connections.spawn(serve_connection(stream, shared.clone()));
+            }
+            Err(err) => debug!("Mock broker failed accepting a connection: {err}"),
+        }
+        while connections.try_join_next().is_some() {}
+    }
+}
+
+/// Handles the requests of a connection one after the other, like brokers do
+async fn serve_connection(stream: TcpStream, shared: Arc<Mutex<Shared>>) {
+    let (read_half, mut write_half) = stream.into_split();
+    let mut reader = BufReader::new(read_half);
+    loop {
+        let request = match read_frame(&mut reader, DecodeLimits::DEFAULT).await {
+            Ok(frame) => decode_request(frame, DecodeLimits::DEFAULT),
+            Err(err) => {
+                debug!("Mock broker closing connection: {err}");
+                return;
+            }
+        };
+        let (header, request) = match request {
+            Ok(request) => request,
+            Err(err) => {
+                // Brokers close connections sending requests they can't parse
+                debug!("Mock broker closing connection after an invalid request: {err}");
+                return;
+            }
+        };
+
+        let script = {
+            let mut shared = shared.lock().expect("mock broker lock poisoned");
+            shared.requests.push((header.clone(), request.clone()));
+            shared
+                .scripts
+                .get_mut(&header.api_key)
+                .and_then(VecDeque::pop_front)
+        };
+        let error = match script {
+            None => None,
+            Some(Script::Error(error_code)) => Some(error_code),
+            Some(Script::Delay(delay)) => {
+                tokio::time::sleep(delay).await;
+                None
+            }
+            Some(Script::Disconnect) => {
+                debug!("Mock broker disconnecting on {:?}", header.api_key);
+                return;
+            }
+        };
+
+        let response = shared
+            .lock()
+            .expect("mock broker lock poisoned")
+            .cluster
+            .handle(&header, request, error);
+        let written = match response {
+            Ok(frame) => write_half.write_all(&frame).await,
+            Err(err) => {
+                debug!("Mock broker failed encoding a response: {err}");
+                return;
+            }
+        };
+        if let Err(err) = written {
+            debug!("Mock broker closing connection: {err}");
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::{
+        messages::{
+            ApiVersionsRequest, CreateTopicsReqV0, CreateTopicsReqV0CreateTopic, DeleteTopicsReqV0,
+            FetchRequest, FetchRequestFetchPartition, FetchRequestFetchTopic, MetadataReqV0Topic,
+            MetadataRequestV0, ProduceRequest, ProduceRequestPartitionProduceData,
+            ProduceRequestTopicProduceData,
+        },
+        records::{Record, RecordBatchAttributes, RecordSet, RecordSetEntry},
+        ApiVersion, BrokerConnection, ConnectionConfig, FormatError,
+    };
+    use bytes::Bytes;
+    use std::time::Instant;
+
+    fn create_topic(name: &str, num_partitions: i32) -> CreateTopicsReqV0CreateTopic {
+        CreateTopicsReqV0CreateTopic {
+            name: name.into(),
+            num_partitions,
+            replication_factor: 1,
+            assignments: vec![],
+            configs: vec![],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_topics() {
+        let broker = MockBroker::start().await.unwrap();
+        broker.create_topic("existing", 2);
+        let conn = BrokerConnection::connect("test-client", broker.addr())
+            .await
+            .unwrap();
+
+        let req = CreateTopicsReqV0 {
+            topics: vec![
+                create_topic("created", 3),
+                create_topic("existing", 1),
+                create_topic("invalid", 0),
+            ],
+            timeout_ms: 1000,
+        };
+        let resp = conn.send(req).await.unwrap();
+        let err_codes: Vec<ErrorCode> = resp.topics.iter().map(|t| t.err_code).collect();
+        assert_eq!(
+            err_codes,
+            [
+                ErrorCode::None,
+                ErrorCode::TopicAlreadyExists,
+                ErrorCode::InvalidPartitions
+            ]
+        );
+        assert_eq!(broker.topics(), ["created", "existing"]);
+
+        let resp = conn
+            .send(MetadataRequestV0 {
+                topics: vec![
+                    MetadataReqV0Topic {
+                        name: "created".into(),
+                    },
+                    MetadataReqV0Topic {
+                        name: "unknown".into(),
+                    },
+                ],
+            })
+            .await
+            .unwrap();
+        assert_eq!(resp.brokers[0].port, i32::from(broker.addr().port()));
+        assert_eq!(resp.topics[0].error_code, ErrorCode::None);
+        assert_eq!(resp.topics[0].partitions.len(), 3);
+        assert_eq!(
+            resp.topics[1].error_code,
+            ErrorCode::UnknownTopicOrPartition
+        );
+
+        let resp = conn
+            .send(MetadataRequestV0 { topics: vec![] })
+            .await
+            .unwrap();
+        let names: Vec<&str> = resp.topics.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, ["created", "existing"]);
+
+        // The handshake, then each request sent above
+        let requests = broker.requests();
+        assert_eq!(requests.len(), 4);
+        assert_eq!(requests[0].0.api_key, ApiKey::ApiVersions);
+        assert_eq!(requests[0].0.client_id, "test-client");
+        assert_eq!(requests[1].1.api_key(), ApiKey::CreateTopics);
+    }
+
+    fn produce_request(topic: &str, partition: i32, values: &[&'static [u8]]) -> ProduceRequest {
+        let batch = RecordBatch {
+            base_offset: 0,
+            partition_leader_epoch: -1,
+            attributes: RecordBatchAttributes::default(),
+            last_offset_delta: values.len() as i32 - 1,
+            base_timestamp: 1_000,
+            max_timestamp: 1_000,
+            producer_id: -1,
+            producer_epoch: -1,
+            base_sequence: -1,
+            records: values
+                .iter()
+                .enumerate()
+                .map(|(i, value)| Record {
+                    offset_delta: i as i32,
+                    value: Some(Bytes::from_static(value)),
+                    ..Default::default()
+                })
+                .collect(),
+        };
+        ProduceRequest {
+            acks: -1,
+            timeout_ms: 1000,
+            topic_data: vec![ProduceRequestTopicProduceData {
+                name: topic.into(),
+                partition_data: vec![ProduceRequestPartitionProduceData {
+                    index: partition,
+                    records: RecordSet(vec![RecordSetEntry::Batch(batch)]),
+                }],
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_produce() {
+        let broker = MockBroker::start().await.unwrap();
+        broker.create_topic("topic", 2);
+        let conn = BrokerConnection::connect("test-client", broker.addr())
+            .await
+            .unwrap();
+
+        let mut base_offsets = Vec::new();
+        for values in [&[&b"a"[..], b"b"][..], &[b"c"]] {
+            let resp = conn
+                .send(produce_request("topic", 1, values))
+                .await
+                .unwrap();
+            let partition = &resp.responses[0].partition_responses[0];
+            assert_eq!(partition.error_code, ErrorCode::None);
+            base_offsets.push(partition.base_offset);
+        }
+        assert_eq!(base_offsets, [0, 2]);
+        let batches = broker.batches("topic", 1).unwrap();
+        let offsets: Vec<i64> = batches.iter().map(|batch| batch.base_offset).collect();
+        assert_eq!(offsets, [0, 2]);
+        assert_eq!(batches[1].records[0].value, Some(Bytes::from_static(b"c")));
+        assert_eq!(broker.batches("topic", 0), Some(vec![]));
+
+        for (topic, partition) in [("topic", 2), ("unknown", 0)] {
+            let resp = conn
+                .send(produce_request(topic, partition, &[b"d"]))
+                .await
+                .unwrap();
+            assert_eq!(
+                resp.responses[0].partition_responses[0].error_code,
+                ErrorCode::UnknownTopicOrPartition
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_produce_without_acks() {
+        let broker = MockBroker::start().await.unwrap();
+        broker.create_topic("topic", 1);
+        let conn = BrokerConnection::connect("test-client", broker.addr())
+            .await
+            .unwrap();
+
+        let request = ProduceRequest {
+            acks: 0,
+            ..produce_request("topic", 0, &[b"a"])
+        };
+        assert!(matches!(
+            conn.send(request.clone()).await,
+            Err(FormatError::NoResponseExpected(ApiKey::Produce))
+        ));
+        conn.send_without_response(request).await.unwrap();
+        assert!(matches!(
+            conn.send_without_response(produce_request("topic", 0, &[b"b"]))
+                .await,
+            Err(FormatError::ResponseExpected(ApiKey::Produce))
+        ));
+
+        // The connection keeps matching responses to the requests that follow
+        let resp = conn
+            .send(MetadataRequestV0 { topics: vec![] })
+            .await
+            .unwrap();
+        assert_eq!(resp.topics.len(), 1);
+        let offsets: Vec<i64> = broker
+            .batches("topic", 0)
+            .unwrap()
+            .iter()
+            .map(|batch| batch.base_offset)
+            .collect();
+        assert_eq!(offsets, [0]);
+    }
+
+    #[tokio::test]
+    async fn test_produce_without_acks_waits_for_in_flight_requests() {
+        let broker = MockBroker::start().await.unwrap();
+        broker.create_topic("topic", 1);
+        let config = ConnectionConfig {
+            max_in_flight: 1,
+            ..ConnectionConfig::default()
+        };
+        let conn = BrokerConnection::connect_with_config("test-client", broker.addr(), config)
+            .await
+            .unwrap();
+        broker.script(ApiKey::Metadata, Script::Delay(Duration::from_millis(300)));
+
+        let request = ProduceRequest {
+            acks: 0,
+            ..produce_request("topic", 0, &[b"a"])
+        };
+        // The metadata request is polled first, taking the only permit until it's answered
+        let (resp, produced) = tokio::join!(
+            conn.send(MetadataRequestV0 { topics: vec![] }),
+            tokio::time::timeout(
+                Duration::from_millis(100),
+                conn.send_without_response(request.clone())
+            )
+        );
+        resp.unwrap();
+        assert!(produced.is_err(), "produced while a request was in flight");
+
+        conn.send_without_response(request).await.unwrap();
+        conn.send(MetadataRequestV0 { topics: vec![] })
+            .await
+            .unwrap();
+        assert_eq!(broker.batches("topic", 0).unwrap().len(), 1);
+    }
+
+    fn fetch_request(topic: &str, topic_id: Uuid, fetch_offset: i64) -> FetchRequest {
+        FetchRequest {
+            replica_id: -1,
+            max_wait_ms: 100,
+            topics: vec![FetchRequestFetchTopic {
+                topic: topic.into(),
+                topic_id,
+                partitions: vec![FetchRequestFetchPartition {
+                    partition: 0,
+                    fetch_offset,
+                    partition_max_bytes: 1024 * 1024,
+                    ..Default::default()
+                }],
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fetch() {
+        let broker = MockBroker::start().await.unwrap();
+        broker.create_topic("topic", 1);
+        let conn = BrokerConnection::connect("test-client", broker.addr())
+            .await
+            .unwrap();
+        for values in [&[&b"a"[..], b"b"][..], &[b"c"]] {
+            conn.send(produce_request("topic", 0, values))
+                .await
+                .unwrap();
+        }
+        let topic_id = broker.topic_id("topic").unwrap();
+
+        // Fetching from the middle of a batch returns the whole batch
+        let resp = conn.send(fetch_request("", topic_id, 1)).await.unwrap();
+        let partition = &resp.responses[0].partitions[0];
+        assert_eq!(partition.error_code, ErrorCode::None);
+        assert_eq!(partition.high_watermark, 3);
+        let offsets: Vec<i64> = partition
+            .records
+            .0
+            .iter()
+            .map(|entry| match entry {
+                RecordSetEntry::Batch(batch) => batch.base_offset,
+                RecordSetEntry::Legacy(_) => panic!("legacy entry"),
+            })
+            .collect();
+        assert_eq!(offsets, [0, 2]);
+
+        let resp = conn.send(fetch_request("", topic_id, 3)).await.unwrap();
+        assert!(resp.responses[0].partitions[0].records.0.is_empty());
+        let resp = conn.send(fetch_request("", topic_id, 4)).await.unwrap();
+        assert_eq!(
+            resp.responses[0].partitions[0].error_code,
+            ErrorCode::OffsetOutOfRange
+        );
+
+        // Before version 13, topics are named
+        let resp = conn
+            .send_version(fetch_request("topic", Uuid::nil(), 2), ApiVersion(12))
+            .await
+            .unwrap();
+        assert_eq!(resp.responses[0].partitions[0].high_watermark, 3);
+        assert_eq!(resp.responses[0].partitions[0].records.0.len(), 1);
+
+        conn.send(DeleteTopicsReqV0 {
+            topic_names: vec!["topic".into()],
+            timeout_ms: 1000,
+        })
+        .await
+        .unwrap();
+        let resp = conn.send(fetch_request("", topic_id, 0)).await.unwrap();
+        assert_eq!(
+            resp.responses[0].partitions[0].error_code,
+            ErrorCode::UnknownTopicId
+        );
+    }
+
+    #[tokio::test]
+    async fn test_flexible_api_versions() {
+        let broker = MockBroker::start().await.unwrap();
+        let conn = BrokerConnection::connect("test-client", broker.addr())
+            .await
+            .unwrap();
+        let resp = conn.send(ApiVersionsRequest::default()).await.unwrap();
+        assert_eq!(resp.error_code, ErrorCode::None);
+        assert_eq!(resp.api_keys.len(), AnyRequest::VERSIONS.len());
+        assert_eq!(broker.requests()[1].0.api_version, ApiVersion(3));
+    }
+
+    #[tokio::test]
+    async fn test_scripted_error() {
+        let broker = MockBroker::start().await.unwrap();
+        broker.script(
+            ApiKey::CreateTopics,
+            Script::Error(ErrorCode::NotController),
+        );
+        let conn = BrokerConnection::connect("test-client", broker.addr())
+            .await
+            .unwrap();
+        let req = CreateTopicsReqV0 {
+            topics: vec![create_topic("topic", 1)],
+            timeout_ms: 1000,
+        };
+        let resp = conn.send(req.clone()).await.unwrap();
+        assert_eq!(resp.topics[0].err_code, ErrorCode::NotController);
+        assert!(broker.topics().is_empty());
+
+        // Scripts only apply once
+        let resp = conn.send(req).await.unwrap();
+        assert_eq!(resp.topics[0].err_code, ErrorCode::None);
+        assert_eq!(broker.topics(), ["topic"]);
+    }
+
+    #[tokio::test]
+    async fn test_scripted_delay() {
+        let broker = MockBroker::start().await.unwrap();
+        let delay = Duration::from_millis(200);
+        broker.script(ApiKey::Metadata, Script::Delay(delay));
+        let conn = BrokerConnection::connect("test-client", broker.addr())
+            .await
+            .unwrap();
+        let start = Instant::now();
+        conn.send(MetadataRequestV0 { topics: vec![] })
+            .await
+            .unwrap();
+        assert!(start.elapsed() >= delay);
+    }
+
+    #[tokio::test]
+    async fn test_scripted_disconnect() {
+        let broker = MockBroker::start().await.unwrap();
+        broker.script(ApiKey::Metadata, Script::Disconnect);
+        let conn = BrokerConnection::connect("test-client", broker.addr())
+            .await
+            .unwrap();
+        let err = conn
+            .send(MetadataRequestV0 { topics: vec![] })
+            .await
+            .unwrap_err();
+        assert!(matches!(err, FormatError::ConnectionClosed(_)), "{err}");
+
+        // The broker keeps accepting connections
+        let conn = BrokerConnection::connect("test-client", broker.addr())
+            .await
+            .unwrap();
+        conn.send(MetadataRequestV0 { topics: vec![] })
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,4 @@
+mod cluster;
+mod mock_broker;
+
+pub use mock_broker::{MockBroker, Script};